@@ -1,12 +1,28 @@
 use chess_engine::board::Board;
 use criterion::{criterion_group, criterion_main, Criterion};
 use chess_engine::eval::evaluate_board;
+use chess_engine::eval::params::EvalTables;
 
 fn run_eval_benchmark(c: &mut Criterion) {
+    let tables = EvalTables::default();
     let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
     c.bench_function("standard_board_evaluation", |b| {
         b.iter(|| {
-            evaluate_board(&board);
+            evaluate_board(&board, &tables);
+        })
+    });
+
+    let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    c.bench_function("kiwipete_board_evaluation", |b| {
+        b.iter(|| {
+            evaluate_board(&board, &tables);
+        })
+    });
+
+    let mut board = board;
+    c.bench_function("kiwipete_psqt_recalculation", |b| {
+        b.iter(|| {
+            board.recalculate_psqt();
         })
     });
 }
@@ -1,6 +1,7 @@
 use chess_engine::board::Board;
-use chess_engine::movegen::picker::MovePicker;
 use chess_engine::movegen::legal_check::is_other_king_attacked;
+use chess_engine::movegen::picker::MovePicker;
+use chess_engine::perft::{perft, perft_hashed, PerftTable};
 use criterion::{criterion_group, criterion_main, Criterion};
 
 fn count_legal_moves_recursive(board: &mut Board, depth: u8) -> u64 {
@@ -30,7 +31,20 @@ fn run_perft_benchmark(c: &mut Criterion) {
             assert_eq!(count_legal_moves_recursive(&mut board, 5), 4865609);
         })
     });
+
+    c.bench_function("standard_perft5_legal_generator", |b| {
+        b.iter(|| {
+            assert_eq!(perft(&mut board, 5), 4865609);
+        })
+    });
+
+    c.bench_function("standard_perft5_hashed", |b| {
+        b.iter(|| {
+            let mut table = PerftTable::new(16);
+            assert_eq!(perft_hashed(&mut board, 5, &mut table), 4865609);
+        })
+    });
 }
 
 criterion_group!(benches, run_perft_benchmark);
-criterion_main!(benches);
\ No newline at end of file
+criterion_main!(benches);
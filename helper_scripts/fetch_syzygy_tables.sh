@@ -0,0 +1,13 @@
+#!/bin/sh
+# Downloads the Syzygy tables probed by tests/syzygy.rs into tests/data/syzygy.
+set -e
+
+url="https://tablebase.lichess.ovh/tables/standard/3-4-5"
+dir="$(dirname "$0")/../tests/data/syzygy"
+mkdir -p "$dir"
+
+for table in KQvK KRvK KPvK KNvKN KRvKR KPvKP KPvKR KQRvK KRPvK KRPvKR; do
+    for extension in rtbw rtbz; do
+        curl -fsSL -o "$dir/$table.$extension" "$url/$table.$extension"
+    done
+done
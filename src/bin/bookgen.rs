@@ -0,0 +1,113 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::time::Instant;
+use chess_engine::book::builder::BookBuilder;
+use chess_engine::pgn::{PgnError, PgnReader};
+
+const USAGE: &str = "usage: bookgen <output.bin> [options] <pgn files...>
+
+Builds a Polyglot opening book from the games in the PGN files. Moves are
+weighted by their results (2 per win, 1 per draw) for the side that played them.
+
+  --max-ply N         moves counted per game (20)
+  --min-count N       drop moves played less than N times (3)
+  --min-score PCT     drop moves that scored less than PCT percent (0)";
+
+struct Options {
+    output: String,
+    inputs: Vec<String>,
+    max_ply: usize,
+    min_count: u32,
+    min_score: f64,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut options = Options { output: String::new(), inputs: Vec::new(), max_ply: 20, min_count: 3, min_score: 0.0 };
+
+    fn number<T: std::str::FromStr>(value: Option<&String>, flag: &str) -> Result<T, String> {
+        value.and_then(|v| v.parse().ok()).ok_or(format!("invalid value for {}", flag))
+    }
+
+    let mut i = 0;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        match flag {
+            "--max-ply" => options.max_ply = number(args.get(i + 1), flag)?,
+            "--min-count" => options.min_count = number(args.get(i + 1), flag)?,
+            "--min-score" => options.min_score = number::<f64>(args.get(i + 1), flag)? / 100.0,
+            "-h" | "--help" => return Err(String::new()),
+            _ if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ => {
+                if options.output.is_empty() {
+                    options.output = args[i].clone();
+                } else {
+                    options.inputs.push(args[i].clone());
+                }
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+
+    if options.output.is_empty() || options.inputs.is_empty() {
+        return Err("missing output or PGN files".to_string());
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(1);
+    });
+
+    let start = Instant::now();
+    let mut builder = BookBuilder::new(options.max_ply);
+    let (mut skipped, mut bad_games) = (0, 0);
+    for path in &options.inputs {
+        let file = File::open(path).unwrap_or_else(|e| {
+            eprintln!("unable to open {}: {}", path, e);
+            std::process::exit(1);
+        });
+        for game in PgnReader::new(BufReader::new(file)) {
+            let game = match game {
+                Ok(game) => game,
+                Err(e @ PgnError::Io(_)) => {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                }
+                Err(_) => {
+                    bad_games += 1;
+                    continue;
+                }
+            };
+            let Some(result) = game.game_result() else {
+                skipped += 1;
+                continue;
+            };
+            let Ok(board) = game.start_board() else {
+                bad_games += 1;
+                continue;
+            };
+            builder.add_game(&board, &game.mainline(), result);
+        }
+    }
+
+    let book = builder.build(options.min_count, options.min_score);
+    if let Err(e) = fs::write(&options.output, book.to_bytes()) {
+        eprintln!("unable to write {}: {}", options.output, e);
+        std::process::exit(1);
+    }
+    println!(
+        "{} games ({} without result skipped, {} unreadable), {} of {} moves kept, {:.1}s",
+        builder.games(),
+        skipped,
+        bad_games,
+        book.len(),
+        builder.len(),
+        start.elapsed().as_secs_f64()
+    );
+}
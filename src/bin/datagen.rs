@@ -0,0 +1,306 @@
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use chess_engine::board::{Board, Color};
+use chess_engine::datagen::{is_insufficient_material, GameResult, TrainingPosition};
+use chess_engine::engine::Engine;
+use chess_engine::eval::params::EvalParams;
+use chess_engine::movegen::legal_check::is_current_king_attacked;
+use chess_engine::r#move::{Move, MOVE_FLAG_CAPTURE, MOVE_FLAG_EN_PASSANT, MOVE_MASK_PROMO};
+
+const USAGE: &str = "usage: datagen <output file> [options]
+
+  --games N           games to play (1000)
+  --threads N         parallel games (1)
+  --nodes N           nodes per search (5000)
+  --hash MB           transposition table size per thread (16)
+  --random-plies N    random moves at the start of each game (8)
+  --format text|binary
+                      text lines '<fen> | <score> | <result>' (default) or 32 byte records
+  --seed N            base seed of the random openings (time based)
+  --win-score CP      adjudicate a win once |score| >= CP ... (1000)
+  --win-plies N       ... for N plies in a row (4)
+  --draw-score CP     adjudicate a draw once |score| <= CP ... (10)
+  --draw-plies N      ... for N plies in a row (12)
+  --draw-after PLY    ... but not before ply PLY (80)
+  --max-plies N       declare a draw after N plies (400)
+  --eval-params FILE  eval parameters for the searches";
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone)]
+struct Options {
+    output: String,
+    games: usize,
+    threads: usize,
+    nodes: u64,
+    hash: usize,
+    random_plies: usize,
+    binary: bool,
+    seed: u64,
+    win_score: i32,
+    win_plies: usize,
+    draw_score: i32,
+    draw_plies: usize,
+    draw_after: usize,
+    max_plies: usize,
+    eval_params: Option<String>,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let time_seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64);
+    let mut options = Options {
+        output: String::new(),
+        games: 1000,
+        threads: 1,
+        nodes: 5000,
+        hash: 16,
+        random_plies: 8,
+        binary: false,
+        seed: time_seed,
+        win_score: 1000,
+        win_plies: 4,
+        draw_score: 10,
+        draw_plies: 12,
+        draw_after: 80,
+        max_plies: 400,
+        eval_params: None,
+    };
+
+    fn number<T: std::str::FromStr>(value: Option<&String>, flag: &str) -> Result<T, String> {
+        value.and_then(|v| v.parse().ok()).ok_or(format!("invalid value for {}", flag))
+    }
+
+    let mut i = 0;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = args.get(i + 1);
+        match flag {
+            "--games" => options.games = number(value, flag)?,
+            "--threads" => options.threads = number(value, flag)?,
+            "--nodes" => options.nodes = number(value, flag)?,
+            "--hash" => options.hash = number(value, flag)?,
+            "--random-plies" => options.random_plies = number(value, flag)?,
+            "--seed" => options.seed = number(value, flag)?,
+            "--win-score" => options.win_score = number(value, flag)?,
+            "--win-plies" => options.win_plies = number(value, flag)?,
+            "--draw-score" => options.draw_score = number(value, flag)?,
+            "--draw-plies" => options.draw_plies = number(value, flag)?,
+            "--draw-after" => options.draw_after = number(value, flag)?,
+            "--max-plies" => options.max_plies = number(value, flag)?,
+            "--format" => match value.map(String::as_str) {
+                Some("text") => options.binary = false,
+                Some("binary") => options.binary = true,
+                _ => return Err("--format must be text or binary".to_string()),
+            },
+            "--eval-params" => options.eval_params = Some(value.cloned().ok_or("missing value for --eval-params")?),
+            path if options.output.is_empty() && !path.starts_with("--") => {
+                options.output = path.to_string();
+                i += 1;
+                continue;
+            }
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+        i += 2;
+    }
+
+    if options.output.is_empty() {
+        return Err("no output file given".to_string());
+    }
+    if options.threads == 0 || options.nodes == 0 {
+        return Err("--threads and --nodes must be positive".to_string());
+    }
+    Ok(options)
+}
+
+struct Xorshift {
+    state: u64,
+}
+
+impl Xorshift {
+    fn new(seed: u64) -> Self {
+        // Never zero, otherwise the generator gets stuck
+        Self { state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1 }
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % n as u64) as usize
+    }
+}
+
+
+fn is_tactical(mv: Move) -> bool {
+    let flags = mv.get_flags();
+    flags == MOVE_FLAG_CAPTURE || flags == MOVE_FLAG_EN_PASSANT || (flags & MOVE_MASK_PROMO) != 0
+}
+
+/// Plays `random_plies` random legal moves from the start position; `None` if the game ended.
+fn random_opening(rng: &mut Xorshift, random_plies: usize) -> Option<Board> {
+    let mut board = Board::from_fen(START_FEN);
+    for _ in 0..random_plies {
+        let moves = board.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        board.make_move(moves[rng.below(moves.len())]);
+    }
+    (!board.legal_moves().is_empty()).then_some(board)
+}
+
+/// Plays one self-play game and returns its quiet positions labelled with the final result.
+fn play_game(engine: &mut Engine, options: &Options, rng: &mut Xorshift) -> Vec<TrainingPosition> {
+    let mut board = loop {
+        if let Some(board) = random_opening(rng, options.random_plies) {
+            break board;
+        }
+    };
+    engine.tt.clear();
+
+    let mut positions: Vec<(Board, i16)> = Vec::new();
+    let mut history = vec![board.hash];
+    let (mut win_streak, mut draw_streak) = (0, 0);
+    let mut last_sign = 0;
+    let mut ply = 0;
+
+    let result = loop {
+        if board.legal_moves().is_empty() {
+            break match (is_current_king_attacked(&board), board.side_to_move) {
+                (true, Color::White) => GameResult::BlackWin,
+                (true, Color::Black) => GameResult::WhiteWin,
+                (false, _) => GameResult::Draw,
+            };
+        }
+        let repetitions = history.iter().filter(|&&h| h == board.hash).count();
+        if board.halfmove_clock >= 100 || repetitions >= 3 || is_insufficient_material(&board) || ply >= options.max_plies {
+            break GameResult::Draw;
+        }
+
+        engine.board = board;
+        let (Some(mv), score) = engine.search_nodes(options.nodes) else {
+            break GameResult::Draw;
+        };
+        let white_score = if board.side_to_move == Color::White { score } else { -score };
+
+        // Adjudication
+        let sign = white_score.signum();
+        if white_score.abs() >= options.win_score && sign == last_sign {
+            win_streak += 1;
+        } else {
+            win_streak = (white_score.abs() >= options.win_score) as usize;
+        }
+        last_sign = sign;
+        if win_streak >= options.win_plies {
+            break if white_score > 0 { GameResult::WhiteWin } else { GameResult::BlackWin };
+        }
+        draw_streak = if white_score.abs() <= options.draw_score { draw_streak + 1 } else { 0 };
+        if ply >= options.draw_after && draw_streak >= options.draw_plies {
+            break GameResult::Draw;
+        }
+
+        // Only quiet positions are useful labels for a static eval
+        if !is_current_king_attacked(&board) && !is_tactical(mv) && white_score.abs() < options.win_score {
+            positions.push((board, white_score as i16));
+        }
+
+        board.make_move(mv);
+        if board.halfmove_clock == 0 {
+            history.clear();
+        }
+        history.push(board.hash);
+        ply += 1;
+    };
+
+    positions
+        .into_iter()
+        .map(|(board, score)| TrainingPosition { board, score, result })
+        .collect()
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(1);
+    });
+
+    let file = File::create(&options.output).unwrap_or_else(|e| {
+        eprintln!("unable to create {}: {}", options.output, e);
+        std::process::exit(1);
+    });
+    let writer = Mutex::new(BufWriter::new(file));
+
+    let params = options.eval_params.as_ref().map(|path| {
+        EvalParams::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    });
+
+    let next_game = AtomicUsize::new(0);
+    let total_positions = AtomicUsize::new(0);
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for thread_id in 0..options.threads {
+            let (options, params, writer, next_game, total_positions) = (&options, &params, &writer, &next_game, &total_positions);
+            scope.spawn(move || {
+                let mut engine = Engine::with_hash("Yakari".to_string(), "EiSiMo".to_string(), options.hash);
+                if let Some(params) = params {
+                    engine.set_eval_params(params.clone());
+                }
+                let mut rng = Xorshift::new(options.seed.wrapping_add(thread_id as u64));
+
+                loop {
+                    let game = next_game.fetch_add(1, Ordering::Relaxed);
+                    if game >= options.games {
+                        break;
+                    }
+                    let positions = play_game(&mut engine, options, &mut rng);
+
+                    let mut out = writer.lock().unwrap();
+                    for position in &positions {
+                        let written = if options.binary {
+                            out.write_all(&position.to_bytes())
+                        } else {
+                            writeln!(out, "{}", position.to_text())
+                        };
+                        written.unwrap_or_else(|e| {
+                            eprintln!("unable to write {}: {}", options.output, e);
+                            std::process::exit(1);
+                        });
+                    }
+                    drop(out);
+
+                    let total = total_positions.fetch_add(positions.len(), Ordering::Relaxed) + positions.len();
+                    if (game + 1) % 100 == 0 {
+                        let seconds = start.elapsed().as_secs_f64();
+                        eprintln!(
+                            "games {:>7}  positions {:>9}  ({:.0} positions/s)",
+                            game + 1,
+                            total,
+                            total as f64 / seconds.max(0.001)
+                        );
+                    }
+                }
+            });
+        }
+    });
+
+    writer.lock().unwrap().flush().unwrap_or_else(|e| {
+        eprintln!("unable to write {}: {}", options.output, e);
+        std::process::exit(1);
+    });
+    eprintln!(
+        "done: {} games, {} positions in {:.1}s",
+        options.games,
+        total_positions.load(Ordering::Relaxed),
+        start.elapsed().as_secs_f64()
+    );
+}
@@ -0,0 +1,136 @@
+use std::env;
+use std::time::Instant;
+use chess_engine::board::Board;
+use chess_engine::epd::Epd;
+use chess_engine::perft::divide;
+
+const USAGE: &str = "usage: perft [<fen>] [options]
+       perft --epd <suite.epd> [options]
+
+Counts the leaf nodes of the move tree below a position (the start position by
+default) and prints the count below every root move. With --epd, verifies the
+D1, D2, ... counts of every position of a perft suite instead.
+
+  --depth N           depth to count (5)
+  --threads N         threads sharing the root moves (1)
+  --hash MB           table size per thread, 0 to count without one (16)
+  --epd FILE          perft suite to verify
+  --max-depth N       skip suite counts deeper than N";
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+struct Options {
+    fen: String,
+    depth: u8,
+    threads: usize,
+    hash: usize,
+    epd: Option<String>,
+    max_depth: u8,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut options = Options { fen: String::new(), depth: 5, threads: 1, hash: 16, epd: None, max_depth: u8::MAX };
+    let mut fen_fields = Vec::new();
+
+    fn number<T: std::str::FromStr>(value: Option<&String>, flag: &str) -> Result<T, String> {
+        value.and_then(|v| v.parse().ok()).ok_or(format!("invalid value for {}", flag))
+    }
+
+    let mut i = 0;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = args.get(i + 1);
+        match flag {
+            "--depth" => options.depth = number(value, flag)?,
+            "--threads" => options.threads = number(value, flag)?,
+            "--hash" => options.hash = number(value, flag)?,
+            "--epd" => options.epd = Some(value.cloned().ok_or("missing value for --epd")?),
+            "--max-depth" => options.max_depth = number(value, flag)?,
+            "-h" | "--help" => return Err(String::new()),
+            // The FEN may come as one argument or as its separate fields
+            field if !field.starts_with("--") => {
+                fen_fields.push(field.to_string());
+                i += 1;
+                continue;
+            }
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+        i += 2;
+    }
+
+    if options.threads == 0 {
+        return Err("--threads must be positive".to_string());
+    }
+    if options.epd.is_some() && !fen_fields.is_empty() {
+        return Err("give either a FEN or --epd".to_string());
+    }
+    options.fen = if fen_fields.is_empty() { START_FEN.to_string() } else { fen_fields.join(" ") };
+    Ok(options)
+}
+
+fn run_position(options: &Options) {
+    let board = Board::try_from_fen(&options.fen).unwrap_or_else(|e| {
+        eprintln!("invalid FEN: {}", e);
+        std::process::exit(1);
+    });
+
+    let start = Instant::now();
+    let counts = divide(&board, options.depth, options.threads, options.hash);
+    let elapsed = start.elapsed().as_secs_f64();
+    for (mv, nodes) in &counts {
+        println!("{}: {}", mv.to_algebraic(), nodes);
+    }
+    let nodes: u64 = if options.depth == 0 { 1 } else { counts.iter().map(|(_, n)| n).sum() };
+    println!();
+    println!("Nodes searched: {}", nodes);
+    println!("{:.3}s, {:.0} nodes/s", elapsed, nodes as f64 / elapsed.max(1e-9));
+}
+
+fn run_suite(path: &str, options: &Options) {
+    let suite = Epd::load(path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let start = Instant::now();
+    let (mut passed, mut failed) = (0, 0);
+    for epd in &suite {
+        let name = epd.id().unwrap_or(&epd.position);
+        let counts = epd.perft_counts();
+        if counts.is_empty() {
+            println!("{}: no D1, D2, ... counts", name);
+            failed += 1;
+            continue;
+        }
+        let board = epd.board();
+        for (depth, expected) in counts.into_iter().filter(|&(depth, _)| depth <= options.max_depth) {
+            let nodes: u64 = divide(&board, depth, options.threads, options.hash).iter().map(|(_, n)| n).sum();
+            if nodes == expected {
+                println!("{} D{} {} ok", name, depth, nodes);
+                passed += 1;
+            } else {
+                println!("{} D{} {} FAILED, expected {}", name, depth, nodes, expected);
+                failed += 1;
+            }
+        }
+    }
+
+    println!();
+    println!("{} passed, {} failed in {:.1}s", passed, failed, start.elapsed().as_secs_f64());
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(1);
+    });
+
+    match &options.epd {
+        Some(path) => run_suite(path, &options),
+        None => run_position(&options),
+    }
+}
@@ -1,59 +1,198 @@
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
 use chess_engine::engine::Engine;
-use std::time::{Instant, Duration};
+use chess_engine::epd::Epd;
+use chess_engine::eval::params::EvalParams;
 
-fn load_csv(path: &str) -> io::Result<Vec<Vec<String>>> {
-    let file = File::open(path)?;
-    let reader = io::BufReader::new(file);
+const USAGE: &str = "usage: suite <suite.epd> [options]
 
-    let mut rows = Vec::new();
+Searches every position of an EPD test suite and scores the moves found. STS
+positions score by their c8/c9 point tables (subscores per theme), others
+score full points for a bm move that is not an am move.
 
-    for line in reader.lines() {
-        let line = line?;
-        let cols = line
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .collect::<Vec<String>>();
+  --time MS           time per position (1000 unless --depth or --nodes is given)
+  --depth N           depth limit per position
+  --nodes N           node limit per position
+  --threads N         positions searched in parallel (1)
+  --hash MB           transposition table size per thread (64)
+  --eval-params FILE  eval parameters for the searches
+  --verbose           print every position's move and points";
 
-        rows.push(cols);
+struct Options {
+    suite: String,
+    time: Option<u64>,
+    depth: Option<u8>,
+    nodes: Option<u64>,
+    threads: usize,
+    hash: usize,
+    eval_params: Option<String>,
+    verbose: bool,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut options =
+        Options { suite: String::new(), time: None, depth: None, nodes: None, threads: 1, hash: 64, eval_params: None, verbose: false };
+
+    fn number<T: std::str::FromStr>(value: Option<&String>, flag: &str) -> Result<T, String> {
+        value.and_then(|v| v.parse().ok()).ok_or(format!("invalid value for {}", flag))
+    }
+
+    let mut i = 0;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = args.get(i + 1);
+        match flag {
+            "--time" => options.time = Some(number(value, flag)?),
+            "--depth" => options.depth = Some(number(value, flag)?),
+            "--nodes" => options.nodes = Some(number(value, flag)?),
+            "--threads" => options.threads = number(value, flag)?,
+            "--hash" => options.hash = number(value, flag)?,
+            "--eval-params" => options.eval_params = Some(value.cloned().ok_or("missing value for --eval-params")?),
+            "--verbose" => {
+                options.verbose = true;
+                i += 1;
+                continue;
+            }
+            "-h" | "--help" => return Err(String::new()),
+            path if options.suite.is_empty() && !path.starts_with("--") => {
+                options.suite = path.to_string();
+                i += 1;
+                continue;
+            }
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+        i += 2;
     }
-    Ok(rows)
+
+    if options.suite.is_empty() {
+        return Err("no suite file given".to_string());
+    }
+    if options.threads == 0 {
+        return Err("--threads must be positive".to_string());
+    }
+    if options.time.is_none() && options.depth.is_none() && options.nodes.is_none() {
+        options.time = Some(1000);
+    }
+    Ok(options)
 }
 
-fn main() {
-    let time_limit_ms = 1000_u64;
-    let time_limit = Duration::from_millis(time_limit_ms);
+/// "STS(v1.0) Undermine.001" is in theme "Undermine"; positions without an id are "Other".
+fn theme(epd: &Epd) -> String {
+    let Some(id) = epd.id() else { return "Other".to_string() };
+    let name = id.split_once(") ").map_or(id, |(_, name)| name);
+    name.rsplit_once('.').map_or(name, |(theme, number)| if number.chars().all(|c| c.is_ascii_digit()) { theme } else { name }).to_string()
+}
+
+#[derive(Default)]
+struct Score {
+    positions: usize,
+    solved: usize,
+    points: u32,
+    max_points: u32,
+}
+
+impl Score {
+    fn print(&self, name: &str) {
+        println!(
+            "{:<40} {:>6} / {:<6} {:>5.1}%   {:>4} / {:<4} best",
+            name,
+            self.points,
+            self.max_points,
+            100.0 * self.points as f64 / self.max_points.max(1) as f64,
+            self.solved,
+            self.positions
+        );
+    }
+}
 
-    let mut total_tests: f32 = 0.0;
-    let mut correct_tests: f32 = 0.0;
-    let sts = load_csv("src/bin/stockfish_testsuite.csv").unwrap();
-    let mut engine = Engine::new("Yakari".to_string(), "EiSiMo".to_string());
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(1);
+    });
 
-    for test in &sts {
-        let fen = &test[0];
-        let bm = &test[1];
+    let suite = Epd::load(&options.suite).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
 
-        engine.setpos_fen(fen);
+    let params = options.eval_params.as_ref().map(|path| {
+        EvalParams::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    });
 
-        let start_time = Instant::now();
+    // Points of every position, filled in by the threads
+    let results = Mutex::new(vec![0; suite.len()]);
+    let next_position = AtomicUsize::new(0);
+    let start = Instant::now();
 
-        let result = engine.search(time_limit_ms-5);
+    thread::scope(|scope| {
+        for _ in 0..options.threads {
+            let (options, params, suite, results, next_position) = (&options, &params, &suite, &results, &next_position);
+            scope.spawn(move || {
+                let mut engine = Engine::with_hash("Yakari".to_string(), "EiSiMo".to_string(), options.hash);
+                if let Some(params) = params {
+                    engine.set_eval_params(params.clone());
+                }
+                loop {
+                    let index = next_position.fetch_add(1, Ordering::Relaxed);
+                    let Some(epd) = suite.get(index) else { break };
 
-        let duration = start_time.elapsed();
-        if duration > time_limit {
-            panic!(
-                "Test exceeded 1 second limit: {:?} for FEN: {}",
-                duration, fen
-            );
+                    engine.tt.clear();
+                    engine.board = epd.board();
+                    let (best_move, _) = engine.search_limits(
+                        options.time.unwrap_or(u64::MAX),
+                        options.depth.unwrap_or(u8::MAX),
+                        options.nodes.unwrap_or(u64::MAX),
+                    );
+                    let (points, san) = match best_move {
+                        Some(mv) => match epd.points(mv) {
+                            Ok(points) => (points, mv.to_san(&engine.board)),
+                            Err(e) => {
+                                eprintln!("{}: {}", epd.id().unwrap_or(&epd.position), e);
+                                (0, mv.to_san(&engine.board))
+                            }
+                        },
+                        None => (0, "none".to_string()),
+                    };
+                    if options.verbose {
+                        println!("{:<40} {:<8} {:>4} / {}", epd.id().unwrap_or(&epd.position), san, points, epd.max_points());
+                    }
+                    results.lock().unwrap()[index] = points;
+                }
+            });
         }
+    });
 
-        total_tests += 1.0;
-        if result == *bm {
-            correct_tests += 1.0;
+    // Themes in the order of the suite
+    let mut themes: Vec<(String, Score)> = Vec::new();
+    let mut total = Score::default();
+    for (epd, points) in suite.iter().zip(results.into_inner().unwrap()) {
+        let name = theme(epd);
+        let index = themes.iter().position(|(n, _)| *n == name).unwrap_or_else(|| {
+            themes.push((name, Score::default()));
+            themes.len() - 1
+        });
+        for score in [&mut themes[index].1, &mut total] {
+            score.positions += 1;
+            score.solved += (points == epd.max_points()) as usize;
+            score.points += points;
+            score.max_points += epd.max_points();
         }
-
     }
 
-    println!("{}", correct_tests / (total_tests / 100.0));
+    if options.verbose {
+        println!();
+    }
+    for (name, score) in &themes {
+        score.print(name);
+    }
+    total.print("Total");
+    println!("{} positions in {:.1}s", suite.len(), start.elapsed().as_secs_f64());
 }
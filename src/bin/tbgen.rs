@@ -0,0 +1,97 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use chess_engine::dtm::generate::{all_codes, generate, generation_order};
+use chess_engine::dtm::{DtmTable, DtmTables, DTM_EXTENSION, MAX_PIECES};
+
+const USAGE: &str = "usage: tbgen <output dir> [options] [endings...]
+
+Solves endings by retrograde analysis and writes one distance to mate table per
+ending (e.g. KRvKB.ykdtm). Without endings all of them with 3 to 4 pieces are
+built. Tables needed for captures and promotions are built first.
+
+  --force             rebuild tables that already exist in the output dir";
+
+struct Options {
+    output: PathBuf,
+    codes: Vec<String>,
+    force: bool,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options { output: PathBuf::new(), codes: Vec::new(), force: false };
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--force" => options.force = true,
+            "-h" | "--help" => return Err(String::new()),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ if options.output.as_os_str().is_empty() => options.output = PathBuf::from(arg),
+            _ => options.codes.push(arg),
+        }
+    }
+    if options.output.as_os_str().is_empty() {
+        return Err("missing output dir".to_string());
+    }
+    if options.codes.is_empty() {
+        options.codes = all_codes(MAX_PIECES);
+    }
+    Ok(options)
+}
+
+fn table_path(dir: &Path, code: &str) -> PathBuf {
+    dir.join(format!("{}.{}", code, DTM_EXTENSION))
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(1);
+    });
+    let order = generation_order(&options.codes).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if let Err(e) = fs::create_dir_all(&options.output) {
+        eprintln!("unable to create {}: {}", options.output.display(), e);
+        std::process::exit(1);
+    }
+
+    let mut tables = DtmTables::from_tables(Vec::new());
+    let start = Instant::now();
+    for code in order {
+        let path = table_path(&options.output, &code);
+        if !options.force && path.exists() {
+            match DtmTable::load(&path) {
+                Ok(table) => {
+                    println!("{:<8} found {}", code, path.display());
+                    tables.insert(table);
+                    continue;
+                }
+                Err(e) => println!("{:<8} rebuilding, {}", code, e),
+            }
+        }
+
+        let table_start = Instant::now();
+        let (table, stats) = generate(&code, &tables).unwrap_or_else(|e| {
+            eprintln!("{}: {}", code, e);
+            std::process::exit(1);
+        });
+        if let Err(e) = table.save(&path) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        println!(
+            "{:<8} {:>10} positions {:>10} wins {:>10} draws {:>10} losses, longest mate {:>3} plies, {:.1}s",
+            code,
+            stats.positions,
+            stats.wins,
+            stats.draws,
+            stats.losses,
+            stats.longest,
+            table_start.elapsed().as_secs_f64()
+        );
+        tables.insert(table);
+    }
+    println!("done in {:.1}s", start.elapsed().as_secs_f64());
+}
@@ -0,0 +1,322 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead};
+use chess_engine::board::{Board, Color};
+use chess_engine::eval::evaluate_board;
+use chess_engine::eval::endgame::probe_endgame;
+use chess_engine::eval::params::{EvalParams, EvalTables, TUNABLE_COUNT};
+use chess_engine::eval::tune::{coefficients, is_eg_parameter, linear_eval, Coefficients};
+use chess_engine::movegen::legal_check::{is_current_king_attacked, is_other_king_attacked};
+use chess_engine::movegen::gen_type::GenType;
+use chess_engine::movegen::picker::MovePicker;
+use chess_engine::psqt::MAX_PHASE;
+
+const USAGE: &str = "usage: tune <data file> [--epochs N] [--lr RATE] [--limit N] [--params FILE] [--format rust|params] [--output FILE]
+
+--params starts from an eval parameter file instead of the built-in weights.
+--format selects Rust constants (default) or an eval parameter file for the EvalParamsFile option.
+
+Each line of the data file holds a FEN followed by a label, e.g.
+  <fen> [1.0]        game result from white's view (1.0, 0.5, 0.0)
+  <fen> \"1/2-1/2\"    game result as PGN token (1-0, 0-1, 1/2-1/2)
+  <fen> ; score 35   white relative search score in centipawns
+  <fen> | 35 | 1.0   datagen output, trained on the game result";
+
+struct Options {
+    data_path: String,
+    epochs: usize,
+    learning_rate: f64,
+    limit: usize,
+    params: Option<String>,
+    format: String,
+    output: Option<String>,
+}
+
+enum Label {
+    Result(f64),
+    Score(f64),
+}
+
+struct Sample {
+    coefficients: Coefficients,
+    label: Label,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut options = Options {
+        data_path: String::new(),
+        epochs: 2000,
+        learning_rate: 1.0,
+        limit: usize::MAX,
+        params: None,
+        format: "rust".to_string(),
+        output: None,
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        let value = |i: usize| args.get(i + 1).cloned().ok_or(format!("missing value for {}", args[i]));
+        match args[i].as_str() {
+            "--epochs" => { options.epochs = value(i)?.parse().map_err(|_| "invalid --epochs")?; i += 1; }
+            "--lr" => { options.learning_rate = value(i)?.parse().map_err(|_| "invalid --lr")?; i += 1; }
+            "--limit" => { options.limit = value(i)?.parse().map_err(|_| "invalid --limit")?; i += 1; }
+            "--params" => { options.params = Some(value(i)?); i += 1; }
+            "--format" => { options.format = value(i)?; i += 1; }
+            "--output" => { options.output = Some(value(i)?); i += 1; }
+            path if options.data_path.is_empty() && !path.starts_with("--") => options.data_path = path.to_string(),
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+        i += 1;
+    }
+
+    if options.data_path.is_empty() {
+        return Err("no data file given".to_string());
+    }
+    if options.format != "rust" && options.format != "params" {
+        return Err(format!("unknown format: {}", options.format));
+    }
+    Ok(options)
+}
+
+fn parse_result(token: &str) -> Option<f64> {
+    match token.trim_matches(|c| c == '[' || c == ']' || c == '"' || c == ';') {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        value => value.parse::<f64>().ok().filter(|v| (0.0..=1.0).contains(v)),
+    }
+}
+
+/// Splits a data line into its FEN and label.
+fn parse_line(line: &str) -> Option<(String, Label)> {
+    // datagen output: <fen> | <score> | <result>
+    if let [fen, _, result] = line.split('|').collect::<Vec<&str>>().as_slice() {
+        return Some((fen.trim().to_string(), Label::Result(parse_result(result.trim())?)));
+    }
+
+    if let Some((fen, rest)) = line.split_once(';').or_else(|| line.split_once('|')) {
+        let tokens: Vec<&str> = rest.split_whitespace().collect();
+        return match tokens.as_slice() {
+            ["score" | "cp", value, ..] => Some((fen.trim().to_string(), Label::Score(value.parse().ok()?))),
+            [value, ..] => Some((fen.trim().to_string(), Label::Result(parse_result(value)?))),
+            [] => None,
+        };
+    }
+
+    let (fen, label) = line.trim().rsplit_once(char::is_whitespace)?;
+    Some((fen.trim().to_string(), Label::Result(parse_result(label)?)))
+}
+
+fn relative_eval(board: &Board, tables: &EvalTables) -> i32 {
+    match board.side_to_move {
+        Color::White => evaluate_board(board, tables),
+        Color::Black => -evaluate_board(board, tables),
+    }
+}
+
+/// Capture-only search returning the score and the quiet leaf position of the principal variation.
+fn quiesce(board: &mut Board, tables: &EvalTables, mut alpha: i32, beta: i32, depth: u8) -> (i32, Board) {
+    let stand_pat = relative_eval(board, tables);
+    if stand_pat >= beta || depth == 0 {
+        return (stand_pat, *board);
+    }
+    let mut leaf = *board;
+    if stand_pat > alpha {
+        alpha = stand_pat;
+    }
+
+    let mut picker = MovePicker::with_gen_type(GenType::Captures);
+    while let Some(mv) = picker.next(board) {
+        let undo = board.make_move(mv);
+        if is_other_king_attacked(board) {
+            board.undo_move(undo);
+            continue;
+        }
+        let (score, child_leaf) = quiesce(board, tables, -beta, -alpha, depth - 1);
+        board.undo_move(undo);
+
+        if -score > alpha {
+            alpha = -score;
+            leaf = child_leaf;
+            if alpha >= beta {
+                break;
+            }
+        }
+    }
+    (alpha, leaf)
+}
+
+fn load_samples(options: &Options, tables: &EvalTables) -> io::Result<Vec<Sample>> {
+    let reader = io::BufReader::new(File::open(&options.data_path)?);
+    let mut samples = Vec::new();
+    let mut skipped = 0_usize;
+
+    for line in reader.lines() {
+        if samples.len() >= options.limit {
+            break;
+        }
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((fen, label)) = parse_line(&line) else {
+            skipped += 1;
+            continue;
+        };
+
+        let Ok(mut board) = Board::try_from_fen(&fen) else {
+            skipped += 1;
+            continue;
+        };
+        // Positions in check are never quiet, known endings bypass the tuned terms
+        if is_current_king_attacked(&board) || probe_endgame(&board, &tables.params).is_some() {
+            skipped += 1;
+            continue;
+        }
+        let (_, leaf) = quiesce(&mut board, tables, -i32::MAX, i32::MAX, 8);
+        // Captures can also end in a known ending
+        if probe_endgame(&leaf, &tables.params).is_some() {
+            skipped += 1;
+            continue;
+        }
+        samples.push(Sample {
+            coefficients: coefficients(&leaf, &tables.params),
+            label,
+        });
+    }
+
+    eprintln!("loaded {} positions ({} skipped)", samples.len(), skipped);
+    Ok(samples)
+}
+
+fn sigmoid(k: f64, score: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+fn target(sample: &Sample, k: f64) -> f64 {
+    match sample.label {
+        Label::Result(result) => result,
+        Label::Score(score) => sigmoid(k, score),
+    }
+}
+
+fn mean_error(samples: &[Sample], weights: &[f64], k: f64) -> f64 {
+    let total: f64 = samples
+        .iter()
+        .map(|s| {
+            let diff = target(s, k) - sigmoid(k, linear_eval(&s.coefficients, weights));
+            diff * diff
+        })
+        .sum();
+    total / samples.len() as f64
+}
+
+/// Finds the scaling constant K minimising the error of the current weights (ternary search).
+fn optimise_k(samples: &[Sample], weights: &[f64]) -> f64 {
+    let (mut low, mut high) = (0.05_f64, 4.0_f64);
+    for _ in 0..60 {
+        let m1 = low + (high - low) / 3.0;
+        let m2 = high - (high - low) / 3.0;
+        if mean_error(samples, weights, m1) < mean_error(samples, weights, m2) {
+            high = m2;
+        } else {
+            low = m1;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Full batch gradient of the mean squared sigmoid error.
+fn gradient(samples: &[Sample], weights: &[f64], k: f64, eg_mask: &[bool]) -> Vec<f64> {
+    let mut grad = vec![0.0; weights.len()];
+    let scale = k * std::f64::consts::LN_10 / 400.0;
+
+    for sample in samples {
+        let eval = linear_eval(&sample.coefficients, weights);
+        let s = sigmoid(k, eval);
+        let error_term = -2.0 * (target(sample, k) - s) * s * (1.0 - s) * scale;
+
+        let phase = sample.coefficients.phase as f64;
+        let mg_factor = phase / MAX_PHASE as f64;
+        let eg_factor = (1.0 - mg_factor) * sample.coefficients.eg_scale(weights);
+        for &(index, coef) in &sample.coefficients.entries {
+            let factor = if eg_mask[index as usize] { eg_factor } else { mg_factor };
+            grad[index as usize] += error_term * coef as f64 * factor;
+        }
+    }
+
+    let n = samples.len() as f64;
+    grad.iter_mut().for_each(|g| *g /= n);
+    grad
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    // The starting weights also drive quiescence search and the non-tunable king safety lookups
+    let start_params = match &options.params {
+        Some(path) => EvalParams::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => EvalParams::default(),
+    };
+    let start_tables = EvalTables::new(start_params.clone());
+
+    let samples = load_samples(&options, &start_tables).unwrap_or_else(|e| {
+        eprintln!("unable to read {}: {}", options.data_path, e);
+        std::process::exit(1);
+    });
+    if samples.is_empty() {
+        eprintln!("no usable positions in {}", options.data_path);
+        std::process::exit(1);
+    }
+
+    let mut weights: Vec<f64> = start_params.to_vector().iter().map(|&v| v as f64).collect();
+    let eg_mask: Vec<bool> = (0..TUNABLE_COUNT).map(is_eg_parameter).collect();
+
+    let k = optimise_k(&samples, &weights);
+    eprintln!("K = {:.4}, initial error = {:.6}", k, mean_error(&samples, &weights, k));
+
+    // Adam optimiser
+    let (beta1, beta2, epsilon) = (0.9, 0.999, 1e-8);
+    let mut m = vec![0.0; TUNABLE_COUNT];
+    let mut v = vec![0.0; TUNABLE_COUNT];
+
+    for epoch in 1..=options.epochs {
+        let grad = gradient(&samples, &weights, k, &eg_mask);
+        for i in 0..TUNABLE_COUNT {
+            m[i] = beta1 * m[i] + (1.0 - beta1) * grad[i];
+            v[i] = beta2 * v[i] + (1.0 - beta2) * grad[i] * grad[i];
+            let m_hat = m[i] / (1.0 - beta1.powi(epoch as i32));
+            let v_hat = v[i] / (1.0 - beta2.powi(epoch as i32));
+            weights[i] -= options.learning_rate * m_hat / (v_hat.sqrt() + epsilon);
+        }
+
+        if epoch % 100 == 0 || epoch == options.epochs {
+            eprintln!("epoch {:>6}: error = {:.6}", epoch, mean_error(&samples, &weights, k));
+        }
+    }
+
+    let mut tuned = start_params;
+    tuned.set_vector(&weights.iter().map(|w| w.round() as i32).collect::<Vec<i32>>());
+    let source = match options.format.as_str() {
+        "params" => tuned.to_text(),
+        _ => tuned.to_rust_source(),
+    };
+
+    match &options.output {
+        Some(path) => fs::write(path, source).unwrap_or_else(|e| {
+            eprintln!("unable to write {}: {}", path, e);
+            std::process::exit(1);
+        }),
+        None => print!("{}", source),
+    }
+}
@@ -1,27 +1,19 @@
 use crate::r#move::*;
+use crate::psqt::{PHASE_WEIGHTS, PSQT};
 use crate::square::Square;
 use crate::zobrist::{self, zobrist_keys};
 use std::ops::Not;
 
 pub const CASTLING_WK_FLAG: u8 = 1;
-pub const CASTLING_WK_MASK: u64 = 96;
-pub const CASTLING_WK_K_POS_MASK: u64 = 16;
-pub const CASTLING_WK_R_POS_MASK: u64 = 128;
-
 pub const CASTLING_WQ_FLAG: u8 = 2;
-pub const CASTLING_WQ_MASK: u64 = 14;
-pub const CASTLING_WQ_K_POS_MASK: u64 = 16;
-pub const CASTLING_WQ_R_POS_MASK: u64 = 1;
-
 pub const CASTLING_BK_FLAG: u8 = 4;
-pub const CASTLING_BK_MASK: u64 = 6917529027641081856;
-pub const CASTLING_BK_K_POS_MASK: u64 = 1152921504606846976;
-pub const CASTLING_BK_R_POS_MASK: u64 = 9223372036854775808;
-
 pub const CASTLING_BQ_FLAG: u8 = 8;
-pub const CASTLING_BQ_MASK: u64 = 1008806316530991104;
-pub const CASTLING_BQ_K_POS_MASK: u64 = 1152921504606846976;
-pub const CASTLING_BQ_R_POS_MASK: u64 = 72057594037927936;
+
+// Castling squares by right, in flag order (WK, WQ, BK, BQ). The king and rook land on the
+// same squares in Chess960 as in standard chess, only their start squares vary.
+pub const CASTLING_KING_TO: [Square; 4] = [Square::G1, Square::C1, Square::G8, Square::C8];
+pub const CASTLING_ROOK_TO: [Square; 4] = [Square::F1, Square::D1, Square::F8, Square::D8];
+pub const CASTLING_ROOKS_STANDARD: [Square; 4] = [Square::H1, Square::A1, Square::H8, Square::A8];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -73,12 +65,24 @@ pub struct Board {
     pub empty_squares: u64,
 
     pub castling_rights: u8,
+    // Start squares of the castling rooks by right, from the FEN
+    pub castling_rooks: [Square; 4],
     pub en_passant_target: Option<Square>,
 
     pub halfmove_clock: u8,
     pub fullmove_number: u16,
 
     pub hash: u64,
+    pub pawn_hash: u64,
+
+    // Running sums of the built-in PSQT + material (white relative) and game phase, see
+    // `recalculate_psqt`. `EvalTables::psqt_sums` reads them.
+    pub psqt_mg: i32,
+    pub psqt_eg: i32,
+    pub phase: i32,
+
+    // Write castling as king takes rook, see `Move::to_uci`
+    pub chess960: bool,
 }
 
 impl Board {
@@ -92,6 +96,7 @@ impl Board {
     pub fn recalculate_hash(&mut self) {
         let keys = zobrist_keys();
         let mut hash = 0;
+        let mut pawn_hash = 0;
 
         for sq in 0..64 {
             if let Some(pt) = self.pieces_on_squares[sq] {
@@ -101,6 +106,9 @@ impl Board {
                     Color::Black
                 };
                 hash ^= keys.pieces[zobrist::piece_index(pt, color)][sq];
+                if pt == PieceType::Pawn {
+                    pawn_hash ^= keys.pieces[zobrist::piece_index(pt, color)][sq];
+                }
             }
         }
         hash ^= keys.castling[self.castling_rights as usize];
@@ -111,18 +119,50 @@ impl Board {
         }
 
         self.hash = hash;
+        self.pawn_hash = pawn_hash;
     }
-    
+
+    pub fn recalculate_psqt(&mut self) {
+        self.psqt_mg = 0;
+        self.psqt_eg = 0;
+        self.phase = 0;
+
+        for pt in PIECE_TYPES {
+            for color in [Color::White, Color::Black] {
+                let sign = 1 - 2 * color as i32;
+                let mut pieces = self.pieces[pt as usize][color as usize];
+                while pieces != 0 {
+                    let sq = pieces.trailing_zeros() as usize;
+                    pieces &= pieces - 1;
+
+                    self.psqt_mg += sign * PSQT[pt as usize][color as usize][0][sq];
+                    self.psqt_eg += sign * PSQT[pt as usize][color as usize][1][sq];
+                    self.phase += PHASE_WEIGHTS[pt as usize];
+                }
+            }
+        }
+    }
+
     fn rm_piece(
         &mut self,
         target_square: Square,
         color: Color,
+        changes: &mut PieceChanges,
     ) -> PieceType {
         let target_square_bitboard = target_square.to_bitboard();
         let piece_type = self.pieces_on_squares[target_square as usize].unwrap();
 
         let keys = zobrist_keys();
-        self.hash ^= keys.pieces[zobrist::piece_index(piece_type, color)][target_square as usize];
+        let piece_key = keys.pieces[zobrist::piece_index(piece_type, color)][target_square as usize];
+        self.hash ^= piece_key;
+        if piece_type == PieceType::Pawn {
+            self.pawn_hash ^= piece_key;
+        }
+
+        let sign = 1 - 2 * color as i32;
+        self.psqt_mg -= sign * PSQT[piece_type as usize][color as usize][0][target_square as usize];
+        self.psqt_eg -= sign * PSQT[piece_type as usize][color as usize][1][target_square as usize];
+        self.phase -= PHASE_WEIGHTS[piece_type as usize];
 
         self.pieces[piece_type as usize][color as usize] ^= target_square_bitboard;
         self.pieces_on_squares[target_square as usize] = None;
@@ -131,14 +171,24 @@ impl Board {
         self.all_occupied ^= target_square_bitboard;
         self.empty_squares |= target_square_bitboard;
 
+        changes.push(PieceChange { piece_type, color, square: target_square, added: false });
         piece_type
     }
 
-    fn put_piece(&mut self, target_square: Square, color: Color, piece_type: PieceType) {
+    fn put_piece(&mut self, target_square: Square, color: Color, piece_type: PieceType, changes: &mut PieceChanges) {
         let target_square_bitboard = target_square.to_bitboard();
 
         let keys = zobrist_keys();
-        self.hash ^= keys.pieces[zobrist::piece_index(piece_type, color)][target_square as usize];
+        let piece_key = keys.pieces[zobrist::piece_index(piece_type, color)][target_square as usize];
+        self.hash ^= piece_key;
+        if piece_type == PieceType::Pawn {
+            self.pawn_hash ^= piece_key;
+        }
+
+        let sign = 1 - 2 * color as i32;
+        self.psqt_mg += sign * PSQT[piece_type as usize][color as usize][0][target_square as usize];
+        self.psqt_eg += sign * PSQT[piece_type as usize][color as usize][1][target_square as usize];
+        self.phase += PHASE_WEIGHTS[piece_type as usize];
 
         self.pieces[piece_type as usize][color as usize] |= target_square_bitboard;
         self.pieces_on_squares[target_square as usize] = Some(piece_type);
@@ -146,11 +196,39 @@ impl Board {
         self.occupied[color as usize] |= target_square_bitboard;
         self.all_occupied |= target_square_bitboard;
         self.empty_squares ^= target_square_bitboard;
+
+        changes.push(PieceChange { piece_type, color, square: target_square, added: true });
+    }
+
+    fn move_piece(&mut self, from: Square, to: Square, color: Color, changes: &mut PieceChanges) {
+        let piece_type = self.rm_piece(from, color, changes);
+        self.put_piece(to, color, piece_type, changes);
     }
 
-    fn move_piece(&mut self, from: Square, to: Square, color: Color) {
-        let piece_type = self.rm_piece(from, color);
-        self.put_piece(to, color, piece_type);
+    /// Castling rights that need a rook on `square`.
+    fn castling_rights_of(&self, square: Square) -> u8 {
+        let mut rights = 0;
+        for (right, &rook) in self.castling_rooks.iter().enumerate() {
+            rights |= ((rook == square) as u8) << right;
+        }
+        rights
+    }
+
+    /// Plays or takes back the castling move of the side to move whose king starts on `king`.
+    /// King and rook are both lifted before either is put down, as in Chess960 their start and
+    /// target squares may overlap.
+    fn castle(&mut self, king: Square, flags: u16, undo: bool, changes: &mut PieceChanges) {
+        let right = ((flags >> 12) & 0b11) as usize;
+        let color = self.side_to_move;
+        let (king_from, king_to, rook_from, rook_to) = if undo {
+            (CASTLING_KING_TO[right], king, CASTLING_ROOK_TO[right], self.castling_rooks[right])
+        } else {
+            (king, CASTLING_KING_TO[right], self.castling_rooks[right], CASTLING_ROOK_TO[right])
+        };
+        self.rm_piece(king_from, color, changes);
+        self.rm_piece(rook_from, color, changes);
+        self.put_piece(king_to, color, PieceType::King, changes);
+        self.put_piece(rook_to, color, PieceType::Rook, changes);
     }
 
     pub fn make_move(&mut self, mv: Move) -> UndoMove {
@@ -172,90 +250,77 @@ impl Board {
 
         let mut opt_captured_piece: Option<PieceType> = None;
         let mut opt_en_passant_target: Option<Square> = None;
+        let changes = &mut PieceChanges::default();
+
+        // Rights end when the king moves or a castling rook moves or is captured
+        let mut lost_castling_rights = 0;
+        if self.castling_rights != 0 {
+            lost_castling_rights = self.castling_rights_of(from) | self.castling_rights_of(to);
+            if self.pieces_on_squares[from as usize] == Some(PieceType::King) {
+                lost_castling_rights |= (CASTLING_WK_FLAG | CASTLING_WQ_FLAG) << (2 * self.side_to_move as u8);
+            }
+        }
 
         match flags {
             MOVE_FLAG_QUIET => {
-                self.move_piece(from, to, self.side_to_move);
+                self.move_piece(from, to, self.side_to_move, changes);
             }
             MOVE_FLAG_CAPTURE => {
-                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move));
-                self.move_piece(from, to, self.side_to_move);
+                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move, changes));
+                self.move_piece(from, to, self.side_to_move, changes);
             }
             MOVE_FLAG_DOUBLE_PAWN => {
-                self.move_piece(from, to, self.side_to_move);
+                self.move_piece(from, to, self.side_to_move, changes);
                 opt_en_passant_target = Some(to + (self.side_to_move as i8 * 16 - 8));
             }
             MOVE_FLAG_PROMO_Q => {
-                self.rm_piece(from, self.side_to_move);
-                self.put_piece(to, self.side_to_move, PieceType::Queen);
+                self.rm_piece(from, self.side_to_move, changes);
+                self.put_piece(to, self.side_to_move, PieceType::Queen, changes);
             }
             MOVE_FLAG_PROMO_N => {
-                self.rm_piece(from, self.side_to_move);
-                self.put_piece(to, self.side_to_move, PieceType::Knight);
+                self.rm_piece(from, self.side_to_move, changes);
+                self.put_piece(to, self.side_to_move, PieceType::Knight, changes);
             }
             MOVE_FLAG_PROMO_B => {
-                self.rm_piece(from, self.side_to_move);
-                self.put_piece(to, self.side_to_move, PieceType::Bishop);
+                self.rm_piece(from, self.side_to_move, changes);
+                self.put_piece(to, self.side_to_move, PieceType::Bishop, changes);
             }
             MOVE_FLAG_PROMO_R => {
-                self.rm_piece(from, self.side_to_move);
-                self.put_piece(to, self.side_to_move, PieceType::Rook);
+                self.rm_piece(from, self.side_to_move, changes);
+                self.put_piece(to, self.side_to_move, PieceType::Rook, changes);
             }
             MOVE_FLAG_PROMO_Q_CAP => {
-                self.rm_piece(from, self.side_to_move);
-                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move));
-                self.put_piece(to, self.side_to_move, PieceType::Queen);
+                self.rm_piece(from, self.side_to_move, changes);
+                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move, changes));
+                self.put_piece(to, self.side_to_move, PieceType::Queen, changes);
             }
             MOVE_FLAG_PROMO_N_CAP => {
-                self.rm_piece(from, self.side_to_move);
-                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move));
-                self.put_piece(to, self.side_to_move, PieceType::Knight);
+                self.rm_piece(from, self.side_to_move, changes);
+                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move, changes));
+                self.put_piece(to, self.side_to_move, PieceType::Knight, changes);
             }
             MOVE_FLAG_PROMO_B_CAP => {
-                self.rm_piece(from, self.side_to_move);
-                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move));
-                self.put_piece(to, self.side_to_move, PieceType::Bishop);
+                self.rm_piece(from, self.side_to_move, changes);
+                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move, changes));
+                self.put_piece(to, self.side_to_move, PieceType::Bishop, changes);
             }
             MOVE_FLAG_PROMO_R_CAP => {
-                self.rm_piece(from, self.side_to_move);
-                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move));
-                self.put_piece(to, self.side_to_move, PieceType::Rook);
-            }
-            MOVE_FLAG_WK_CASTLE => {
-                self.move_piece(Square::E1, Square::G1, self.side_to_move);
-                self.move_piece(Square::H1, Square::F1, self.side_to_move);
-            }
-            MOVE_FLAG_BK_CASTLE => {
-                self.move_piece(Square::E8, Square::G8, self.side_to_move);
-                self.move_piece(Square::H8, Square::F8, self.side_to_move);
-            }
-            MOVE_FLAG_WQ_CASTLE => {
-                self.move_piece(Square::E1, Square::C1, self.side_to_move);
-                self.move_piece(Square::A1, Square::D1, self.side_to_move);
+                self.rm_piece(from, self.side_to_move, changes);
+                opt_captured_piece = Some(self.rm_piece(to, !self.side_to_move, changes));
+                self.put_piece(to, self.side_to_move, PieceType::Rook, changes);
             }
-            MOVE_FLAG_BQ_CASTLE => {
-                self.move_piece(Square::E8, Square::C8, self.side_to_move);
-                self.move_piece(Square::A8, Square::D8, self.side_to_move);
+            MOVE_FLAG_WK_CASTLE | MOVE_FLAG_WQ_CASTLE | MOVE_FLAG_BK_CASTLE | MOVE_FLAG_BQ_CASTLE => {
+                self.castle(from, flags, false, changes);
             }
             MOVE_FLAG_EN_PASSANT => {
-                self.move_piece(from, to, self.side_to_move);
-                self.rm_piece(to + (self.side_to_move as i8 * 16 - 8), !self.side_to_move);
+                self.move_piece(from, to, self.side_to_move, changes);
+                self.rm_piece(to + (self.side_to_move as i8 * 16 - 8), !self.side_to_move, changes);
                 opt_captured_piece = Some(PieceType::Pawn);
             }
             _ => { panic!("unable to make_move: invalid flags: {}", flags); }
         }
 
-        let wk = self.pieces[PieceType::King as usize][Color::White as usize];
-        let wr = self.pieces[PieceType::Rook as usize][Color::White as usize];
-        let bk = self.pieces[PieceType::King as usize][Color::Black as usize];
-        let br = self.pieces[PieceType::Rook as usize][Color::Black as usize];
-        let castling_right_wk = ((wk & CASTLING_WK_K_POS_MASK) > 0 && (wr & CASTLING_WK_R_POS_MASK) > 0) as u8;
-        let castling_right_wq = (((wk & CASTLING_WQ_K_POS_MASK) > 0 && (wr & CASTLING_WQ_R_POS_MASK) > 0) as u8) << 1;
-        let castling_right_bk = (((bk & CASTLING_BK_K_POS_MASK) > 0 && (br & CASTLING_BK_R_POS_MASK) > 0) as u8) << 2;
-        let castling_right_bq = (((bk & CASTLING_BQ_K_POS_MASK) > 0 && (br & CASTLING_BQ_R_POS_MASK) > 0) as u8) << 3;
-        let new_castling_rights =
-            castling_right_wk | castling_right_wq | castling_right_bk | castling_right_bq;
-        self.castling_rights = self.castling_rights & new_castling_rights;
+        self.castling_rights &= !lost_castling_rights;
 
         self.en_passant_target = opt_en_passant_target;
 
@@ -280,6 +345,7 @@ impl Board {
             old_en_passant_target,
             old_castling_rights,
             old_halfmove_clock,
+            *changes,
         )
     }
 
@@ -305,47 +371,36 @@ impl Board {
         let from = mv.get_from();
         let to = mv.get_to();
         let flags = mv.get_flags();
+        // Only the search's accumulators need the changes, and it pops them instead
+        let changes = &mut PieceChanges::default();
 
         match flags {
             MOVE_FLAG_QUIET => {
-                self.move_piece(to, from, self.side_to_move);
+                self.move_piece(to, from, self.side_to_move, changes);
             }
             MOVE_FLAG_CAPTURE => {
-                self.move_piece(to, from, self.side_to_move);
-                self.put_piece(to, !self.side_to_move, undo_info.captured_piece.unwrap());
+                self.move_piece(to, from, self.side_to_move, changes);
+                self.put_piece(to, !self.side_to_move, undo_info.captured_piece.unwrap(), changes);
             }
             MOVE_FLAG_DOUBLE_PAWN => {
-                self.move_piece(to, from, self.side_to_move);
+                self.move_piece(to, from, self.side_to_move, changes);
             }
             MOVE_FLAG_PROMO_Q | MOVE_FLAG_PROMO_N | MOVE_FLAG_PROMO_B | MOVE_FLAG_PROMO_R => {
-                self.rm_piece(to, self.side_to_move);
-                self.put_piece(from, self.side_to_move, PieceType::Pawn);
+                self.rm_piece(to, self.side_to_move, changes);
+                self.put_piece(from, self.side_to_move, PieceType::Pawn, changes);
             }
             MOVE_FLAG_PROMO_Q_CAP | MOVE_FLAG_PROMO_N_CAP | MOVE_FLAG_PROMO_B_CAP | MOVE_FLAG_PROMO_R_CAP => {
-                self.rm_piece(to, self.side_to_move);
-                self.put_piece(from, self.side_to_move, PieceType::Pawn);
-                self.put_piece(to, !self.side_to_move, undo_info.captured_piece.unwrap());
-            }
-            MOVE_FLAG_WK_CASTLE => {
-                self.move_piece(Square::G1, Square::E1, self.side_to_move);
-                self.move_piece(Square::F1, Square::H1, self.side_to_move);
-            }
-            MOVE_FLAG_BK_CASTLE => {
-                self.move_piece(Square::G8, Square::E8, self.side_to_move);
-                self.move_piece(Square::F8, Square::H8, self.side_to_move);
-            }
-            MOVE_FLAG_WQ_CASTLE => {
-                self.move_piece(Square::C1, Square::E1, self.side_to_move);
-                self.move_piece(Square::D1, Square::A1, self.side_to_move);
+                self.rm_piece(to, self.side_to_move, changes);
+                self.put_piece(from, self.side_to_move, PieceType::Pawn, changes);
+                self.put_piece(to, !self.side_to_move, undo_info.captured_piece.unwrap(), changes);
             }
-            MOVE_FLAG_BQ_CASTLE => {
-                self.move_piece(Square::C8, Square::E8, self.side_to_move);
-                self.move_piece(Square::D8, Square::A8, self.side_to_move);
+            MOVE_FLAG_WK_CASTLE | MOVE_FLAG_WQ_CASTLE | MOVE_FLAG_BK_CASTLE | MOVE_FLAG_BQ_CASTLE => {
+                self.castle(from, flags, true, changes);
             }
             MOVE_FLAG_EN_PASSANT => {
-                self.move_piece(to, from, self.side_to_move);
+                self.move_piece(to, from, self.side_to_move, changes);
                 let captured_pawn_square = to + (self.side_to_move as i8 * 16 - 8);
-                self.put_piece(captured_pawn_square, !self.side_to_move, PieceType::Pawn);
+                self.put_piece(captured_pawn_square, !self.side_to_move, PieceType::Pawn, changes);
             }
             _ => { panic!("unable to unmake_move: invalid flags: {}", flags); }
         }
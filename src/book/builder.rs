@@ -0,0 +1,97 @@
+use crate::board::{Board, Color};
+use crate::datagen::GameResult;
+use crate::r#move::Move;
+use super::{encode_move, polyglot_key, BookEntry, PolyglotBook};
+use std::collections::HashMap;
+
+/// Results after a move, from the point of view of the side that played it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MoveStats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MoveStats {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, 0.0 to 1.0.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    /// Book weight before scaling: a win counts twice as much as a draw, losses nothing.
+    pub fn weight(&self) -> u64 {
+        2 * self.wins as u64 + self.draws as u64
+    }
+}
+
+/// Collects move statistics from finished games.
+pub struct BookBuilder {
+    max_ply: usize,
+    stats: HashMap<(u64, u16), MoveStats>,
+    games: usize,
+}
+
+impl BookBuilder {
+    /// Only the first `max_ply` moves of each game are counted.
+    pub fn new(max_ply: usize) -> Self {
+        BookBuilder { max_ply, stats: HashMap::new(), games: 0 }
+    }
+
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    /// Number of distinct position and move pairs seen.
+    pub fn len(&self) -> usize {
+        self.stats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+
+    pub fn stats(&self, board: &Board, mv: Move) -> Option<MoveStats> {
+        self.stats.get(&(polyglot_key(board), encode_move(mv, board))).copied()
+    }
+
+    /// Plays `moves` (assumed legal) from `start` and records them with the game's result.
+    pub fn add_game(&mut self, start: &Board, moves: &[Move], result: GameResult) {
+        let mut board = *start;
+        for &mv in moves.iter().take(self.max_ply) {
+            let stats = self.stats.entry((polyglot_key(&board), encode_move(mv, &board))).or_default();
+            match (result, board.side_to_move) {
+                (GameResult::Draw, _) => stats.draws += 1,
+                (GameResult::WhiteWin, Color::White) | (GameResult::BlackWin, Color::Black) => stats.wins += 1,
+                _ => stats.losses += 1,
+            }
+            board.make_move(mv);
+        }
+        self.games += 1;
+    }
+
+    /// The book of all moves played at least `min_count` times that scored at least
+    /// `min_score` (0.0 to 1.0); moves that never scored are left out. Weights are scaled down
+    /// to fit 16 bits when needed.
+    pub fn build(&self, min_count: u32, min_score: f64) -> PolyglotBook {
+        let kept: Vec<(u64, u16, u64)> = self
+            .stats
+            .iter()
+            .filter(|(_, s)| s.games() >= min_count && s.score() >= min_score && s.weight() > 0)
+            .map(|(&(key, raw_move), s)| (key, raw_move, s.weight()))
+            .collect();
+
+        let max_weight = kept.iter().map(|&(_, _, weight)| weight).max().unwrap_or(0);
+        let scale = |weight: u64| {
+            if max_weight <= u16::MAX as u64 { weight as u16 } else { (weight * u16::MAX as u64 / max_weight).max(1) as u16 }
+        };
+        PolyglotBook::from_entries(
+            kept.into_iter()
+                .map(|(key, raw_move, weight)| BookEntry { key, raw_move, weight: scale(weight), learn: 0 })
+                .collect(),
+        )
+    }
+}
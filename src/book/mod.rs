@@ -0,0 +1,233 @@
+pub mod builder;
+pub mod keys;
+
+use crate::board::{Board, Color, PieceType};
+use crate::movegen::tables::ATTACKING_PAWNS;
+use crate::r#move::{Move, MOVE_FLAG_CASTLE_TRUE, MOVE_FLAG_PROMO, MOVE_MASK_CASTLE};
+use keys::POLYGLOT_RANDOM;
+use std::fmt;
+use std::fs;
+
+// Polyglot opening books (.bin): 16 byte big endian entries sorted by key
+//   key u64, move u16, weight u16, learn u32
+// The move packs to file (bits 0-2), to rank (3-5), from file (6-8), from rank (9-11) and the
+// promotion piece (12-14: none, n, b, r, q). Castling is written as the king taking its rook.
+pub const ENTRY_SIZE: usize = 16;
+
+const RANDOM_CASTLING: usize = 768;
+const RANDOM_EN_PASSANT: usize = 772;
+const RANDOM_TURN: usize = 780;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookError {
+    Io(String),
+    BadSize(usize),
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "unable to read book: {}", e),
+            Self::BadSize(size) => write!(f, "book size {} is not a multiple of {} bytes", size, ENTRY_SIZE),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+/// How a move is chosen among the book moves of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BookSelection {
+    /// At random, in proportion to the weights.
+    #[default]
+    Weighted,
+    /// Always the highest weight.
+    Best,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    pub fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Self {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+/// The Polyglot hash of `board`. The en passant file only counts if a pawn can capture there.
+pub fn polyglot_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for pt in [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King] {
+        for color in [Color::White, Color::Black] {
+            let kind = 2 * pt as usize + if color == Color::White { 1 } else { 0 };
+            let mut pieces = board.pieces[pt as usize][color as usize];
+            while pieces != 0 {
+                key ^= POLYGLOT_RANDOM[64 * kind + pieces.trailing_zeros() as usize];
+                pieces &= pieces - 1;
+            }
+        }
+    }
+
+    // Our rights use the same order: K, Q, k, q
+    for i in 0..4 {
+        if board.castling_rights & (1 << i) != 0 {
+            key ^= POLYGLOT_RANDOM[RANDOM_CASTLING + i];
+        }
+    }
+
+    if let Some(target) = board.en_passant_target {
+        let pawns = board.pieces[PieceType::Pawn as usize][board.side_to_move as usize];
+        if pawns & ATTACKING_PAWNS[board.side_to_move as usize][target as usize] != 0 {
+            key ^= POLYGLOT_RANDOM[RANDOM_EN_PASSANT + target as usize % 8];
+        }
+    }
+
+    if board.side_to_move == Color::White {
+        key ^= POLYGLOT_RANDOM[RANDOM_TURN];
+    }
+    key
+}
+
+
+/// Promotion piece in Polyglot numbering: 0 none, 1 knight ... 4 queen.
+fn promotion_code(mv: Move) -> u16 {
+    let flags = mv.get_flags();
+    if flags & MOVE_FLAG_PROMO != 0 { ((flags >> 13) & 0b11) + 1 } else { 0 }
+}
+
+/// The legal move of `board` a book move stands for, `None` if there is none.
+pub fn decode_move(raw_move: u16, board: &mut Board) -> Option<Move> {
+    let from = ((raw_move >> 6) & 63) as usize;
+    let to = (raw_move & 63) as usize;
+    let promotion = (raw_move >> 12) & 7;
+
+    // King takes own rook means castling, also in Chess960 where the rooks can start anywhere
+    let us = board.side_to_move as usize;
+    let king = board.pieces[PieceType::King as usize][us];
+    let castling = (2 * us..2 * us + 2).find(|&right| {
+        king & (1 << from) != 0 && board.castling_rights & (1 << right) != 0 && board.castling_rooks[right] as usize == to
+    });
+
+    board.legal_moves().into_iter().find(|&mv| {
+        let castle = mv.get_flags() & MOVE_MASK_CASTLE == MOVE_FLAG_CASTLE_TRUE;
+        match castling {
+            Some(right) => castle && castling_right(mv) == right,
+            None => !castle && mv.get_from() as usize == from && mv.get_to() as usize == to && promotion_code(mv) == promotion,
+        }
+    })
+}
+
+/// The book encoding of `mv` on `board`, castling as the king taking its rook.
+pub fn encode_move(mv: Move, board: &Board) -> u16 {
+    let from = mv.get_from() as u16;
+    let to = if mv.get_flags() & MOVE_MASK_CASTLE == MOVE_FLAG_CASTLE_TRUE {
+        board.castling_rooks[castling_right(mv)] as u16
+    } else {
+        mv.get_to() as u16
+    };
+    promotion_code(mv) << 12 | from << 6 | to
+}
+
+/// Index of the castling right (in flag order) used by the castling move `mv`.
+fn castling_right(mv: Move) -> usize {
+    ((mv.get_flags() >> 12) & 0b11) as usize
+}
+
+/// A whole book held in memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolyglotBook {
+    entries: Vec<BookEntry>,
+}
+
+impl PolyglotBook {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BookError> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(BookError::BadSize(bytes.len()));
+        }
+        let entries = bytes.chunks_exact(ENTRY_SIZE).map(|chunk| BookEntry::from_bytes(chunk.try_into().unwrap())).collect();
+        Ok(Self::from_entries(entries))
+    }
+
+    /// Sorts `entries` into book order.
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Self {
+        entries.sort_by_key(|e| (e.key, std::cmp::Reverse(e.weight), e.raw_move));
+        PolyglotBook { entries }
+    }
+
+    pub fn load(path: &str) -> Result<Self, BookError> {
+        let bytes = fs::read(path).map_err(|e| BookError::Io(format!("{}: {}", path, e)))?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flat_map(BookEntry::to_bytes).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries of a position key, found by binary search.
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = start + self.entries[start..].partition_point(|e| e.key == key);
+        &self.entries[start..end]
+    }
+
+    /// Legal book moves of `board` with their weights, highest first.
+    pub fn moves(&self, board: &mut Board) -> Vec<(Move, u16)> {
+        let mut moves: Vec<(Move, u16)> = self
+            .entries(polyglot_key(board))
+            .iter()
+            .filter_map(|e| Some((decode_move(e.raw_move, board)?, e.weight)))
+            .collect();
+        moves.sort_by_key(|&(_, weight)| std::cmp::Reverse(weight));
+        moves
+    }
+
+    /// A book move for `board`; `random` picks among the weighted moves. Moves with weight 0
+    /// are never played.
+    pub fn pick(&self, board: &mut Board, selection: BookSelection, random: u64) -> Option<Move> {
+        let moves: Vec<(Move, u16)> = self.moves(board).into_iter().filter(|&(_, weight)| weight > 0).collect();
+        match selection {
+            BookSelection::Best => moves.first().map(|&(mv, _)| mv),
+            BookSelection::Weighted => {
+                let total: u64 = moves.iter().map(|&(_, weight)| weight as u64).sum();
+                if total == 0 {
+                    return None;
+                }
+                let mut target = random % total;
+                for (mv, weight) in moves {
+                    if target < weight as u64 {
+                        return Some(mv);
+                    }
+                    target -= weight as u64;
+                }
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::board::{Board, Color, PieceType, PIECE_TYPES};
+use crate::square::SQUARES;
+
+/// Size of one binary training record.
+pub const RECORD_SIZE: usize = 32;
+
+const PIECE_CHARS: [char; 12] = ['P', 'N', 'B', 'R', 'Q', 'K', 'p', 'n', 'b', 'r', 'q', 'k'];
+
+/// Game outcome from white's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum GameResult {
+    BlackWin = 0,
+    Draw = 1,
+    WhiteWin = 2,
+}
+
+impl GameResult {
+    /// 0.0, 0.5 or 1.0, the label used by the tuner.
+    pub fn as_score(self) -> f32 {
+        self as u8 as f32 / 2.0
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::BlackWin),
+            1 => Some(Self::Draw),
+            2 => Some(Self::WhiteWin),
+            _ => None,
+        }
+    }
+}
+
+/// A labelled training position.
+/// Binary layout (32 bytes, little endian):
+///   occupancy u64, one 4 bit piece code (color * 6 + piece type) per occupied square in
+///   ascending square order (16 bytes), white relative score i16, result u8, side to move u8,
+///   castling rights u8, en passant square u8 (64 = none), halfmove clock u8, fullmove number u8.
+/// The side to move byte carries bits 8 to 14 of the fullmove number above its lowest bit, the
+/// upper castling nibble flags rights whose rook is the innermost rather than the outermost one
+/// on its side of the king (Chess960 positions with both rooks on one side).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrainingPosition {
+    pub board: Board,
+    pub score: i16, // White relative centipawns
+    pub result: GameResult,
+}
+
+impl TrainingPosition {
+    pub fn to_bytes(&self) -> [u8; RECORD_SIZE] {
+        let board = &self.board;
+        let mut bytes = [0_u8; RECORD_SIZE];
+        bytes[0..8].copy_from_slice(&board.all_occupied.to_le_bytes());
+
+        let mut occupied = board.all_occupied;
+        let mut index = 0;
+        while occupied != 0 {
+            let sq = occupied.trailing_zeros() as usize;
+            occupied &= occupied - 1;
+
+            let pt = board.pieces_on_squares[sq].unwrap();
+            let color = if board.occupied[Color::White as usize] & (1 << sq) != 0 { 0 } else { 6 };
+            bytes[8 + index / 2] |= ((color + pt as u8) & 0xF) << (4 * (index % 2));
+            index += 1;
+        }
+
+        bytes[24..26].copy_from_slice(&self.score.to_le_bytes());
+        bytes[26] = self.result as u8;
+        let fullmove = board.fullmove_number.min(0x7FFF);
+        bytes[27] = board.side_to_move as u8 | ((fullmove >> 8) as u8) << 1;
+        bytes[28] = board.castling_rights;
+        for right in 0..4 {
+            let color = right / 2;
+            let rooks = (board.pieces[PieceType::Rook as usize][color] >> (56 * color)) as u8;
+            let king = (board.pieces[PieceType::King as usize][color] >> (56 * color)) as u8;
+            let file = board.castling_rooks[right] as u8 % 8;
+            if board.castling_rights & (1 << right) != 0 && outer_rook_file(rooks, king, right % 2 == 0) != Some(file) {
+                bytes[28] |= 16 << right;
+            }
+        }
+        bytes[29] = board.en_passant_target.map_or(64, |sq| sq as u8);
+        bytes[30] = board.halfmove_clock;
+        bytes[31] = fullmove as u8;
+        bytes
+    }
+
+    /// Decodes a record, `None` if it is malformed.
+    pub fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Option<Self> {
+        let occupancy = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        if occupancy.count_ones() > 32 || bytes[29] > 64 {
+            return None;
+        }
+
+        let mut squares = [None; 64];
+        let mut occupied = occupancy;
+        let mut index = 0;
+        while occupied != 0 {
+            let sq = occupied.trailing_zeros() as usize;
+            occupied &= occupied - 1;
+
+            let code = (bytes[8 + index / 2] >> (4 * (index % 2))) & 0xF;
+            squares[sq] = Some(*PIECE_CHARS.get(code as usize)?);
+            index += 1;
+        }
+
+        let mut fen = String::with_capacity(90);
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match squares[rank * 8 + file] {
+                    Some(c) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(c);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        let mut castling = String::new();
+        for (right, letter) in ['K', 'Q', 'k', 'q'].into_iter().enumerate() {
+            if bytes[28] & (1 << right) == 0 {
+                continue;
+            }
+            if bytes[28] & (16 << right) == 0 {
+                castling.push(letter);
+                continue;
+            }
+            // Innermost rook, written as its Shredder-FEN file
+            let back_rank = &squares[56 * (right / 2)..][..8];
+            let rank_mask = |piece: char| (0..8).filter(|&file| back_rank[file] == Some(piece)).fold(0_u8, |mask, file| mask | 1 << file);
+            let (rook, king) = if right < 2 { ('R', 'K') } else { ('r', 'k') };
+            let file = inner_rook_file(rank_mask(rook), rank_mask(king), right % 2 == 0)?;
+            let file = (b'a' + file) as char;
+            castling.push(if right < 2 { file.to_ascii_uppercase() } else { file });
+        }
+        let en_passant = match bytes[29] {
+            64 => "-".to_string(),
+            sq => format!("{:?}", SQUARES[sq as usize]).to_lowercase(),
+        };
+        fen.push_str(&format!(
+            " {} {} {} {} {}",
+            if bytes[27] & 1 == 0 { 'w' } else { 'b' },
+            if castling.is_empty() { "-" } else { &castling },
+            en_passant,
+            bytes[30],
+            u16::from(bytes[27] >> 1) << 8 | u16::from(bytes[31]),
+        ));
+
+        Some(Self {
+            board: Board::try_from_fen(&fen).ok()?,
+            score: i16::from_le_bytes([bytes[24], bytes[25]]),
+            result: GameResult::from_u8(bytes[26])?,
+        })
+    }
+
+    /// Text form `<fen> | <score> | <result>`, e.g. `... w - - 0 1 | 35 | 1.0`.
+    pub fn to_text(&self) -> String {
+        format!("{} | {} | {:.1}", self.board.to_fen(), self.score, self.result.as_score())
+    }
+}
+
+/// Rooks of one back rank (as a file mask) on the kingside or queenside of the king.
+fn side_rooks(rooks: u8, king: u8, kingside: bool) -> u8 {
+    let below_king = king.wrapping_sub(1) & !king;
+    if king == 0 {
+        0
+    } else if kingside {
+        rooks & !below_king & !king
+    } else {
+        rooks & below_king
+    }
+}
+
+fn outer_rook_file(rooks: u8, king: u8, kingside: bool) -> Option<u8> {
+    match side_rooks(rooks, king, kingside) {
+        0 => None,
+        side if kingside => Some(7 - side.leading_zeros() as u8),
+        side => Some(side.trailing_zeros() as u8),
+    }
+}
+
+fn inner_rook_file(rooks: u8, king: u8, kingside: bool) -> Option<u8> {
+    match side_rooks(rooks, king, kingside) {
+        0 => None,
+        side if kingside => Some(side.trailing_zeros() as u8),
+        side => Some(7 - side.leading_zeros() as u8),
+    }
+}
+
+/// Only kings, or kings and a single minor piece, can never be won.
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let heavy_or_pawns = [PieceType::Pawn, PieceType::Rook, PieceType::Queen]
+        .iter()
+        .any(|&pt| board.pieces[pt as usize][0] | board.pieces[pt as usize][1] != 0);
+    let minors: u32 = PIECE_TYPES[1..3]
+        .iter()
+        .map(|&pt| (board.pieces[pt as usize][0] | board.pieces[pt as usize][1]).count_ones())
+        .sum();
+    !heavy_or_pawns && minors <= 1
+}
@@ -0,0 +1,451 @@
+use crate::board::{Board, Color, PieceType};
+use crate::eval::endgame::material_key;
+use crate::movegen::legal_check::{is_current_king_attacked, is_other_king_attacked};
+use crate::movegen::attacks::{bishop_attacks, rook_attacks};
+use crate::movegen::tables::{KING_ATTACKS, KNIGHT_ATTACKS};
+use crate::r#move::MOVE_FLAG_EN_PASSANT;
+use crate::square::SQUARES;
+use std::mem;
+use super::{material_code, Dtm, DtmError, DtmTable, DtmTables, Material};
+
+// Retrograde analysis: start from the mates (and from captures or promotions into already
+// solved tables), then walk moves backwards one distance at a time.
+//  - A position with a move into a lost position is won one ply later.
+//  - A position is lost once every move leads into a won position. Each position counts its
+//    distinct successors; when the count reaches zero the loss is confirmed by a forward pass.
+// Positions never reached are draws. Successors are counted per index, not per move, because
+// mirror images share an index.
+
+const RESOLVED: u8 = 1;
+const PROPAGATED: u8 = 2;
+const INVALID: u8 = 4;
+const CANNOT_LOSE: u8 = 8; // Some capture or promotion holds at least a draw
+
+/// Summary of a generated table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GenerationStats {
+    pub positions: usize, // Legal positions after symmetry reduction
+    pub wins: usize,      // Side to move wins
+    pub losses: usize,
+    pub draws: usize,
+    pub longest: u32, // Longest mate in plies
+}
+
+/// Squares a piece on `sq` could have come from with a non capturing move, as a bitboard,
+/// plus the origin of a pawn double push (64 if none).
+fn unmove_origins(pt: PieceType, color: Color, sq: usize, occupancy: u64) -> (u64, usize) {
+    let empty = !occupancy;
+    match pt {
+        PieceType::King => (KING_ATTACKS[sq] & empty, 64),
+        PieceType::Knight => (KNIGHT_ATTACKS[sq] & empty, 64),
+        PieceType::Bishop => (bishop_attacks(sq, occupancy) & empty, 64),
+        PieceType::Rook => (rook_attacks(sq, occupancy) & empty, 64),
+        PieceType::Queen => ((bishop_attacks(sq, occupancy) | rook_attacks(sq, occupancy)) & empty, 64),
+        PieceType::Pawn => {
+            // Relative to white: rank 2 pawns have no origin, rank 4 pawns may have come from rank 2
+            let (relative_rank, behind) = match color {
+                Color::White => (sq / 8, sq.wrapping_sub(8)),
+                Color::Black => (7 - sq / 8, sq + 8),
+            };
+            if relative_rank < 2 || occupancy & (1 << behind) != 0 {
+                return (0, 64);
+            }
+            let double = if color == Color::White { sq.wrapping_sub(16) } else { sq + 16 };
+            let double = if relative_rank == 3 && occupancy & (1 << double) == 0 { double } else { 64 };
+            (1 << behind, double)
+        }
+    }
+}
+
+struct Generator<'a> {
+    material: Material,
+    subtables: &'a DtmTables,
+    empty: Board,
+    entries: Vec<u8>,
+    flags: Vec<u8>,
+    counts: Vec<u8>,
+    buckets: Vec<Vec<(u32, bool)>>, // Positions to resolve per distance, `true` for wins
+}
+
+impl Generator<'_> {
+    fn push(&mut self, index: usize, win: bool, plies: u32) {
+        let plies = plies as usize;
+        if self.buckets.len() <= plies {
+            self.buckets.resize(plies + 1, Vec::new());
+        }
+        self.buckets[plies].push((index as u32, win));
+    }
+
+    fn board(&self, index: usize) -> Board {
+        let (squares, side_to_move) = self.material.decode(index);
+        self.material.board(&self.empty, &squares[..self.material.pieces.len()], side_to_move)
+    }
+
+    fn index_of(&self, board: &Board) -> usize {
+        let (squares, side_to_move) = self.material.board_squares(board).unwrap();
+        self.material.index(&squares[..self.material.pieces.len()], side_to_move)
+    }
+
+    /// Value of a position outside this table, from the point of view of the side that moved there.
+    fn exit_value(&self, board: &mut Board) -> Result<Dtm, DtmError> {
+        if board.all_occupied.count_ones() == 2 {
+            return Ok(Dtm::Draw);
+        }
+        let value = self.subtables.probe(board).ok_or_else(|| DtmError::MissingTable(material_code(board)))?;
+        Ok(value.previous())
+    }
+
+    /// Best value of the en passant captures available in `board` for its side to move.
+    fn en_passant_value(&self, board: &mut Board) -> Result<Option<Dtm>, DtmError> {
+        if board.en_passant_target.is_none() {
+            return Ok(None);
+        }
+        let mut best: Option<Dtm> = None;
+        for &mv in board.legal_moves().iter() {
+            if mv.get_flags() != MOVE_FLAG_EN_PASSANT {
+                continue;
+            }
+            let undo = board.make_move(mv);
+            let value = self.exit_value(board);
+            board.undo_move(undo);
+            let value = value?;
+            if best.is_none_or(|b| value.rank() > b.rank()) {
+                best = Some(value);
+            }
+        }
+        Ok(best)
+    }
+
+    fn initialise(&mut self) -> Result<(), DtmError> {
+        let n = self.material.pieces.len();
+        let key = self.material.key;
+
+        for index in 0..self.entries.len() {
+            let (squares, side_to_move) = self.material.decode(index);
+            let squares = &squares[..n];
+            let occupied = squares.iter().fold(0_u64, |occ, &sq| occ | 1 << sq);
+            let pawn_on_back_rank = self
+                .material
+                .pieces
+                .iter()
+                .zip(squares)
+                .any(|(&(pt, _), &sq)| pt == PieceType::Pawn && !(8..56).contains(&sq));
+            if occupied.count_ones() as usize != n || pawn_on_back_rank || self.material.index(squares, side_to_move) != index {
+                self.flags[index] = INVALID;
+                continue;
+            }
+            let mut board = self.material.board(&self.empty, squares, side_to_move);
+            if is_other_king_attacked(&board) {
+                self.flags[index] = INVALID;
+                continue;
+            }
+
+            let moves = board.legal_moves();
+            if moves.is_empty() {
+                if is_current_king_attacked(&board) {
+                    self.push(index, false, 0);
+                } else {
+                    self.flags[index] = RESOLVED | PROPAGATED;
+                }
+                continue;
+            }
+
+            let mut successors = Vec::with_capacity(moves.len());
+            let mut exit_win: Option<u32> = None;
+            let mut exit_loss = 0;
+            for mv in moves {
+                let undo = board.make_move(mv);
+                if material_key(&board) == key {
+                    successors.push(self.index_of(&board));
+                } else {
+                    match self.exit_value(&mut board) {
+                        Ok(Dtm::Win(plies)) => exit_win = Some(exit_win.map_or(plies, |w| w.min(plies))),
+                        Ok(Dtm::Loss(plies)) => exit_loss = exit_loss.max(plies),
+                        Ok(Dtm::Draw) => self.flags[index] |= CANNOT_LOSE,
+                        Err(e) => return Err(e),
+                    }
+                }
+                board.undo_move(undo);
+            }
+            successors.sort_unstable();
+            successors.dedup();
+            self.counts[index] = successors.len() as u8;
+
+            if let Some(plies) = exit_win {
+                self.flags[index] |= CANNOT_LOSE;
+                self.push(index, true, plies);
+            } else if successors.is_empty() && self.flags[index] & CANNOT_LOSE == 0 {
+                self.push(index, false, exit_loss);
+            }
+        }
+        Ok(())
+    }
+
+    /// Confirms that every move of `index` loses, returns the longest resistance in plies.
+    fn verify_loss(&self, index: usize) -> Result<Option<u32>, DtmError> {
+        let key = self.material.key;
+        let mut board = self.board(index);
+        let mut longest = 0;
+
+        for &mv in board.legal_moves().iter() {
+            let undo = board.make_move(mv);
+            let value = if material_key(&board) == key {
+                let successor = self.index_of(&board);
+                let stored = if self.flags[successor] & RESOLVED != 0 { Dtm::from_entry(self.entries[successor]) } else { Dtm::Draw };
+                // En passant is not part of the index, it can only help the side to move
+                match self.en_passant_value(&mut board)? {
+                    Some(ep) if ep.rank() > stored.rank() => ep,
+                    _ => stored,
+                }
+                .previous()
+            } else {
+                self.exit_value(&mut board)?
+            };
+            board.undo_move(undo);
+
+            match value {
+                Dtm::Loss(plies) => longest = longest.max(plies),
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(longest))
+    }
+
+    /// Walks back from a freshly resolved position `index`.
+    fn propagate(&mut self, index: usize, win: bool, distance: u32) -> Result<(), DtmError> {
+        let n = self.material.pieces.len();
+        let (squares, side_to_move) = self.material.decode(index);
+        let board = self.material.board(&self.empty, &squares[..n], side_to_move);
+        let mover = !side_to_move;
+        let mut losing_predecessors = Vec::new();
+
+        for (i, (pt, color)) in self.material.pieces.clone().into_iter().enumerate() {
+            if color != mover {
+                continue;
+            }
+            let (mut origins, double) = unmove_origins(pt, color, squares[i], board.all_occupied);
+            if double < 64 {
+                origins |= 1 << double;
+            }
+            while origins != 0 {
+                let origin = origins.trailing_zeros() as usize;
+                origins &= origins - 1;
+
+                let mut previous = squares;
+                previous[i] = origin;
+                let previous_board = self.material.board(&self.empty, &previous[..n], mover);
+                if is_other_king_attacked(&previous_board) {
+                    continue;
+                }
+                let predecessor = self.material.index(&previous[..n], mover);
+                if self.flags[predecessor] & (RESOLVED | INVALID) != 0 {
+                    continue;
+                }
+
+                if win {
+                    losing_predecessors.push(predecessor);
+                } else if origin == double {
+                    // The double push allows en passant, which may save the position
+                    let mut en_passant_board = board;
+                    en_passant_board.en_passant_target = Some(SQUARES[(origin + squares[i]) / 2]);
+                    match self.en_passant_value(&mut en_passant_board)? {
+                        Some(Dtm::Loss(plies)) => self.push(predecessor, true, plies.max(distance) + 1),
+                        Some(_) => {}
+                        None => self.resolve_win(predecessor, distance + 1),
+                    }
+                } else {
+                    self.resolve_win(predecessor, distance + 1);
+                }
+            }
+        }
+
+        losing_predecessors.sort_unstable();
+        losing_predecessors.dedup();
+        for predecessor in losing_predecessors {
+            if self.flags[predecessor] & (RESOLVED | CANNOT_LOSE) != 0 {
+                continue;
+            }
+            self.counts[predecessor] -= 1;
+            if self.counts[predecessor] == 0
+                && let Some(plies) = self.verify_loss(predecessor)?
+            {
+                self.push(predecessor, false, plies);
+            }
+        }
+        Ok(())
+    }
+
+    /// Nothing shorter is possible anymore, so the win is final right away.
+    fn resolve_win(&mut self, index: usize, plies: u32) {
+        if self.flags[index] & RESOLVED == 0 {
+            self.flags[index] |= RESOLVED;
+            self.entries[index] = plies as u8 + 1;
+            self.push(index, true, plies);
+        }
+    }
+
+    fn run(&mut self) -> Result<GenerationStats, DtmError> {
+        self.initialise()?;
+
+        let mut distance = 0;
+        while distance < self.buckets.len() {
+            if distance + 1 >= u8::MAX as usize {
+                return Err(DtmError::TooDeep(self.material.code.clone()));
+            }
+            for (index, win) in mem::take(&mut self.buckets[distance]) {
+                let index = index as usize;
+                if self.flags[index] & PROPAGATED != 0 || (self.flags[index] & RESOLVED != 0 && self.entries[index] as usize != distance + 1) {
+                    continue;
+                }
+                self.flags[index] |= RESOLVED | PROPAGATED;
+                self.entries[index] = distance as u8 + 1;
+                self.propagate(index, win, distance as u32)?;
+            }
+            distance += 1;
+        }
+
+        let mut stats = GenerationStats::default();
+        for (index, &flags) in self.flags.iter().enumerate() {
+            if flags & INVALID != 0 {
+                continue;
+            }
+            stats.positions += 1;
+            match Dtm::from_entry(self.entries[index]) {
+                Dtm::Win(plies) => {
+                    stats.wins += 1;
+                    stats.longest = stats.longest.max(plies);
+                }
+                Dtm::Loss(plies) => {
+                    stats.losses += 1;
+                    stats.longest = stats.longest.max(plies);
+                }
+                Dtm::Draw => stats.draws += 1,
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// Solves the ending `code` (e.g. "KRvKB"). Captures and promotions are looked up in
+/// `subtables`, which must hold every table they lead to.
+pub fn generate(code: &str, subtables: &DtmTables) -> Result<(DtmTable, GenerationStats), DtmError> {
+    let material = Material::from_code(code)?;
+    let size = material.size();
+    let mut generator = Generator {
+        empty: Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1"),
+        subtables,
+        entries: vec![0; size],
+        flags: vec![0; size],
+        counts: vec![0; size],
+        buckets: Vec::new(),
+        material,
+    };
+    let stats = generator.run()?;
+    Ok((DtmTable { material: generator.material, entries: generator.entries }, stats))
+}
+
+/// Tables that captures and promotions from `code` lead into, by their normalised codes.
+pub fn dependencies(code: &str) -> Result<Vec<String>, DtmError> {
+    let material = Material::from_code(code)?;
+    let mut codes = Vec::new();
+    let sides: Vec<Vec<PieceType>> = [Color::White, Color::Black]
+        .iter()
+        .map(|&c| material.pieces[2..].iter().filter(|p| p.1 == c).map(|p| p.0).collect())
+        .collect();
+
+    for side in 0..2 {
+        for i in 0..sides[side].len() {
+            let mut captured = sides.clone();
+            captured[side].remove(i);
+            codes.push(captured);
+
+            if sides[side][i] == PieceType::Pawn {
+                for promotion in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+                    let mut promoted = sides.clone();
+                    promoted[side][i] = promotion;
+                    codes.push(promoted.clone());
+                    // Capturing promotions
+                    promoted[1 - side].iter().enumerate().for_each(|(j, _)| {
+                        let mut both = promoted.clone();
+                        both[1 - side].remove(j);
+                        codes.push(both);
+                    });
+                }
+            }
+        }
+    }
+
+    let mut result: Vec<String> = codes
+        .into_iter()
+        .filter(|sides| sides[0].len() + sides[1].len() > 0)
+        .map(|sides| normalised_code(&sides[0], &sides[1]))
+        .collect();
+    result.sort();
+    result.dedup();
+    Ok(result)
+}
+
+/// The file name of an ending: pieces ordered queen to pawn, the stronger side first.
+pub fn normalised_code(white: &[PieceType], black: &[PieceType]) -> String {
+    let letters = |pieces: &[PieceType]| {
+        let mut pieces = pieces.to_vec();
+        pieces.sort_by_key(|&pt| std::cmp::Reverse(pt as usize));
+        pieces.iter().map(|&pt| ['P', 'N', 'B', 'R', 'Q', 'K'][pt as usize]).collect::<String>()
+    };
+    let strength = |pieces: &[PieceType]| {
+        let mut values: Vec<usize> = pieces.iter().map(|&pt| pt as usize).collect();
+        values.sort_unstable_by(|a, b| b.cmp(a));
+        values
+    };
+    let (first, second) = if strength(white) >= strength(black) { (white, black) } else { (black, white) };
+    format!("K{}vK{}", letters(first), letters(second))
+}
+
+/// All normalised endings with 3 to `max_pieces` pieces, smaller ones first.
+pub fn all_codes(max_pieces: usize) -> Vec<String> {
+    const PIECES: [PieceType; 5] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight, PieceType::Pawn];
+    let mut codes = Vec::new();
+    for extra in 1..=max_pieces.saturating_sub(2) {
+        let mut combinations: Vec<Vec<PieceType>> = vec![Vec::new()];
+        for _ in 0..extra {
+            combinations = combinations
+                .iter()
+                .flat_map(|c| PIECES.iter().map(move |&p| [c.clone(), vec![p]].concat()))
+                .collect();
+        }
+        for pieces in combinations {
+            for split in 0..=extra {
+                codes.push(normalised_code(&pieces[..split], &pieces[split..]));
+            }
+        }
+    }
+    let mut unique = Vec::new();
+    for code in codes {
+        if !unique.contains(&code) {
+            unique.push(code);
+        }
+    }
+    unique
+}
+
+/// Solving order of the pawnful tables doesn't matter for correctness, only that `dependencies`
+/// come first; this helper makes it explicit for a list of requested codes.
+pub fn generation_order(codes: &[String]) -> Result<Vec<String>, DtmError> {
+    fn visit(code: &str, order: &mut Vec<String>) -> Result<(), DtmError> {
+        if order.iter().any(|c| c == code) {
+            return Ok(());
+        }
+        for dependency in dependencies(code)? {
+            if dependency != code {
+                visit(&dependency, order)?;
+            }
+        }
+        order.push(code.to_string());
+        Ok(())
+    }
+    let mut order = Vec::new();
+    for code in codes {
+        visit(code, &mut order)?;
+    }
+    Ok(order)
+}
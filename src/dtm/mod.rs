@@ -0,0 +1,447 @@
+pub mod generate;
+
+use crate::board::{Board, Color, PieceType};
+use crate::eval::endgame::{material_key, material_key_from_code};
+use crate::movegen::legal_check::is_current_king_attacked;
+use crate::r#move::{Move, MOVE_FLAG_EN_PASSANT};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Distance to mate tables built by `tbgen`, one file per material code ("KRvKB.ykdtm").
+// File layout (little endian):
+//   magic "YKDT", version u32, code length u8, code (ASCII), entry count u32, entries u8
+//
+// Each position has one entry: 0 for draws (and unused indices), otherwise plies to mate + 1.
+// An odd number of plies means the side to move mates, an even one that it gets mated.
+//
+// Pieces are ordered white king, black king, then the other pieces in code order. The index is
+//   ((king region * 64 + black king) * 64 + piece 1) * 64 ... ) * 2 + side to move
+// with the board mirrored so that the white king is inside its region: the a1-d1-d4 triangle
+// for pawnless tables, files a-d otherwise. Of several such mirror images the smallest index
+// is used, identical pieces are sorted by square.
+pub const DTM_MAGIC: &[u8; 4] = b"YKDT";
+pub const DTM_FILE_VERSION: u32 = 1;
+pub const DTM_EXTENSION: &str = "ykdtm";
+
+/// Largest number of pieces (kings included) a table may have.
+pub const MAX_PIECES: usize = 4;
+
+// a1, b1, c1, d1, b2, c2, d2, c3, d3, d4
+const TRIANGLE: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DtmError {
+    Io(String),
+    BadMagic,
+    UnsupportedVersion(u32),
+    InvalidCode(String),
+    WrongSize { expected: usize, found: usize },
+    TooDeep(String),
+    MissingTable(String),
+    NoTables(String),
+}
+
+impl fmt::Display for DtmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "unable to access DTM table: {}", e),
+            Self::BadMagic => write!(f, "not a DTM table (bad magic)"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported DTM table version {} (expected {})", v, DTM_FILE_VERSION),
+            Self::InvalidCode(code) => write!(f, "invalid ending '{}', expected e.g. KRvKB with up to {} pieces", code, MAX_PIECES),
+            Self::WrongSize { expected, found } => write!(f, "DTM table has {} entries, expected {}", found, expected),
+            Self::TooDeep(code) => write!(f, "{} has mates too long for the table format", code),
+            Self::MissingTable(code) => write!(f, "{} is needed first", code),
+            Self::NoTables(dir) => write!(f, "no .{} tables in {}", DTM_EXTENSION, dir),
+        }
+    }
+}
+
+impl std::error::Error for DtmError {}
+
+/// Distance to mate in plies from the side to move's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u32),
+    Loss(u32),
+    Draw,
+}
+
+impl Dtm {
+    fn from_entry(entry: u8) -> Self {
+        match entry {
+            0 => Dtm::Draw,
+            e if e % 2 == 0 => Dtm::Win(e as u32 - 1),
+            e => Dtm::Loss(e as u32 - 1),
+        }
+    }
+
+    /// The value one ply earlier, for the side that made the move leading here.
+    pub fn previous(self) -> Self {
+        match self {
+            Dtm::Win(plies) => Dtm::Loss(plies + 1),
+            Dtm::Loss(plies) => Dtm::Win(plies + 1),
+            Dtm::Draw => Dtm::Draw,
+        }
+    }
+
+    /// Ordering key, higher is better for the side to move: quick wins, then draws, then slow losses.
+    pub fn rank(self) -> i64 {
+        match self {
+            Dtm::Win(plies) => 1_000 - plies as i64,
+            Dtm::Draw => 0,
+            Dtm::Loss(plies) => -1_000 + plies as i64,
+        }
+    }
+}
+
+/// Pieces of a table in index order and its material keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Material {
+    pub code: String,
+    pub pieces: Vec<(PieceType, Color)>,
+    pub key: u64,  // First side of the code white
+    pub key2: u64, // ... and black
+}
+
+fn piece_from_char(c: char) -> Option<PieceType> {
+    match c {
+        'P' => Some(PieceType::Pawn),
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        _ => None,
+    }
+}
+
+impl Material {
+    /// Parses a code like "KRvKB".
+    pub fn from_code(code: &str) -> Result<Self, DtmError> {
+        let invalid = || DtmError::InvalidCode(code.to_string());
+        let (white, black) = code.split_once('v').ok_or_else(invalid)?;
+        let (Some(white), Some(black)) = (white.strip_prefix('K'), black.strip_prefix('K')) else {
+            return Err(invalid());
+        };
+        if white.len() + black.len() + 2 > MAX_PIECES {
+            return Err(invalid());
+        }
+
+        let mut pieces = vec![(PieceType::King, Color::White), (PieceType::King, Color::Black)];
+        for (side, color) in [(white, Color::White), (black, Color::Black)] {
+            for c in side.chars() {
+                pieces.push((piece_from_char(c).ok_or_else(invalid)?, color));
+            }
+        }
+        let letters = format!("K{}K{}", white, black);
+        Ok(Material {
+            code: code.to_string(),
+            pieces,
+            key: material_key_from_code(&letters, Color::White),
+            key2: material_key_from_code(&letters, Color::Black),
+        })
+    }
+
+    pub fn has_pawns(&self) -> bool {
+        self.pieces.iter().any(|&(pt, _)| pt == PieceType::Pawn)
+    }
+
+    /// Number of index entries.
+    pub fn size(&self) -> usize {
+        let region = if self.has_pawns() { 32 } else { TRIANGLE.len() };
+        region * 64_usize.pow(self.pieces.len() as u32 - 1) * 2
+    }
+
+    // Mirror images that keep the position equivalent: bit 0 flips files, bit 1 ranks,
+    // bit 2 swaps files and ranks. Pawns only allow the file flip.
+    fn symmetries(&self) -> &'static [usize] {
+        if self.has_pawns() { &[0, 1] } else { &[0, 1, 2, 3, 4, 5, 6, 7] }
+    }
+
+    fn region_index(&self, sq: usize) -> Option<usize> {
+        if self.has_pawns() {
+            (sq % 8 < 4).then_some((sq / 8) * 4 + sq % 8)
+        } else {
+            TRIANGLE.iter().position(|&s| s == sq)
+        }
+    }
+
+    /// Index of the position with `squares` in piece order, the smallest of its mirror images.
+    pub fn index(&self, squares: &[usize], side_to_move: Color) -> usize {
+        let n = self.pieces.len();
+        let identical = n == 4 && self.pieces[2] == self.pieces[3];
+        let mut best = usize::MAX;
+
+        for &symmetry in self.symmetries() {
+            let Some(region) = self.region_index(transform(squares[0], symmetry)) else {
+                continue;
+            };
+            let mut mapped = [0; MAX_PIECES];
+            for i in 1..n {
+                mapped[i] = transform(squares[i], symmetry);
+            }
+            if identical && mapped[2] > mapped[3] {
+                mapped.swap(2, 3);
+            }
+            let index = mapped[1..n].iter().fold(region, |index, &sq| index * 64 + sq);
+            best = best.min(index * 2 + side_to_move as usize);
+        }
+        best
+    }
+
+    /// Piece squares and side to move of an index.
+    pub fn decode(&self, index: usize) -> ([usize; MAX_PIECES], Color) {
+        let n = self.pieces.len();
+        let side_to_move = if index.is_multiple_of(2) { Color::White } else { Color::Black };
+        let mut rest = index / 2;
+        let mut squares = [0; MAX_PIECES];
+        for i in (1..n).rev() {
+            squares[i] = rest % 64;
+            rest /= 64;
+        }
+        squares[0] = if self.has_pawns() { (rest / 4) * 8 + rest % 4 } else { TRIANGLE[rest] };
+        (squares, side_to_move)
+    }
+
+    /// Squares of `board`'s pieces in index order, with the colors swapped if the board has
+    /// this table's material the other way round.
+    pub(crate) fn board_squares(&self, board: &Board) -> Option<([usize; MAX_PIECES], Color)> {
+        let key = material_key(board);
+        let flip = if key == self.key {
+            false
+        } else if key == self.key2 {
+            true
+        } else {
+            return None;
+        };
+
+        let mut squares = [0; MAX_PIECES];
+        let mut taken = 0_u64;
+        for (i, &(pt, color)) in self.pieces.iter().enumerate() {
+            let color = if flip { !color } else { color };
+            let pieces = board.pieces[pt as usize][color as usize] & !taken;
+            let sq = pieces.trailing_zeros() as usize;
+            taken |= 1 << sq;
+            squares[i] = if flip { sq ^ 56 } else { sq };
+        }
+        let side_to_move = if flip { !board.side_to_move } else { board.side_to_move };
+        Some((squares, side_to_move))
+    }
+
+    /// A board with the pieces on `squares`, built from `empty` (a board without pieces).
+    pub fn board(&self, empty: &Board, squares: &[usize], side_to_move: Color) -> Board {
+        let mut board = *empty;
+        board.side_to_move = side_to_move;
+        for (&(pt, color), &sq) in self.pieces.iter().zip(squares) {
+            board.pieces[pt as usize][color as usize] |= 1 << sq;
+            board.occupied[color as usize] |= 1 << sq;
+            board.pieces_on_squares[sq] = Some(pt);
+        }
+        board.all_occupied = board.occupied[0] | board.occupied[1];
+        board.empty_squares = !board.all_occupied;
+        board
+    }
+}
+
+fn transform(sq: usize, symmetry: usize) -> usize {
+    let (mut file, mut rank) = (sq % 8, sq / 8);
+    if symmetry & 1 != 0 {
+        file = 7 - file;
+    }
+    if symmetry & 2 != 0 {
+        rank = 7 - rank;
+    }
+    if symmetry & 4 != 0 {
+        (file, rank) = (rank, file);
+    }
+    rank * 8 + file
+}
+
+/// Material of `board` as a code, white first (e.g. "KRvKB").
+pub fn material_code(board: &Board) -> String {
+    let side = |color: Color| {
+        let mut code = String::from("K");
+        for (pt, letter) in [(PieceType::Queen, 'Q'), (PieceType::Rook, 'R'), (PieceType::Bishop, 'B'), (PieceType::Knight, 'N'), (PieceType::Pawn, 'P')] {
+            for _ in 0..board.pieces[pt as usize][color as usize].count_ones() {
+                code.push(letter);
+            }
+        }
+        code
+    };
+    format!("{}v{}", side(Color::White), side(Color::Black))
+}
+
+
+/// Whether the side to move has a legal en passant capture; tables assume there is none.
+fn has_en_passant(board: &mut Board) -> bool {
+    board.en_passant_target.is_some() && board.legal_moves().iter().any(|mv| mv.get_flags() == MOVE_FLAG_EN_PASSANT)
+}
+
+/// A complete DTM table for one material code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtmTable {
+    pub material: Material,
+    entries: Vec<u8>,
+}
+
+impl DtmTable {
+    pub fn code(&self) -> &str {
+        &self.material.code
+    }
+
+    /// Value at an index, see the layout at the top of this file.
+    pub fn get(&self, index: usize) -> Dtm {
+        Dtm::from_entry(self.entries[index])
+    }
+
+    /// Exact result of `board`; `None` for other material, castling rights or en passant.
+    pub fn probe(&self, board: &mut Board) -> Option<Dtm> {
+        if board.castling_rights != 0 || has_en_passant(board) {
+            return None;
+        }
+        let (squares, side_to_move) = self.material.board_squares(board)?;
+        Some(self.get(self.material.index(&squares, side_to_move)))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let code = self.material.code.as_bytes();
+        let mut bytes = Vec::with_capacity(13 + code.len() + self.entries.len());
+        bytes.extend_from_slice(DTM_MAGIC);
+        bytes.extend_from_slice(&DTM_FILE_VERSION.to_le_bytes());
+        bytes.push(code.len() as u8);
+        bytes.extend_from_slice(code);
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.entries);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DtmError> {
+        if bytes.len() < 9 || &bytes[0..4] != DTM_MAGIC {
+            return Err(DtmError::BadMagic);
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if version != DTM_FILE_VERSION {
+            return Err(DtmError::UnsupportedVersion(version));
+        }
+        let code_end = 9 + bytes[8] as usize;
+        let code = bytes.get(9..code_end).and_then(|code| std::str::from_utf8(code).ok()).ok_or(DtmError::BadMagic)?;
+        let material = Material::from_code(code)?;
+
+        let count = bytes
+            .get(code_end..code_end + 4)
+            .map_or(0, |count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
+        let entries = bytes.get(code_end + 4..).unwrap_or_default();
+        if count != material.size() || entries.len() != count {
+            return Err(DtmError::WrongSize { expected: material.size(), found: entries.len() });
+        }
+        Ok(DtmTable { material, entries: entries.to_vec() })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, DtmError> {
+        let bytes = fs::read(path).map_err(|e| DtmError::Io(format!("{}: {}", path.display(), e)))?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), DtmError> {
+        fs::write(path, self.to_bytes()).map_err(|e| DtmError::Io(format!("{}: {}", path.display(), e)))
+    }
+}
+
+struct TableEntry {
+    path: PathBuf,
+    table: OnceLock<Option<DtmTable>>,
+}
+
+/// The tables found in the `DtmPath` directory, loaded on first use.
+pub struct DtmTables {
+    entries: Vec<TableEntry>,
+    by_key: HashMap<u64, usize>,
+}
+
+impl DtmTables {
+    /// Finds the tables in `dir` without loading them.
+    pub fn open(dir: &str) -> Result<Self, DtmError> {
+        let mut tables = DtmTables { entries: Vec::new(), by_key: HashMap::new() };
+        let files = fs::read_dir(dir).map_err(|e| DtmError::Io(format!("{}: {}", dir, e)))?;
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(DTM_EXTENSION) {
+                continue;
+            }
+            let Some(Ok(material)) = path.file_stem().and_then(|s| s.to_str()).map(Material::from_code) else {
+                continue;
+            };
+            tables.by_key.insert(material.key, tables.entries.len());
+            tables.by_key.insert(material.key2, tables.entries.len());
+            tables.entries.push(TableEntry { path, table: OnceLock::new() });
+        }
+        if tables.is_empty() {
+            return Err(DtmError::NoTables(dir.to_string()));
+        }
+        Ok(tables)
+    }
+
+    /// Wraps tables that are already in memory, e.g. while generating.
+    pub fn from_tables(tables: Vec<DtmTable>) -> Self {
+        let mut result = DtmTables { entries: Vec::new(), by_key: HashMap::new() };
+        for table in tables {
+            result.insert(table);
+        }
+        result
+    }
+
+    /// Adds a table held in memory, replacing any other with the same material.
+    pub fn insert(&mut self, table: DtmTable) {
+        self.by_key.insert(table.material.key, self.entries.len());
+        self.by_key.insert(table.material.key2, self.entries.len());
+        self.entries.push(TableEntry { path: PathBuf::new(), table: OnceLock::from(Some(table)) });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Exact result of `board`, `None` without a table for it. Bare kings are a draw.
+    pub fn probe(&self, board: &mut Board) -> Option<Dtm> {
+        if board.all_occupied.count_ones() == 2 {
+            return Some(Dtm::Draw);
+        }
+        if board.all_occupied.count_ones() as usize > MAX_PIECES {
+            return None;
+        }
+        let entry = &self.entries[*self.by_key.get(&material_key(board))?];
+        let table = entry.table.get_or_init(|| DtmTable::load(&entry.path).ok());
+        table.as_ref()?.probe(board)
+    }
+
+    /// The fastest mate, else a drawing move, else the slowest loss, with the position's value.
+    /// `None` if no probed move keeps that value, since a capture or promotion out of the
+    /// tables (or an en passant capture) may be the only one that does.
+    pub fn best_move(&self, board: &mut Board) -> Option<(Move, Dtm)> {
+        let value = self.probe(board)?;
+        let mut best: Option<(Move, Dtm)> = None;
+        for &mv in board.legal_moves().iter() {
+            let undo = board.make_move(mv);
+            let child = self.probe(board);
+            let mated = is_current_king_attacked(board) && board.legal_moves().is_empty();
+            board.undo_move(undo);
+
+            // Captures and promotions may leave the tables
+            let result = match child {
+                Some(child) => child.previous(),
+                None if mated => Dtm::Win(1),
+                None => continue,
+            };
+            if best.is_none_or(|(_, b)| result.rank() > b.rank()) {
+                best = Some((mv, result));
+            }
+        }
+        best.filter(|&(_, result)| result == value).map(|(mv, _)| (mv, value))
+    }
+}
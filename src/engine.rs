@@ -1,52 +1,245 @@
 use crate::board::Board;
+use crate::book::{BookError, BookSelection, PolyglotBook};
+use crate::eval::nnue::{AccumulatorStack, Network, NnueError};
+use crate::eval::params::{EvalParams, EvalTables, ParamsError};
+use crate::parsing::{FenError, MoveParseError};
 use crate::r#move::Move;
-use crate::search::alpha_beta;
+use crate::dtm::{DtmError, DtmTables};
+use crate::search::{alpha_beta, dtm_score, tablebase_score, SearchTables};
+use crate::syzygy::{SyzygyError, Tablebases};
 use crate::tt::TranspositionTable; // Import TT
-use std::time::{Instant, Duration};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use crate::uci::UCI;
 
+/// Why a `position` could not be set up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionError {
+    Fen(FenError),
+    Move(MoveParseError),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fen(e) => write!(f, "invalid FEN: {}", e),
+            Self::Move(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
 pub struct Engine {
     pub name: String,
     pub author: String,
     pub board: Board,
     pub tt: TranspositionTable, // Engine owns the TT
+    pub book: Option<PolyglotBook>,
+    pub own_book: bool, // Play book moves before searching
+    pub book_selection: BookSelection,
+    pub chess960: bool, // UCI_Chess960: castling read and written as king takes rook
+    pub eval_tables: Arc<EvalTables>, // EvalParamsFile, shared with engines of other threads
+    pub network: Option<Arc<Network>>, // NnueFile, the classical eval without one
+    pub tablebases: Option<Arc<Tablebases>>, // SyzygyPath
+    pub dtm_tables: Option<Arc<DtmTables>>, // DtmPath
 }
 
 impl Engine {
     pub fn new(name: String, author: String) -> Engine {
+        Self::with_hash(name, author, 4096)
+    }
+
+    /// Like `new`, with a transposition table of `hash_mb` megabytes.
+    pub fn with_hash(name: String, author: String, hash_mb: usize) -> Engine {
         // Use the standard starting position
         let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
 
-        let tt = TranspositionTable::new(4096);
+        let tt = TranspositionTable::new(hash_mb);
 
         Engine {
             name,
             author,
             board,
             tt,
+            book: None,
+            own_book: false,
+            book_selection: BookSelection::default(),
+            chess960: false,
+            eval_tables: Arc::new(EvalTables::default()),
+            network: None,
+            tablebases: None,
+            dtm_tables: None,
         }
     }
 
-    pub fn setpos_fen(&mut self, fen: &str) {
-        self.board = Board::from_fen(fen);
+    /// Loads an eval parameter file; an empty path or `<empty>` restores the built-in defaults.
+    pub fn load_eval_params_file(&mut self, path: &str) -> Result<(), ParamsError> {
+        let params = if path.is_empty() || path == "<empty>" {
+            EvalParams::default()
+        } else {
+            EvalParams::load(path)?
+        };
+        self.set_eval_params(params);
+        Ok(())
+    }
+
+    /// Evaluates with `params` from now on.
+    pub fn set_eval_params(&mut self, params: EvalParams) {
+        self.eval_tables = Arc::new(EvalTables::new(params));
+
+        // Cached scores were computed with the old weights
+        self.tt.clear();
+    }
+
+    /// Loads an NNUE network; an empty path or `<empty>` goes back to the classical eval.
+    pub fn load_nnue_file(&mut self, path: &str) -> Result<(), NnueError> {
+        let network = if path.is_empty() || path == "<empty>" {
+            None
+        } else {
+            Some(Network::load(path)?)
+        };
+        self.network = network.map(Arc::new);
+        self.tt.clear();
+        Ok(())
+    }
+
+    /// Opens the Syzygy tables in `path` and returns how many were found; an empty path or
+    /// `<empty>` disables probing.
+    pub fn set_syzygy_path(&mut self, path: &str) -> Result<usize, SyzygyError> {
+        let tables = if path.is_empty() || path == "<empty>" {
+            None
+        } else {
+            Some(Arc::new(Tablebases::open(path)?))
+        };
+        let count = tables.as_deref().map_or(0, Tablebases::len);
+        self.tablebases = tables;
+
+        self.tt.clear();
+        Ok(count)
+    }
+
+    /// Opens the DTM tables (built by `tbgen`) in `path` and returns how many were found; an
+    /// empty path or `<empty>` disables probing.
+    pub fn set_dtm_path(&mut self, path: &str) -> Result<usize, DtmError> {
+        let tables = if path.is_empty() || path == "<empty>" {
+            None
+        } else {
+            Some(Arc::new(DtmTables::open(path)?))
+        };
+        let count = tables.as_deref().map_or(0, DtmTables::len);
+        self.dtm_tables = tables;
+
+        self.tt.clear();
+        Ok(count)
+    }
+
+    /// Loads a Polyglot book and returns its number of entries; an empty path or `<empty>`
+    /// removes the book.
+    pub fn load_book(&mut self, path: &str) -> Result<usize, BookError> {
+        self.book = if path.is_empty() || path == "<empty>" {
+            None
+        } else {
+            Some(PolyglotBook::load(path)?)
+        };
+        Ok(self.book.as_ref().map_or(0, PolyglotBook::len))
+    }
+
+    /// Sets up the position of `fen` after `moves`, keeping the current one if either is invalid.
+    pub fn setpos_fen(&mut self, fen: &str, moves: &[&str]) -> Result<(), PositionError> {
+        let mut board = Board::try_from_fen(fen).map_err(PositionError::Fen)?;
+        board.chess960 = self.chess960;
+        self.board = Self::play_moves(board, moves)?;
+        Ok(())
+    }
+
+    /// Like `setpos_fen` from the standard start position.
+    pub fn setpos_startpos(&mut self, moves: &[&str]) -> Result<(), PositionError> {
+        let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        board.chess960 = self.chess960;
+        self.board = Self::play_moves(board, moves)?;
+        Ok(())
     }
 
-    pub fn setpos_startpos(&mut self, moves: &[&str]) {
-        self.board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    fn play_moves(mut board: Board, moves: &[&str]) -> Result<Board, PositionError> {
         for mv_str in moves {
-            let mv = Move::from_algebraic(mv_str, &self.board);
-            self.board.make_move(mv);
+            let mv = board.parse_uci_move(mv_str).map_err(PositionError::Move)?;
+            board.make_move(mv);
         }
+        Ok(board)
     }
 
     pub fn search(&mut self, time_limit_ms: u64) -> String {
+        if self.own_book
+            && let Some(book) = &self.book
+        {
+            let random = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+            if let Some(mv) = book.pick(&mut self.board, self.book_selection, random) {
+                println!("info string book move {}", mv.to_uci(&self.board));
+                return mv.to_uci(&self.board);
+            }
+        }
+
+        let (opt_move, _score) = self.iterative_deepening(time_limit_ms, u8::MAX, u64::MAX, true);
+
+        if let Some(mv) = opt_move {
+            mv.to_uci(&self.board)
+        } else {
+            // Fallback: If even depth 1 failed (e.g. 0ms time limit), try to return *any* legal move
+            // or just return null if truly nothing works.
+            "null".to_string()
+        }
+    }
+
+    /// Silent fixed node search, e.g. for data generation.
+    /// Returns the best move of the last completed depth and its side to move relative score.
+    pub fn search_nodes(&mut self, node_limit: u64) -> (Option<Move>, i32) {
+        self.iterative_deepening(u64::MAX, u8::MAX, node_limit, false)
+    }
+
+    /// Silent search stopping at whichever limit comes first, e.g. for test suites.
+    pub fn search_limits(&mut self, time_limit_ms: u64, depth_limit: u8, node_limit: u64) -> (Option<Move>, i32) {
+        self.iterative_deepening(time_limit_ms, depth_limit, node_limit, false)
+    }
+
+    fn iterative_deepening(&mut self, time_limit_ms: u64, depth_limit: u8, node_limit: u64, report: bool) -> (Option<Move>, i32) {
         let start_time = Instant::now();
         let time_limit = Duration::from_millis(time_limit_ms);
 
         let mut nodes = 0;
+        let tables = SearchTables {
+            eval: &self.eval_tables,
+            syzygy: self.tablebases.as_deref(),
+            dtm: self.dtm_tables.as_deref(),
+        };
+        let tbhits_start = tables.syzygy.map_or(0, |tb| tb.hits());
+        let tbhits = || tables.syzygy.map(|tb| tb.hits() - tbhits_start);
+
+        // Solved positions are played without a search: by DTM, else by DTZ
+        if let Some(dtm_tables) = tables.dtm
+            && let Some((best_move, dtm)) = dtm_tables.best_move(&mut self.board)
+        {
+            let score = dtm_score(dtm, 0);
+            if report {
+                UCI::send_info(Some(1), Some(0), None, None, Some(score), Some(&best_move.to_uci(&self.board)), tbhits());
+            }
+            return (Some(best_move), score);
+        }
+        if let Some(tb) = tables.syzygy
+            && let Some(root) = tb.probe_root(&mut self.board)
+        {
+            let score = tablebase_score(root.wdl, 0);
+            if report {
+                UCI::send_info(Some(1), Some(0), None, None, Some(score), Some(&root.best_move.to_uci(&self.board)), tbhits());
+            }
+            return (Some(root.best_move), score);
+        }
+
+        let mut accumulators = AccumulatorStack::new(&self.board, self.network.as_deref());
 
         // Initial search at depth 1
-        let (mut opt_move, mut _score) = alpha_beta(
+        let (mut opt_move, mut score) = alpha_beta(
             &mut self.board,
             1,
             0,
@@ -54,8 +247,11 @@ impl Engine {
             i32::MAX,
             start_time,
             time_limit,
+            node_limit,
             &mut nodes,
-            &mut self.tt
+            &mut self.tt,
+            &mut accumulators,
+            tables,
         );
 
         // If we timed out immediately at depth 1 (very rare), opt_move might be None.
@@ -64,7 +260,7 @@ impl Engine {
         let mut depth = 2;
 
         // Iterative Deepening
-        while start_time.elapsed() < time_limit {
+        while start_time.elapsed() < time_limit && nodes < node_limit && depth <= depth_limit && depth < u8::MAX {
             let (new_move, new_score) = alpha_beta(
                 &mut self.board,
                 depth,
@@ -73,17 +269,22 @@ impl Engine {
                 i32::MAX,
                 start_time,
                 time_limit,
+                node_limit,
                 &mut nodes,
-                &mut self.tt
+                &mut self.tt,
+                &mut accumulators,
+                tables,
             );
 
             // FIX: Only update if we actually got a move back (didn't timeout)
             if let Some(mv) = new_move {
                 opt_move = Some(mv);
-                _score = new_score;
+                score = new_score;
 
                 // Optional: Move send_info here to update the GUI after every completed depth
-                UCI::send_info(Some(depth as usize), Some(nodes), None, None, None, None);
+                if report {
+                    UCI::send_info(Some(depth as usize), Some(nodes), None, None, None, None, tbhits());
+                }
             } else {
                 // If new_move is None, the search was aborted due to time.
                 // We discard the partial results and stop increasing depth.
@@ -93,12 +294,6 @@ impl Engine {
             depth += 1;
         }
 
-        if let Some(mv) = opt_move {
-            mv.to_algebraic()
-        } else {
-            // Fallback: If even depth 1 failed (e.g. 0ms time limit), try to return *any* legal move
-            // or just return null if truly nothing works.
-            "null".to_string()
-        }
+        (opt_move, score)
     }
-}
\ No newline at end of file
+}
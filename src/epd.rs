@@ -0,0 +1,271 @@
+use crate::board::Board;
+use crate::parsing::{FenError, SanError};
+use crate::r#move::Move;
+use std::fmt;
+
+// Extended Position Description: the first four FEN fields followed by operations, each an
+// opcode with its operands and a closing ';'. Operands keep their order; quoted strings are
+// stored without the quotes.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EpdError {
+    Io(String),
+    BadPosition(String),
+    Fen(FenError),
+    Unterminated(String),
+    Move { opcode: String, error: SanError },
+    /// An error in the line of a file, numbered from 1.
+    Line(usize, Box<EpdError>),
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "unable to read EPD: {}", e),
+            Self::BadPosition(line) => write!(f, "EPD needs a position of four FEN fields: '{}'", line),
+            Self::Fen(e) => write!(f, "invalid position: {}", e),
+            Self::Unterminated(line) => write!(f, "unterminated string in '{}'", line),
+            Self::Move { opcode, error } => write!(f, "{}: {}", opcode, error),
+            Self::Line(n, e) => write!(f, "line {}: {}", n, e),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Epd {
+    /// Piece placement, side to move, castling rights and en passant square.
+    pub position: String,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+/// Opcodes whose operand is always written as a string.
+fn is_string_opcode(opcode: &str) -> bool {
+    opcode == "id" || (opcode.len() == 2 && opcode.starts_with('c') && opcode.as_bytes()[1].is_ascii_digit())
+}
+
+impl Epd {
+    /// The position of `board` without operations.
+    pub fn from_board(board: &Board) -> Self {
+        let fen = board.to_fen();
+        Epd { position: fen.split_whitespace().take(4).collect::<Vec<_>>().join(" "), operations: Vec::new() }
+    }
+
+    pub fn parse(line: &str) -> Result<Self, EpdError> {
+        let mut rest = line.trim();
+        let mut fields = Vec::new();
+        for _ in 0..4 {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() || field.contains(';') {
+                return Err(EpdError::BadPosition(line.to_string()));
+            }
+            fields.push(field);
+            rest = tail.trim_start();
+        }
+
+        let mut operations = Vec::new();
+        let mut chars = rest.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace() || *c == ';').is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+            let opcode: String = std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && *c != ';')).collect();
+            let mut operands = Vec::new();
+            loop {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                match chars.next() {
+                    None | Some(';') => break,
+                    Some('"') => {
+                        let mut operand = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('\\') => operand.extend(chars.next()),
+                                Some('"') => break,
+                                Some(c) => operand.push(c),
+                                None => return Err(EpdError::Unterminated(line.to_string())),
+                            }
+                        }
+                        operands.push(operand);
+                    }
+                    Some(c) => {
+                        let mut operand = String::from(c);
+                        operand.extend(std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && *c != ';')));
+                        operands.push(operand);
+                    }
+                }
+            }
+            operations.push((opcode, operands));
+        }
+        let epd = Epd { position: fields.join(" "), operations };
+        Board::try_from_fen(&epd.fen()).map_err(EpdError::Fen)?;
+        Ok(epd)
+    }
+
+    /// All positions of an EPD file, skipping blank lines and '#' comments.
+    pub fn parse_all(text: &str) -> Result<Vec<Self>, EpdError> {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| Epd::parse(line).map_err(|e| EpdError::Line(i + 1, Box::new(e))))
+            .collect()
+    }
+
+    pub fn load(path: &str) -> Result<Vec<Self>, EpdError> {
+        let text = std::fs::read_to_string(path).map_err(|e| EpdError::Io(format!("{}: {}", path, e)))?;
+        Epd::parse_all(&text)
+    }
+
+    /// The position as FEN, with the move counters of the `hmvc` and `fmvn` opcodes when present.
+    pub fn fen(&self) -> String {
+        let halfmove = self.string("hmvc").unwrap_or("0");
+        let fullmove = self.string("fmvn").unwrap_or("1");
+        format!("{} {} {}", self.position, halfmove, fullmove)
+    }
+
+    /// The position, checked by `parse`. Panics if the fields were since changed to an invalid
+    /// position or move counter.
+    pub fn board(&self) -> Board {
+        Board::from_fen(&self.fen())
+    }
+
+    pub fn get(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(op, _)| op == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    /// The first operand of `opcode`.
+    pub fn string(&self, opcode: &str) -> Option<&str> {
+        self.get(opcode)?.first().map(String::as_str)
+    }
+
+    /// Replaces the operands of `opcode`, or appends the operation.
+    pub fn set(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(op, _)| op == opcode) {
+            Some(operation) => operation.1 = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    pub fn remove(&mut self, opcode: &str) {
+        self.operations.retain(|(op, _)| op != opcode);
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.string("id")
+    }
+
+    /// Comment `c0` to `c9`.
+    pub fn comment(&self, n: usize) -> Option<&str> {
+        self.string(&format!("c{}", n))
+    }
+
+    /// Moves to mate (`dm`).
+    pub fn direct_mate(&self) -> Option<u32> {
+        self.string("dm")?.parse().ok()
+    }
+
+    /// Analysis depth (`acd`).
+    pub fn depth(&self) -> Option<u32> {
+        self.string("acd")?.parse().ok()
+    }
+
+    /// Evaluation in centipawns for the side to move (`ce`).
+    pub fn centipawns(&self) -> Option<i32> {
+        self.string("ce")?.parse().ok()
+    }
+
+    /// Expected perft counts of a perft suite (`D1 20; D2 400; ...`), by increasing depth.
+    pub fn perft_counts(&self) -> Vec<(u8, u64)> {
+        let mut counts: Vec<(u8, u64)> = self
+            .operations
+            .iter()
+            .filter_map(|(opcode, operands)| Some((opcode.strip_prefix('D')?.parse().ok()?, operands.first()?.parse().ok()?)))
+            .collect();
+        counts.sort();
+        counts
+    }
+
+    /// The SAN operands of `opcode`, each a move of the position.
+    pub fn moves(&self, opcode: &str) -> Result<Vec<Move>, EpdError> {
+        let board = self.board();
+        self.get(opcode)
+            .unwrap_or(&[])
+            .iter()
+            .map(|san| Move::from_san(san, &board).map_err(|error| EpdError::Move { opcode: opcode.to_string(), error }))
+            .collect()
+    }
+
+    pub fn best_moves(&self) -> Result<Vec<Move>, EpdError> {
+        self.moves("bm")
+    }
+
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, EpdError> {
+        self.moves("am")
+    }
+
+    /// The predicted variation (`pv`), played one move after the other.
+    pub fn pv(&self) -> Result<Vec<Move>, EpdError> {
+        let mut board = self.board();
+        let mut moves = Vec::new();
+        for san in self.get("pv").unwrap_or(&[]) {
+            let mv = Move::from_san(san, &board).map_err(|error| EpdError::Move { opcode: "pv".to_string(), error })?;
+            board.make_move(mv);
+            moves.push(mv);
+        }
+        Ok(moves)
+    }
+
+    /// Points for playing `mv`: from the STS tables when present (`c9` moves in coordinate
+    /// notation, `c8` their points), else full points for a best move that is not to be avoided.
+    pub fn points(&self, mv: Move) -> Result<u32, EpdError> {
+        if let (Some(moves), Some(points)) = (self.comment(9), self.comment(8)) {
+            let uci = mv.to_algebraic();
+            return Ok(moves.split_whitespace().zip(points.split_whitespace()).find(|(m, _)| *m == uci).and_then(|(_, p)| p.parse().ok()).unwrap_or(0));
+        }
+        let best = self.get("bm").is_none() || self.best_moves()?.contains(&mv);
+        Ok(if best && !self.avoid_moves()?.contains(&mv) { 100 } else { 0 })
+    }
+
+    /// Points for the best answer.
+    pub fn max_points(&self) -> u32 {
+        self.comment(8).and_then(|points| points.split_whitespace().filter_map(|p| p.parse().ok()).max()).unwrap_or(100)
+    }
+
+    /// Sets `opcode` to moves of the position, written in SAN.
+    pub fn set_moves(&mut self, opcode: &str, moves: &[Move]) {
+        let board = self.board();
+        self.set(opcode, moves.iter().map(|mv| mv.to_san(&board)).collect());
+    }
+
+    pub fn set_pv(&mut self, moves: &[Move]) {
+        let mut board = self.board();
+        let sans = moves
+            .iter()
+            .map(|&mv| {
+                let san = mv.to_san(&board);
+                board.make_move(mv);
+                san
+            })
+            .collect();
+        self.set("pv", sans);
+    }
+}
+
+impl fmt::Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.position)?;
+        for (opcode, operands) in &self.operations {
+            write!(f, " {}", opcode)?;
+            for operand in operands {
+                if is_string_opcode(opcode) || operand.is_empty() || operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"') {
+                    write!(f, " \"{}\"", operand.replace('\\', "\\\\").replace('"', "\\\""))?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use crate::board::{Board, Color, PieceType};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use super::kpk::probe_kpk;
+use super::params::EvalParams;
+
+/// Full endgame weight; `scale_factor` results are out of this.
+pub const SCALE_NORMAL: i32 = 64;
+
+/// Base score of a won ending, well above any positional score but far away from mate scores.
+pub const KNOWN_WIN: i32 = 10_000;
+
+const DARK_SQUARES: u64 = 0xAA55_AA55_AA55_AA55;
+const PIECE_CODES: [char; 5] = ['P', 'N', 'B', 'R', 'Q'];
+
+// Strong side relative score, `None` to fall back to the normal evaluation
+type EvalFn = fn(&Board, Color, &EvalParams) -> Option<i32>;
+
+/// A specialised evaluation for one material signature, `strong` is the side with the extra material.
+#[derive(Clone, Copy)]
+struct Endgame {
+    name: &'static str,
+    strong: Color,
+    evaluate: EvalFn,
+}
+
+/// Packs the piece counts (pawns to queens, 4 bits each, white in the low half) of `board`.
+/// Two positions share a key exactly when they have the same material.
+pub fn material_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for color in [Color::White, Color::Black] {
+        for pt in 0..5 {
+            let count = board.pieces[pt][color as usize].count_ones().min(15) as u64;
+            key |= count << (4 * (pt + 5 * color as usize));
+        }
+    }
+    key
+}
+
+/// Material key of an ending written like "KBNK": the first king's pieces belong to `strong`.
+/// Panics on malformed codes, they are compile time constants.
+pub fn material_key_from_code(code: &str, strong: Color) -> u64 {
+    let weak_start = code[1..].find('K').expect("unable to parse endgame code: missing second king") + 1;
+    let mut key = 0;
+    for (pieces, color) in [(&code[1..weak_start], strong), (&code[weak_start + 1..], !strong)] {
+        for c in pieces.chars() {
+            let pt = PIECE_CODES.iter().position(|&p| p == c).expect("unable to parse endgame code: invalid piece");
+            key += 1 << (4 * (pt + 5 * color as usize));
+        }
+    }
+    key
+}
+
+fn endgames() -> &'static HashMap<u64, Endgame> {
+    static ENDGAMES: OnceLock<HashMap<u64, Endgame>> = OnceLock::new();
+    ENDGAMES.get_or_init(|| {
+        let mut codes: Vec<(&'static str, EvalFn)> = vec![
+            ("KK", draw),
+            ("KNK", draw),
+            ("KBK", draw),
+            ("KNNK", draw),
+            ("KPK", evaluate_kpk),
+            ("KBNK", evaluate_kbnk),
+            ("KRK", evaluate_mop_up),
+            ("KQK", evaluate_mop_up),
+        ];
+        for code in ["KBPK", "KBPPK", "KBPPPK", "KBPPPPK"] {
+            codes.push((code, evaluate_kbpk));
+        }
+
+        let mut table = HashMap::new();
+        for (name, evaluate) in codes {
+            for strong in [Color::White, Color::Black] {
+                table.insert(material_key_from_code(name, strong), Endgame { name, strong, evaluate });
+            }
+        }
+        table
+    })
+}
+
+fn distance(a: usize, b: usize) -> i32 {
+    ((a % 8).abs_diff(b % 8)).max((a / 8).abs_diff(b / 8)) as i32
+}
+
+/// 0 in the centre up to 6 in the corners.
+fn edge_distance_bonus(square: usize) -> i32 {
+    let (file, rank) = ((square % 8) as i32, (square / 8) as i32);
+    (3 - file.min(7 - file)) + (3 - rank.min(7 - rank))
+}
+
+fn king_square(board: &Board, color: Color) -> usize {
+    board.pieces[PieceType::King as usize][color as usize].trailing_zeros() as usize
+}
+
+fn non_pawn_material(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    (1..5).map(|pt| board.pieces[pt][color as usize].count_ones() as i32 * params.eg_material[pt]).sum()
+}
+
+fn draw(_: &Board, _: Color, _: &EvalParams) -> Option<i32> {
+    Some(0)
+}
+
+/// Drives the lone king to the edge and brings the strong king closer (KRK, KQK, ...).
+fn evaluate_mop_up(board: &Board, strong: Color, params: &EvalParams) -> Option<i32> {
+    let strong_king = king_square(board, strong);
+    let weak_king = king_square(board, !strong);
+
+    Some(KNOWN_WIN
+        + non_pawn_material(board, strong, params)
+        + 20 * edge_distance_bonus(weak_king)
+        + 10 * (7 - distance(strong_king, weak_king)))
+}
+
+/// Exact bitbase result; a won pawn is worth more the closer it is to promotion.
+fn evaluate_kpk(board: &Board, strong: Color, params: &EvalParams) -> Option<i32> {
+    let pawn = board.pieces[PieceType::Pawn as usize][strong as usize].trailing_zeros() as i32;
+    let relative_rank = if strong == Color::White { pawn / 8 } else { 7 - pawn / 8 };
+
+    match probe_kpk(board)? {
+        true => Some(KNOWN_WIN + params.eg_material[PieceType::Pawn as usize] + 20 * relative_rank),
+        false => Some(0),
+    }
+}
+
+/// Like the mop-up, but the lone king can only be mated in a corner of the bishop's colour.
+fn evaluate_kbnk(board: &Board, strong: Color, params: &EvalParams) -> Option<i32> {
+    let strong_king = king_square(board, strong);
+    let weak_king = king_square(board, !strong);
+    let bishop = board.pieces[PieceType::Bishop as usize][strong as usize];
+
+    // a1/h8 are dark, a8/h1 light
+    let corners = if bishop & DARK_SQUARES != 0 { [0, 63] } else { [56, 7] };
+    let corner_distance = corners.iter().map(|&c| distance(weak_king, c)).min().unwrap();
+
+    Some(KNOWN_WIN
+        + non_pawn_material(board, strong, params)
+        + 40 * (7 - corner_distance)
+        + 10 * edge_distance_bonus(weak_king)
+        + 10 * (7 - distance(strong_king, weak_king)))
+}
+
+/// Bishop and rook pawns: a draw when the bishop does not control the promotion square and the
+/// defending king already guards it. Otherwise the normal evaluation is good enough.
+fn evaluate_kbpk(board: &Board, strong: Color, _: &EvalParams) -> Option<i32> {
+    let pawns = board.pieces[PieceType::Pawn as usize][strong as usize];
+    let bishop = board.pieces[PieceType::Bishop as usize][strong as usize];
+    let file_a = super::FILE_A_MASK;
+
+    let rook_file = if pawns & !file_a == 0 {
+        Some(0)
+    } else if pawns & !(file_a << 7) == 0 {
+        Some(7)
+    } else {
+        None
+    };
+
+    if let Some(file) = rook_file {
+        let promotion_square = if strong == Color::White { 56 + file } else { file };
+        let wrong_bishop = ((bishop & DARK_SQUARES) != 0) != ((1_u64 << promotion_square) & DARK_SQUARES != 0);
+        if wrong_bishop && distance(king_square(board, !strong), promotion_square) <= 1 {
+            return Some(0);
+        }
+    }
+    None
+}
+
+/// Specialised evaluation for known endings, white relative, with the ending's name.
+pub fn probe_endgame(board: &Board, params: &EvalParams) -> Option<(&'static str, i32)> {
+    // Every handled ending has at most one side with more than a bare king and little material
+    if board.phase > 4 {
+        return None;
+    }
+
+    let endgame = endgames().get(&material_key(board))?;
+    let score = (endgame.evaluate)(board, endgame.strong, params)?;
+    Some((endgame.name, if endgame.strong == Color::White { score } else { -score }))
+}
+
+/// Endgame scale factor (out of `SCALE_NORMAL`) for drawish material configurations.
+/// `eg_score` is the white relative endgame score and decides which side is pressing.
+pub fn scale_factor(board: &Board, eg_score: i32, params: &EvalParams) -> i32 {
+    let strong = if eg_score >= 0 { Color::White } else { Color::Black };
+    let weak = !strong;
+    let bishops = &board.pieces[PieceType::Bishop as usize];
+
+    // Pawnless: a material edge of a minor piece or less is rarely enough
+    if board.pieces[PieceType::Pawn as usize][strong as usize] == 0 {
+        let strong_material = non_pawn_material(board, strong, params);
+        let weak_material = non_pawn_material(board, weak, params);
+        let material = &params.eg_material;
+        if strong_material - weak_material <= material[PieceType::Bishop as usize] {
+            return if strong_material < material[PieceType::Rook as usize] {
+                0
+            } else if weak_material <= material[PieceType::Bishop as usize] {
+                4
+            } else {
+                14
+            };
+        }
+    }
+
+    // Opposite coloured bishops
+    if bishops[0].count_ones() == 1
+        && bishops[1].count_ones() == 1
+        && ((bishops[0] & DARK_SQUARES) == 0) != ((bishops[1] & DARK_SQUARES) == 0)
+    {
+        let other_pieces = (1..5)
+            .filter(|&pt| pt != PieceType::Bishop as usize)
+            .any(|pt| board.pieces[pt][0] | board.pieces[pt][1] != 0);
+        return if other_pieces { 46 } else { 22 };
+    }
+
+    SCALE_NORMAL
+}
@@ -0,0 +1,164 @@
+use crate::board::{Board, Color, PieceType};
+use crate::movegen::attacks::{bishop_attacks, rook_attacks};
+use crate::movegen::tables::{KING_ATTACKS, KNIGHT_ATTACKS};
+use super::params::EvalParams;
+use super::{forward_ranks, FILE_A_MASK};
+
+// Compiled in defaults, the evaluation reads the active `EvalParams`
+
+// Pawn shield: indexed by the distance (in ranks) of the closest own pawn in front of the king.
+// Index 0 is unused, 4 means "no shield pawn on this file (or too far away)".
+pub const SHIELD_PENALTY: [i32; 5] = [0, 0, 12, 22, 30];
+
+// Pawn storm: indexed by the distance (in ranks) of the closest enemy pawn in front of the king.
+// A pawn directly in front of the king (distance 1) is blocked and therefore less dangerous.
+pub const STORM_PENALTY: [i32; 5] = [0, 5, 30, 15, 0];
+
+pub const SEMI_OPEN_FILE_PENALTY: i32 = 15;
+pub const OPEN_FILE_PENALTY: i32 = 25;
+
+// Attack units per attacked king zone square, indexed by PieceType.
+pub const ATTACK_WEIGHT: [i32; 6] = [0, 2, 2, 3, 5, 0];
+
+// Percentage of the safety table that applies, indexed by number of attacking pieces.
+// A single attacker is hardly ever a real threat.
+pub const ATTACKER_COUNT_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+// Non-linear penalty indexed by attack units, so coordinated attacks hurt much more.
+pub const SAFETY_TABLE: [i32; 64] = [
+    0,   0,   1,   2,   3,   5,   7,   9,
+    12,  15,  18,  22,  26,  30,  35,  39,
+    44,  50,  56,  62,  68,  75,  82,  85,
+    89,  97, 105, 113, 122, 131, 140, 150,
+    169, 180, 191, 202, 213, 225, 237, 248,
+    260, 272, 283, 295, 307, 319, 330, 342,
+    354, 366, 377, 389, 401, 412, 424, 436,
+    448, 459, 471, 483, 494, 500, 500, 500,
+];
+
+/// Distance in ranks between the king and the closest pawn of `pawns` (already restricted
+/// to the squares in front of the king), capped at 4.
+fn closest_pawn_distance(pawns: u64, king_rank: usize, color: Color) -> usize {
+    if pawns == 0 {
+        return 4;
+    }
+    let pawn_rank = match color {
+        Color::White => pawns.trailing_zeros() as usize / 8,
+        Color::Black => (63 - pawns.leading_zeros() as usize) / 8,
+    };
+    pawn_rank.abs_diff(king_rank).min(4)
+}
+
+/// Raw king safety features of one side, before any weights are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KingTerms {
+    pub shield: [i32; 5], // Files per shield pawn distance
+    pub storm: [i32; 5],  // Files per storming pawn distance
+    pub semi_open_files: i32,
+    pub open_files: i32,
+    pub attack_units: usize, // Already capped to the size of `SAFETY_TABLE`
+    pub attackers: usize,    // Already capped to the size of `ATTACKER_COUNT_SCALE`
+}
+
+/// Collects pawn cover and king zone attack features around the king of `color`.
+pub fn king_terms(board: &Board, color: Color, params: &EvalParams) -> KingTerms {
+    let mut terms = KingTerms::default();
+
+    let king = board.pieces[PieceType::King as usize][color as usize];
+    if king == 0 {
+        return terms;
+    }
+    let king_square = king.trailing_zeros() as usize;
+    let king_file = king_square % 8;
+    let king_rank = king_square / 8;
+
+    // 1. Pawn shield, pawn storm and open files
+    let own_pawns = board.pieces[PieceType::Pawn as usize][color as usize];
+    let enemy_pawns = board.pieces[PieceType::Pawn as usize][!color as usize];
+    let in_front = forward_ranks(king_rank, color);
+
+    for file in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+        let file_mask = FILE_A_MASK << file;
+
+        let shield = own_pawns & file_mask & in_front;
+        terms.shield[closest_pawn_distance(shield, king_rank, color)] += 1;
+
+        let storm = enemy_pawns & file_mask & in_front;
+        terms.storm[closest_pawn_distance(storm, king_rank, color)] += 1;
+
+        if own_pawns & file_mask == 0 {
+            if enemy_pawns & file_mask == 0 {
+                terms.open_files += 1;
+            } else {
+                terms.semi_open_files += 1;
+            }
+        }
+    }
+
+    // 2. Attacks on the king zone
+    let king_zone = KING_ATTACKS[king_square] | king;
+    let enemy = !color as usize;
+    let occupancy = board.all_occupied;
+
+    let attack_weight = &params.attack_weight;
+    let mut attackers = 0_usize;
+    let mut attack_units = 0_i32;
+
+    for pt in [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen] {
+        let mut pieces = board.pieces[pt as usize][enemy];
+        while pieces != 0 {
+            let sq = pieces.trailing_zeros() as usize;
+            pieces &= pieces - 1;
+
+            let attacks = match pt {
+                PieceType::Knight => KNIGHT_ATTACKS[sq],
+                PieceType::Bishop => bishop_attacks(sq, occupancy),
+                PieceType::Rook => rook_attacks(sq, occupancy),
+                _ => bishop_attacks(sq, occupancy) | rook_attacks(sq, occupancy),
+            };
+
+            let zone_hits = (attacks & king_zone).count_ones() as i32;
+            if zone_hits > 0 {
+                attackers += 1;
+                attack_units += attack_weight[pt as usize] * zone_hits;
+            }
+        }
+    }
+
+    terms.attack_units = (attack_units as usize).min(SAFETY_TABLE.len() - 1);
+    terms.attackers = attackers.min(ATTACKER_COUNT_SCALE.len() - 1);
+    terms
+}
+
+fn cover_penalty(terms: &KingTerms, params: &EvalParams) -> i32 {
+    let mut penalty = terms.semi_open_files * params.semi_open_file_penalty + terms.open_files * params.open_file_penalty;
+    for distance in 0..5 {
+        penalty += terms.shield[distance] * params.shield_penalty[distance];
+        penalty += terms.storm[distance] * params.storm_penalty[distance];
+    }
+    penalty
+}
+
+fn attack_penalty(terms: &KingTerms, params: &EvalParams) -> i32 {
+    params.safety_table[terms.attack_units] * params.attacker_count_scale[terms.attackers] / 100
+}
+
+/// Pawn shield, pawn storm and open file terms around the king of `color`.
+/// Returns a penalty, i.e. a positive value is bad for `color`.
+pub fn pawn_cover_penalty(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    cover_penalty(&king_terms(board, color, params), params)
+}
+
+/// Penalty for enemy pieces attacking the zone around the king of `color`.
+/// Each attacking piece adds weighted attack units, which are mapped through
+/// `SAFETY_TABLE` and scaled by the number of attackers.
+pub fn king_attack_penalty(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    attack_penalty(&king_terms(board, color, params), params)
+}
+
+/// Middlegame king safety score for `color` (positive is good for `color`).
+/// There is no endgame counterpart, so the term fades out with the game phase.
+pub fn evaluate_king_safety(board: &Board, color: Color, params: &EvalParams) -> i32 {
+    let terms = king_terms(board, color, params);
+    -(cover_penalty(&terms, params) + attack_penalty(&terms, params))
+}
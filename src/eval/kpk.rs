@@ -0,0 +1,189 @@
+use crate::board::{Board, Color, PieceType};
+use crate::movegen::tables::KING_ATTACKS;
+use std::sync::OnceLock;
+use super::pawns::pawn_attacks;
+
+// Positions are normalised to a white pawn on files a-d (ranks 2-7):
+// index = ((pawn * 2 + side to move) * 64 + black king) * 64 + white king
+const PAWN_SQUARES: usize = 24;
+const KPK_SIZE: usize = PAWN_SQUARES * 2 * 64 * 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+fn index(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> usize {
+    let pawn_index = (pawn / 8 - 1) * 4 + pawn % 8;
+    ((pawn_index * 2 + !white_to_move as usize) * 64 + black_king) * 64 + white_king
+}
+
+/// Win/draw bitbase with one bit per position, `true` meaning white wins.
+pub struct KpkBitbase {
+    bits: Vec<u64>,
+}
+
+impl KpkBitbase {
+    /// Builds the bitbase by retrograde analysis: mark immediate wins and draws, then keep
+    /// propagating until no position changes. Whatever is still unknown is a draw.
+    pub fn generate() -> Self {
+        let mut outcomes = vec![Outcome::Invalid; KPK_SIZE];
+        let mut positions = Vec::with_capacity(KPK_SIZE);
+
+        for rank in 1..7 {
+            for file in 0..4 {
+                let pawn = rank * 8 + file;
+                for white_to_move in [true, false] {
+                    for black_king in 0..64 {
+                        for white_king in 0..64 {
+                            let i = index(white_to_move, white_king, black_king, pawn);
+                            outcomes[i] = classify_initial(white_to_move, white_king, black_king, pawn);
+                            if outcomes[i] == Outcome::Unknown {
+                                positions.push((i, white_to_move, white_king, black_king, pawn));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(i, white_to_move, white_king, black_king, pawn) in &positions {
+                if outcomes[i] != Outcome::Unknown {
+                    continue;
+                }
+                let outcome = if white_to_move {
+                    classify_white(&outcomes, white_king, black_king, pawn)
+                } else {
+                    classify_black(&outcomes, white_king, black_king, pawn)
+                };
+                if outcome != Outcome::Unknown {
+                    outcomes[i] = outcome;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut bits = vec![0_u64; KPK_SIZE / 64];
+        for (i, outcome) in outcomes.iter().enumerate() {
+            if *outcome == Outcome::Win {
+                bits[i / 64] |= 1 << (i % 64);
+            }
+        }
+        Self { bits }
+    }
+
+    /// Whether white wins with the given normalised position (pawn on files a-d, ranks 2-7).
+    pub fn is_win(&self, white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> bool {
+        let i = index(white_to_move, white_king, black_king, pawn);
+        self.bits[i / 64] & (1 << (i % 64)) != 0
+    }
+}
+
+fn classify_initial(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> Outcome {
+    let pawn_attacks = pawn_attacks(1 << pawn, Color::White);
+    let promotion = pawn + 8;
+
+    if white_king == black_king
+        || white_king == pawn
+        || black_king == pawn
+        || KING_ATTACKS[white_king] & (1 << black_king) != 0
+        || (white_to_move && pawn_attacks & (1 << black_king) != 0)
+    {
+        return Outcome::Invalid;
+    }
+
+    if white_to_move {
+        // Promotes safely on the next move
+        if pawn / 8 == 6
+            && white_king != promotion
+            && black_king != promotion
+            && (KING_ATTACKS[black_king] & (1 << promotion) == 0 || KING_ATTACKS[white_king] & (1 << promotion) != 0)
+        {
+            return Outcome::Win;
+        }
+    } else {
+        // Stalemated, or the pawn can be taken
+        let escapes = KING_ATTACKS[black_king] & !(KING_ATTACKS[white_king] | pawn_attacks);
+        if escapes == 0 || (escapes & (1 << pawn) != 0) {
+            return Outcome::Draw;
+        }
+    }
+    Outcome::Unknown
+}
+
+/// White needs one move into a won position.
+fn classify_white(outcomes: &[Outcome], white_king: usize, black_king: usize, pawn: usize) -> Outcome {
+    let mut king_moves = KING_ATTACKS[white_king] & !KING_ATTACKS[black_king] & !(1 << pawn);
+    while king_moves != 0 {
+        let to = king_moves.trailing_zeros() as usize;
+        king_moves &= king_moves - 1;
+        if outcomes[index(false, to, black_king, pawn)] == Outcome::Win {
+            return Outcome::Win;
+        }
+    }
+
+    // Single and double pushes, promotions are covered by `classify_initial`
+    let push = pawn + 8;
+    if pawn / 8 < 6 && push != white_king && push != black_king {
+        if outcomes[index(false, white_king, black_king, push)] == Outcome::Win {
+            return Outcome::Win;
+        }
+        let double = push + 8;
+        if pawn / 8 == 1 && double != white_king && double != black_king && outcomes[index(false, white_king, black_king, double)] == Outcome::Win {
+            return Outcome::Win;
+        }
+    }
+    Outcome::Unknown
+}
+
+/// Black draws with one move into a drawn position and loses if every move loses.
+fn classify_black(outcomes: &[Outcome], white_king: usize, black_king: usize, pawn: usize) -> Outcome {
+    let mut king_moves = KING_ATTACKS[black_king] & !(KING_ATTACKS[white_king] | pawn_attacks(1 << pawn, Color::White));
+    let mut all_win = true;
+    while king_moves != 0 {
+        let to = king_moves.trailing_zeros() as usize;
+        king_moves &= king_moves - 1;
+        match outcomes[index(true, white_king, to, pawn)] {
+            Outcome::Draw => return Outcome::Draw,
+            Outcome::Win => {}
+            _ => all_win = false,
+        }
+    }
+    if all_win { Outcome::Win } else { Outcome::Unknown }
+}
+
+pub fn kpk_bitbase() -> &'static KpkBitbase {
+    static BITBASE: OnceLock<KpkBitbase> = OnceLock::new();
+    BITBASE.get_or_init(KpkBitbase::generate)
+}
+
+/// Exact result of a king and pawn versus king position: `Some(true)` if the side with the
+/// pawn wins, `Some(false)` for a draw, `None` for any other material.
+pub fn probe_kpk(board: &Board) -> Option<bool> {
+    let pawns = &board.pieces[PieceType::Pawn as usize];
+    if board.all_occupied.count_ones() != 3 || (pawns[0] | pawns[1]).count_ones() != 1 {
+        return None;
+    }
+
+    let strong = if pawns[0] != 0 { Color::White } else { Color::Black };
+    let kings = &board.pieces[PieceType::King as usize];
+    let mut strong_king = kings[strong as usize].trailing_zeros() as usize;
+    let mut weak_king = kings[!strong as usize].trailing_zeros() as usize;
+    let mut pawn = pawns[strong as usize].trailing_zeros() as usize;
+
+    // Black pawns are seen from the other side of the board, right side pawns are mirrored
+    if strong == Color::Black {
+        (strong_king, weak_king, pawn) = (strong_king ^ 56, weak_king ^ 56, pawn ^ 56);
+    }
+    if pawn % 8 >= 4 {
+        (strong_king, weak_king, pawn) = (strong_king ^ 7, weak_king ^ 7, pawn ^ 7);
+    }
+
+    Some(kpk_bitbase().is_win(board.side_to_move == strong, strong_king, weak_king, pawn))
+}
@@ -0,0 +1,85 @@
+pub mod endgame;
+pub mod king_safety;
+pub mod kpk;
+pub mod nnue;
+pub mod params;
+pub mod pawns;
+pub mod trace;
+pub mod tune;
+
+use crate::board::*;
+use crate::psqt::MAX_PHASE;
+use endgame::{probe_endgame, scale_factor, SCALE_NORMAL};
+use king_safety::evaluate_king_safety;
+use nnue::{evaluate_nnue, AccumulatorStack, Network};
+use params::EvalTables;
+use pawns::probe_pawn_structure;
+
+pub use trace::{trace, trace_with_network, EvalTrace};
+
+pub const FILE_A_MASK: u64 = 0x0101_0101_0101_0101;
+
+/// Returns all squares in front of `rank` from the perspective of `color`.
+pub fn forward_ranks(rank: usize, color: Color) -> u64 {
+    match color {
+        Color::White if rank < 7 => !0_u64 << (8 * (rank + 1)),
+        Color::Black => (1_u64 << (8 * rank)) - 1,
+        _ => 0,
+    }
+}
+
+/// Returns the given file plus its neighbouring files.
+pub fn adjacent_files_mask(file: usize) -> u64 {
+    let file_mask = FILE_A_MASK << file;
+    let west = if file > 0 { file_mask >> 1 } else { 0 };
+    let east = if file < 7 { file_mask << 1 } else { 0 };
+    file_mask | west | east
+}
+
+/// White relative evaluation: the `network` if given, else `evaluate_board`. Known endings are
+/// always evaluated by the endgame module.
+pub fn evaluate(board: &Board, tables: &EvalTables, network: Option<&Network>) -> i32 {
+    network_or_classical(board, tables, network.map(|network| evaluate_nnue(board, network)))
+}
+
+/// Like `evaluate`, with the network and its first layer taken from the search's `accumulators`.
+pub fn evaluate_with(board: &Board, tables: &EvalTables, accumulators: &AccumulatorStack) -> i32 {
+    network_or_classical(board, tables, accumulators.evaluate(board))
+}
+
+fn network_or_classical(board: &Board, tables: &EvalTables, nnue_score: Option<i32>) -> i32 {
+    if let Some((_, score)) = probe_endgame(board, &tables.params) {
+        return score;
+    }
+    match nnue_score {
+        Some(score) if board.side_to_move == Color::White => score,
+        Some(score) => -score,
+        None => evaluate_board(board, tables),
+    }
+}
+
+/// Classical white relative evaluation with the weights of `tables`.
+pub fn evaluate_board(board: &Board, tables: &EvalTables) -> i32 {
+    let params = &tables.params;
+    if let Some((_, score)) = probe_endgame(board, params) {
+        return score;
+    }
+
+    // PSQT and material, kept up to date incrementally by `Board` for the built-in tables
+    let (mut mg_score, mut eg_score) = tables.psqt_sums(board);
+
+    let pawn_entry = probe_pawn_structure(board, tables);
+    mg_score += pawn_entry.mg_score;
+    eg_score += pawn_entry.eg_score;
+
+    mg_score += evaluate_king_safety(board, Color::White, params);
+    mg_score -= evaluate_king_safety(board, Color::Black, params);
+
+    eg_score = eg_score * scale_factor(board, eg_score, params) / SCALE_NORMAL;
+
+    let phase = board.phase.min(MAX_PHASE);
+    let mg_phase = phase;
+    let eg_phase = MAX_PHASE - phase;
+
+    ((mg_score * mg_phase) + (eg_score * eg_phase)) / MAX_PHASE
+}
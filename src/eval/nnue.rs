@@ -0,0 +1,320 @@
+use crate::board::{Board, Color, PieceType, PIECE_TYPES};
+use crate::r#move::PieceChanges;
+use std::fmt;
+use std::fs;
+
+// Network file layout (all little endian):
+//   magic "YKNN", version u32, input count u32, hidden size u32
+//   feature weights i16 [INPUTS][HIDDEN], feature biases i16 [HIDDEN]
+//   output weights i8 [2 * HIDDEN] (side to move half first), output bias i32
+pub const NNUE_MAGIC: &[u8; 4] = b"YKNN";
+pub const NNUE_FILE_VERSION: u32 = 1;
+
+/// HalfKA inputs: own king square x (6 piece types x 2 relative colors) x piece square.
+pub const INPUTS: usize = 64 * 12 * 64;
+pub const HIDDEN: usize = 256;
+
+// Quantisation: accumulator activations are clipped to [0, QA], output weights are scaled by QB
+pub const QA: i32 = 255;
+pub const QB: i32 = 64;
+// Maps the network output (in sigmoid units) to centipawns
+pub const SCALE: i32 = 400;
+
+const HEADER_SIZE: usize = 16;
+const FILE_SIZE: usize = HEADER_SIZE + (INPUTS * HIDDEN + HIDDEN) * 2 + 2 * HIDDEN + 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NnueError {
+    Io(String),
+    BadMagic,
+    UnsupportedVersion(u32),
+    ArchitectureMismatch { inputs: u32, hidden: u32 },
+    WrongSize { expected: usize, found: usize },
+}
+
+impl fmt::Display for NnueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "unable to read network file: {}", e),
+            Self::BadMagic => write!(f, "not a network file (bad magic)"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported network file version {} (expected {})", v, NNUE_FILE_VERSION),
+            Self::ArchitectureMismatch { inputs, hidden } => write!(
+                f,
+                "network is {}x{}, this build expects {}x{}",
+                inputs, hidden, INPUTS, HIDDEN
+            ),
+            Self::WrongSize { expected, found } => write!(f, "network file has {} bytes, expected {}", found, expected),
+        }
+    }
+}
+
+impl std::error::Error for NnueError {}
+
+/// Quantised network weights.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    pub feature_weights: Vec<i16>, // [INPUTS * HIDDEN], one HIDDEN wide row per feature
+    pub feature_bias: Vec<i16>,    // [HIDDEN]
+    pub output_weights: Vec<i8>,   // [2 * HIDDEN]
+    pub output_bias: i32,
+}
+
+impl Network {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, NnueError> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..4] != NNUE_MAGIC {
+            return Err(NnueError::BadMagic);
+        }
+        let read_u32 = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let version = read_u32(4);
+        if version != NNUE_FILE_VERSION {
+            return Err(NnueError::UnsupportedVersion(version));
+        }
+        let (inputs, hidden) = (read_u32(8), read_u32(12));
+        if inputs as usize != INPUTS || hidden as usize != HIDDEN {
+            return Err(NnueError::ArchitectureMismatch { inputs, hidden });
+        }
+        if bytes.len() != FILE_SIZE {
+            return Err(NnueError::WrongSize { expected: FILE_SIZE, found: bytes.len() });
+        }
+
+        let mut pos = HEADER_SIZE;
+        let mut read_i16s = |count: usize| {
+            let values = bytes[pos..pos + 2 * count]
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                .collect::<Vec<i16>>();
+            pos += 2 * count;
+            values
+        };
+        let feature_weights = read_i16s(INPUTS * HIDDEN);
+        let feature_bias = read_i16s(HIDDEN);
+
+        let output_start = HEADER_SIZE + (INPUTS * HIDDEN + HIDDEN) * 2;
+        let output_weights = bytes[output_start..output_start + 2 * HIDDEN].iter().map(|&b| b as i8).collect();
+        let output_bias = read_u32(output_start + 2 * HIDDEN) as i32;
+
+        Ok(Self { feature_weights, feature_bias, output_weights, output_bias })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(FILE_SIZE);
+        bytes.extend_from_slice(NNUE_MAGIC);
+        for value in [NNUE_FILE_VERSION, INPUTS as u32, HIDDEN as u32] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in self.feature_weights.iter().chain(&self.feature_bias) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend(self.output_weights.iter().map(|&w| w as u8));
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    pub fn load(path: &str) -> Result<Self, NnueError> {
+        let bytes = fs::read(path).map_err(|e| NnueError::Io(format!("{}: {}", path, e)))?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), NnueError> {
+        fs::write(path, self.to_bytes()).map_err(|e| NnueError::Io(format!("{}: {}", path, e)))
+    }
+
+    #[inline(always)]
+    fn feature_row(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * HIDDEN..(feature + 1) * HIDDEN]
+    }
+
+    /// Side to move relative evaluation of the given accumulator in centipawns.
+    pub fn evaluate(&self, accumulator: &Accumulator, side_to_move: Color) -> i32 {
+        let us = &accumulator.values[side_to_move as usize];
+        let them = &accumulator.values[!side_to_move as usize];
+        let (us_weights, them_weights) = self.output_weights.split_at(HIDDEN);
+
+        let output = clipped_dot(us, us_weights) + clipped_dot(them, them_weights) + self.output_bias;
+        // A saturated output times SCALE does not fit into i32
+        (output as i64 * SCALE as i64 / (QA * QB) as i64) as i32
+    }
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+fn clipped_dot(values: &[i16; HIDDEN], weights: &[i8]) -> i32 {
+    clipped_dot_scalar(values, weights)
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+fn clipped_dot(values: &[i16; HIDDEN], weights: &[i8]) -> i32 {
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 support was just checked
+        unsafe { clipped_dot_avx2(values, weights) }
+    } else {
+        clipped_dot_scalar(values, weights)
+    }
+}
+
+#[inline(always)]
+fn clipped_dot_scalar(values: &[i16; HIDDEN], weights: &[i8]) -> i32 {
+    values
+        .iter()
+        .zip(weights)
+        .map(|(&v, &w)| (v as i32).clamp(0, QA) * w as i32)
+        .sum()
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn clipped_dot_avx2(values: &[i16; HIDDEN], weights: &[i8]) -> i32 {
+    use std::arch::x86_64::*;
+
+    assert_eq!(weights.len(), HIDDEN);
+    let zero = _mm256_setzero_si256();
+    let qa = _mm256_set1_epi16(QA as i16);
+    let mut sum = _mm256_setzero_si256();
+
+    for i in (0..HIDDEN).step_by(16) {
+        // SAFETY: i + 16 <= HIDDEN for both slices, unaligned loads are used
+        let (v, w) = unsafe {
+            (
+                _mm256_loadu_si256(values.as_ptr().add(i) as *const __m256i),
+                _mm_loadu_si128(weights.as_ptr().add(i) as *const __m128i),
+            )
+        };
+        let clipped = _mm256_min_epi16(_mm256_max_epi16(v, zero), qa);
+        // 255 * 127 fits into i16, the pairwise products are summed into i32 lanes
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, _mm256_cvtepi8_epi16(w)));
+    }
+
+    let halves = _mm_add_epi32(_mm256_castsi256_si128(sum), _mm256_extracti128_si256(sum, 1));
+    let pairs = _mm_add_epi32(halves, _mm_shuffle_epi32(halves, 0b01_00_11_10));
+    let single = _mm_add_epi32(pairs, _mm_shuffle_epi32(pairs, 0b10_11_00_01));
+    _mm_cvtsi128_si32(single)
+}
+
+/// HalfKA feature index of a piece as seen from `perspective`, whose king is on `king_square`.
+/// Black's view is flipped vertically so both sides share the same weights.
+#[inline(always)]
+pub fn feature_index(perspective: Color, king_square: usize, piece_type: PieceType, color: Color, square: usize) -> usize {
+    let flip = if perspective == Color::White { 0 } else { 56 };
+    let relative_color = (color != perspective) as usize;
+    ((king_square ^ flip) * 12 + relative_color * 6 + piece_type as usize) * 64 + (square ^ flip)
+}
+
+/// First layer outputs for both perspectives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Accumulator {
+    pub values: [[i16; HIDDEN]; 2],
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self { values: [[0; HIDDEN]; 2] }
+    }
+}
+
+impl Accumulator {
+    /// Computes both perspectives of `board` from scratch.
+    pub fn new(board: &Board, network: &Network) -> Self {
+        let mut accumulator = Self::default();
+        for perspective in [Color::White, Color::Black] {
+            accumulator.refresh_perspective(board, network, perspective);
+        }
+        accumulator
+    }
+
+    fn refresh_perspective(&mut self, board: &Board, network: &Network, perspective: Color) {
+        self.values[perspective as usize].copy_from_slice(&network.feature_bias);
+        let king_square = board.pieces[PieceType::King as usize][perspective as usize].trailing_zeros() as usize % 64;
+        for pt in PIECE_TYPES {
+            for color in [Color::White, Color::Black] {
+                let mut pieces = board.pieces[pt as usize][color as usize];
+                while pieces != 0 {
+                    let sq = pieces.trailing_zeros() as usize;
+                    pieces &= pieces - 1;
+                    self.update(network, perspective, king_square, pt, color, sq, true);
+                }
+            }
+        }
+    }
+
+    /// Applies a piece being added (`add`) or removed to one perspective.
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn update(&mut self, network: &Network, perspective: Color, king_square: usize, piece_type: PieceType, color: Color, square: usize, add: bool) {
+        let row = network.feature_row(feature_index(perspective, king_square, piece_type, color, square));
+        let values = &mut self.values[perspective as usize];
+        if add {
+            for (value, weight) in values.iter_mut().zip(row) {
+                *value = value.wrapping_add(*weight);
+            }
+        } else {
+            for (value, weight) in values.iter_mut().zip(row) {
+                *value = value.wrapping_sub(*weight);
+            }
+        }
+    }
+}
+
+/// Accumulators along the line being searched, one per ply from the root. Each move is applied
+/// from the pieces `make_move` lifted and put down. A king move changes every feature of its own
+/// perspective, which is rebuilt instead. Without a network the stack only counts plies.
+pub struct AccumulatorStack<'a> {
+    network: Option<&'a Network>,
+    entries: Vec<Accumulator>,
+    ply: usize,
+}
+
+impl<'a> AccumulatorStack<'a> {
+    pub fn new(root: &Board, network: Option<&'a Network>) -> Self {
+        let entries = match network {
+            Some(network) => vec![Accumulator::new(root, network)],
+            None => Vec::new(),
+        };
+        Self { network, entries, ply: 0 }
+    }
+
+    /// Adds the accumulator of `board`, just reached from the current position by a move that
+    /// made the piece `changes`.
+    pub fn push(&mut self, board: &Board, changes: &PieceChanges) {
+        self.ply += 1;
+        let Some(network) = self.network else { return };
+        if self.entries.len() <= self.ply {
+            self.entries.push(self.entries[self.ply - 1]);
+        }
+        let (parents, children) = self.entries.split_at_mut(self.ply);
+        let child = &mut children[0];
+        *child = parents[self.ply - 1];
+
+        for perspective in [Color::White, Color::Black] {
+            let king_moved = changes.iter().any(|c| c.piece_type == PieceType::King && c.color == perspective);
+            if king_moved {
+                child.refresh_perspective(board, network, perspective);
+                continue;
+            }
+            let king_square = board.pieces[PieceType::King as usize][perspective as usize].trailing_zeros() as usize % 64;
+            for change in changes.iter() {
+                child.update(network, perspective, king_square, change.piece_type, change.color, change.square as usize, change.added);
+            }
+        }
+    }
+
+    /// Goes back to the position before the last `push`.
+    pub fn pop(&mut self) {
+        self.ply -= 1;
+    }
+
+    /// The accumulator of the current position, `None` without a network.
+    pub fn current(&self) -> Option<&Accumulator> {
+        self.network.map(|_| &self.entries[self.ply])
+    }
+
+    /// Side to move relative network evaluation of `board`, the current position, `None`
+    /// without a network.
+    pub fn evaluate(&self, board: &Board) -> Option<i32> {
+        let network = self.network?;
+        Some(network.evaluate(&self.entries[self.ply], board.side_to_move))
+    }
+}
+
+/// Side to move relative network evaluation with the first layer computed from scratch.
+pub fn evaluate_nnue(board: &Board, network: &Network) -> i32 {
+    network.evaluate(&Accumulator::new(board, network), board.side_to_move)
+}
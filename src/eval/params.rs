@@ -0,0 +1,394 @@
+use crate::psqt::*;
+use std::fmt;
+use crate::board::{Board, Color, PIECE_TYPES};
+use std::fs;
+use std::sync::atomic::{AtomicU32, Ordering};
+use super::king_safety::{ATTACKER_COUNT_SCALE, ATTACK_WEIGHT, OPEN_FILE_PENALTY, SAFETY_TABLE, SEMI_OPEN_FILE_PENALTY, SHIELD_PENALTY, STORM_PENALTY};
+use super::pawns::{BACKWARD_PENALTY, DOUBLED_PENALTY, ISOLATED_PENALTY, PASSED_BONUS_EG, PASSED_BONUS_MG};
+
+const PIECE_NAMES: [&str; 6] = ["PAWN", "KNIGHT", "BISHOP", "ROOK", "QUEEN", "KING"];
+
+pub const PARAMS_FILE_VERSION: u32 = 1;
+
+// Sanity bound for any single weight, keeps the eval far away from i32 overflow and mate scores
+const VALUE_LIMIT: i32 = 10_000;
+
+// Offsets of each parameter group inside the tunable vector, see `EvalParams::tunable_mut`
+pub const MG_MATERIAL_OFFSET: usize = 0;
+pub const EG_MATERIAL_OFFSET: usize = MG_MATERIAL_OFFSET + 6;
+pub const MG_PSQT_OFFSET: usize = EG_MATERIAL_OFFSET + 6;
+pub const EG_PSQT_OFFSET: usize = MG_PSQT_OFFSET + 6 * 64;
+pub const DOUBLED_OFFSET: usize = EG_PSQT_OFFSET + 6 * 64;
+pub const ISOLATED_OFFSET: usize = DOUBLED_OFFSET + 2;
+pub const BACKWARD_OFFSET: usize = ISOLATED_OFFSET + 2;
+pub const PASSED_MG_OFFSET: usize = BACKWARD_OFFSET + 2;
+pub const PASSED_EG_OFFSET: usize = PASSED_MG_OFFSET + 8;
+pub const SHIELD_OFFSET: usize = PASSED_EG_OFFSET + 8;
+pub const STORM_OFFSET: usize = SHIELD_OFFSET + 5;
+pub const SEMI_OPEN_FILE_OFFSET: usize = STORM_OFFSET + 5;
+pub const OPEN_FILE_OFFSET: usize = SEMI_OPEN_FILE_OFFSET + 1;
+pub const SAFETY_TABLE_OFFSET: usize = OPEN_FILE_OFFSET + 1;
+pub const TUNABLE_COUNT: usize = SAFETY_TABLE_OFFSET + 64;
+
+/// The complete set of evaluation weights.
+/// PSQTs use the same layout as the tables in `psqt.rs` (a8 first, from white's point of view)
+/// and do not include material.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalParams {
+    pub mg_material: [i32; 6],
+    pub eg_material: [i32; 6],
+    pub mg_psqt: [[i32; 64]; 6],
+    pub eg_psqt: [[i32; 64]; 6],
+
+    pub doubled_penalty: (i32, i32),
+    pub isolated_penalty: (i32, i32),
+    pub backward_penalty: (i32, i32),
+    pub passed_bonus_mg: [i32; 8],
+    pub passed_bonus_eg: [i32; 8],
+
+    pub shield_penalty: [i32; 5],
+    pub storm_penalty: [i32; 5],
+    pub semi_open_file_penalty: i32,
+    pub open_file_penalty: i32,
+    pub safety_table: [i32; 64],
+
+    // Used as lookup indices rather than linear weights, so they are not part of the tunable vector
+    pub attack_weight: [i32; 6],
+    pub attacker_count_scale: [i32; 8],
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            mg_material: MG_MATERIAL,
+            eg_material: EG_MATERIAL,
+            mg_psqt: [MG_PAWN_TABLE, MG_KNIGHT_TABLE, MG_BISHOP_TABLE, MG_ROOK_TABLE, MG_QUEEN_TABLE, MG_KING_TABLE],
+            eg_psqt: [EG_PAWN_TABLE, EG_KNIGHT_TABLE, EG_BISHOP_TABLE, EG_ROOK_TABLE, EG_QUEEN_TABLE, EG_KING_TABLE],
+            doubled_penalty: DOUBLED_PENALTY,
+            isolated_penalty: ISOLATED_PENALTY,
+            backward_penalty: BACKWARD_PENALTY,
+            passed_bonus_mg: PASSED_BONUS_MG,
+            passed_bonus_eg: PASSED_BONUS_EG,
+            shield_penalty: SHIELD_PENALTY,
+            storm_penalty: STORM_PENALTY,
+            semi_open_file_penalty: SEMI_OPEN_FILE_PENALTY,
+            open_file_penalty: OPEN_FILE_PENALTY,
+            safety_table: SAFETY_TABLE,
+            attack_weight: ATTACK_WEIGHT,
+            attacker_count_scale: ATTACKER_COUNT_SCALE,
+        }
+    }
+}
+
+impl EvalParams {
+    /// All linear weights in a fixed order (see the `*_OFFSET` constants).
+    pub fn tunable_mut(&mut self) -> Vec<&mut i32> {
+        let mut out: Vec<&mut i32> = Vec::with_capacity(TUNABLE_COUNT);
+        out.extend(self.mg_material.iter_mut());
+        out.extend(self.eg_material.iter_mut());
+        out.extend(self.mg_psqt.iter_mut().flatten());
+        out.extend(self.eg_psqt.iter_mut().flatten());
+        out.push(&mut self.doubled_penalty.0);
+        out.push(&mut self.doubled_penalty.1);
+        out.push(&mut self.isolated_penalty.0);
+        out.push(&mut self.isolated_penalty.1);
+        out.push(&mut self.backward_penalty.0);
+        out.push(&mut self.backward_penalty.1);
+        out.extend(self.passed_bonus_mg.iter_mut());
+        out.extend(self.passed_bonus_eg.iter_mut());
+        out.extend(self.shield_penalty.iter_mut());
+        out.extend(self.storm_penalty.iter_mut());
+        out.push(&mut self.semi_open_file_penalty);
+        out.push(&mut self.open_file_penalty);
+        out.extend(self.safety_table.iter_mut());
+        debug_assert_eq!(out.len(), TUNABLE_COUNT);
+        out
+    }
+
+    pub fn to_vector(&self) -> Vec<i32> {
+        self.clone().tunable_mut().into_iter().map(|v| *v).collect()
+    }
+
+    /// Builds a parameter set from a tunable vector; non-tunable fields keep their defaults.
+    pub fn from_vector(values: &[i32]) -> Self {
+        let mut params = Self::default();
+        params.set_vector(values);
+        params
+    }
+
+    /// Overwrites all tunable weights, leaving the non-tunable fields untouched.
+    pub fn set_vector(&mut self, values: &[i32]) {
+        assert_eq!(values.len(), TUNABLE_COUNT, "unable to set EvalParams: wrong vector length");
+        for (slot, value) in self.tunable_mut().into_iter().zip(values) {
+            *slot = *value;
+        }
+    }
+
+    /// Renders the parameters as Rust constants, ready to be pasted over
+    /// the definitions in `psqt.rs`, `eval/pawns.rs` and `eval/king_safety.rs`.
+    pub fn to_rust_source(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("// ---- psqt.rs ----\n");
+        for (pt, name) in PIECE_NAMES.iter().enumerate() {
+            out.push_str(&format!("const MG_{}_VAL: i32 = {};\n", name, self.mg_material[pt]));
+            out.push_str(&format!("const EG_{}_VAL: i32 = {};\n", name, self.eg_material[pt]));
+        }
+        for (pt, name) in PIECE_NAMES.iter().enumerate() {
+            out.push('\n');
+            out.push_str(&rust_table(&format!("MG_{}_TABLE", name), &self.mg_psqt[pt]));
+            out.push('\n');
+            out.push_str(&rust_table(&format!("EG_{}_TABLE", name), &self.eg_psqt[pt]));
+        }
+
+        out.push_str("\n// ---- eval/pawns.rs ----\n");
+        out.push_str(&format!("pub const DOUBLED_PENALTY: (i32, i32) = {:?};\n", self.doubled_penalty));
+        out.push_str(&format!("pub const ISOLATED_PENALTY: (i32, i32) = {:?};\n", self.isolated_penalty));
+        out.push_str(&format!("pub const BACKWARD_PENALTY: (i32, i32) = {:?};\n", self.backward_penalty));
+        out.push_str(&format!("pub const PASSED_BONUS_MG: [i32; 8] = {:?};\n", self.passed_bonus_mg));
+        out.push_str(&format!("pub const PASSED_BONUS_EG: [i32; 8] = {:?};\n", self.passed_bonus_eg));
+
+        out.push_str("\n// ---- eval/king_safety.rs ----\n");
+        out.push_str(&format!("pub const SHIELD_PENALTY: [i32; 5] = {:?};\n", self.shield_penalty));
+        out.push_str(&format!("pub const STORM_PENALTY: [i32; 5] = {:?};\n", self.storm_penalty));
+        out.push_str(&format!("pub const SEMI_OPEN_FILE_PENALTY: i32 = {};\n", self.semi_open_file_penalty));
+        out.push_str(&format!("pub const OPEN_FILE_PENALTY: i32 = {};\n", self.open_file_penalty));
+        out.push_str(&rust_table("SAFETY_TABLE", &self.safety_table));
+
+        out
+    }
+}
+
+fn rust_table(name: &str, table: &[i32; 64]) -> String {
+    let mut out = format!("pub const {}: [i32; 64] = [\n", name);
+    for row in table.chunks(8) {
+        let cells: Vec<String> = row.iter().map(|v| format!("{:4}", v)).collect();
+        out.push_str(&format!("    {},\n", cells.join(",")));
+    }
+    out.push_str("];\n");
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamsError {
+    Io(String),
+    MissingVersion,
+    UnsupportedVersion(String),
+    UnknownKey { line: usize, key: String },
+    DuplicateKey { line: usize, key: String },
+    InvalidValue { line: usize, key: String, token: String },
+    MissingValues { key: String, expected: usize, found: usize },
+    MissingKey(String),
+    OutOfRange { key: String, value: i32, min: i32, max: i32 },
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "unable to read eval file: {}", e),
+            Self::MissingVersion => write!(f, "eval file does not start with a 'version' line"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported eval file version '{}' (expected {})", v, PARAMS_FILE_VERSION),
+            Self::UnknownKey { line, key } => write!(f, "line {}: unknown parameter '{}'", line, key),
+            Self::DuplicateKey { line, key } => write!(f, "line {}: parameter '{}' is defined twice", line, key),
+            Self::InvalidValue { line, key, token } => write!(f, "line {}: invalid value '{}' for parameter '{}'", line, token, key),
+            Self::MissingValues { key, expected, found } => write!(f, "parameter '{}' needs {} values, found {}", key, expected, found),
+            Self::MissingKey(key) => write!(f, "parameter '{}' is missing", key),
+            Self::OutOfRange { key, value, min, max } => write!(f, "parameter '{}' value {} is outside of [{}, {}]", key, value, min, max),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+/// A named group of weights inside `EvalParams` together with its valid range.
+struct Field<'a> {
+    name: String,
+    values: Vec<&'a mut i32>,
+    min: i32,
+    max: i32,
+}
+
+impl<'a> Field<'a> {
+    fn new(name: &str, values: impl Iterator<Item = &'a mut i32>, min: i32, max: i32) -> Self {
+        Self { name: name.to_string(), values: values.collect(), min, max }
+    }
+}
+
+impl EvalParams {
+    fn fields_mut(&mut self) -> Vec<Field<'_>> {
+        let mut fields = vec![
+            Field::new("mg_material", self.mg_material.iter_mut(), 0, VALUE_LIMIT),
+            Field::new("eg_material", self.eg_material.iter_mut(), 0, VALUE_LIMIT),
+        ];
+        for (name, table) in PIECE_NAMES.iter().zip(self.mg_psqt.iter_mut()) {
+            fields.push(Field::new(&format!("mg_psqt_{}", name.to_lowercase()), table.iter_mut(), -VALUE_LIMIT, VALUE_LIMIT));
+        }
+        for (name, table) in PIECE_NAMES.iter().zip(self.eg_psqt.iter_mut()) {
+            fields.push(Field::new(&format!("eg_psqt_{}", name.to_lowercase()), table.iter_mut(), -VALUE_LIMIT, VALUE_LIMIT));
+        }
+        fields.extend([
+            Field::new("doubled_penalty", [&mut self.doubled_penalty.0, &mut self.doubled_penalty.1].into_iter(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("isolated_penalty", [&mut self.isolated_penalty.0, &mut self.isolated_penalty.1].into_iter(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("backward_penalty", [&mut self.backward_penalty.0, &mut self.backward_penalty.1].into_iter(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("passed_bonus_mg", self.passed_bonus_mg.iter_mut(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("passed_bonus_eg", self.passed_bonus_eg.iter_mut(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("shield_penalty", self.shield_penalty.iter_mut(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("storm_penalty", self.storm_penalty.iter_mut(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("semi_open_file_penalty", [&mut self.semi_open_file_penalty].into_iter(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("open_file_penalty", [&mut self.open_file_penalty].into_iter(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("safety_table", self.safety_table.iter_mut(), -VALUE_LIMIT, VALUE_LIMIT),
+            Field::new("attack_weight", self.attack_weight.iter_mut(), 0, 64),
+            Field::new("attacker_count_scale", self.attacker_count_scale.iter_mut(), 0, 100),
+        ]);
+        fields
+    }
+
+    /// Serialises the parameters into the versioned plain-text eval file format.
+    pub fn to_text(&self) -> String {
+        let mut params = self.clone();
+        let mut out = format!("# Evaluation parameters\nversion {}\n", PARAMS_FILE_VERSION);
+        for field in params.fields_mut() {
+            out.push_str(&format!("\n{}", field.name));
+            let values: Vec<i32> = field.values.iter().map(|v| **v).collect();
+            if values.len() == 64 {
+                for row in values.chunks(8) {
+                    let cells: Vec<String> = row.iter().map(|v| format!("{:5}", v)).collect();
+                    out.push_str(&format!("\n   {}", cells.join("")));
+                }
+            } else {
+                for value in values {
+                    out.push_str(&format!(" {}", value));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Parses and validates a complete parameter set in the plain-text eval file format.
+    /// Every parameter must be present exactly once with the right number of values.
+    pub fn from_text(text: &str) -> Result<Self, ParamsError> {
+        // Tokens with their line numbers, comments stripped
+        let mut tokens = text.lines().enumerate().flat_map(|(i, line)| {
+            let content = line.split('#').next().unwrap_or("");
+            content.split_whitespace().map(move |token| (i + 1, token))
+        }).peekable();
+
+        match (tokens.next(), tokens.next()) {
+            (Some((_, "version")), Some((_, version))) => {
+                if version.parse::<u32>() != Ok(PARAMS_FILE_VERSION) {
+                    return Err(ParamsError::UnsupportedVersion(version.to_string()));
+                }
+            }
+            _ => return Err(ParamsError::MissingVersion),
+        }
+
+        let mut params = Self::default();
+        let mut fields = params.fields_mut();
+        let mut seen = vec![false; fields.len()];
+
+        while let Some((line, key)) = tokens.next() {
+            let Some(index) = fields.iter().position(|f| f.name == key) else {
+                return Err(ParamsError::UnknownKey { line, key: key.to_string() });
+            };
+            if seen[index] {
+                return Err(ParamsError::DuplicateKey { line, key: key.to_string() });
+            }
+            seen[index] = true;
+
+            let field = &mut fields[index];
+            let expected = field.values.len();
+            for found in 0..expected {
+                let Some((line, token)) = tokens.next_if(|(_, t)| !t.starts_with(|c: char| c.is_ascii_alphabetic())) else {
+                    return Err(ParamsError::MissingValues { key: field.name.clone(), expected, found });
+                };
+                let value = token.parse::<i32>().map_err(|_| ParamsError::InvalidValue {
+                    line,
+                    key: field.name.clone(),
+                    token: token.to_string(),
+                })?;
+                if value < field.min || value > field.max {
+                    return Err(ParamsError::OutOfRange { key: field.name.clone(), value, min: field.min, max: field.max });
+                }
+                *field.values[found] = value;
+            }
+        }
+
+        if let Some(missing) = seen.iter().position(|s| !s) {
+            return Err(ParamsError::MissingKey(fields[missing].name.clone()));
+        }
+        drop(fields);
+        Ok(params)
+    }
+
+    pub fn load(path: &str) -> Result<Self, ParamsError> {
+        let text = fs::read_to_string(path).map_err(|e| ParamsError::Io(format!("{}: {}", path, e)))?;
+        Self::from_text(&text)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), ParamsError> {
+        fs::write(path, self.to_text()).map_err(|e| ParamsError::Io(format!("{}: {}", path, e)))
+    }
+}
+
+static NEXT_TABLES_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Parameters in the form the evaluation consumes them. Owned by the `Engine`, the evaluation
+/// functions take them as an argument.
+#[derive(Debug, Clone)]
+pub struct EvalTables {
+    pub params: EvalParams,
+    // Same layout as `psqt::PSQT`: [Piece] -> [Color] -> [Phase] -> [Square], material included
+    pub psqt: [[[[i32; 64]; 2]; 2]; 6],
+    // Whether `psqt` equals `psqt::PSQT`, whose sums `Board` keeps up to date
+    builtin_psqt: bool,
+    id: u32,
+}
+
+impl EvalTables {
+    pub fn new(params: EvalParams) -> Self {
+        let mut psqt = [[[[0; 64]; 2]; 2]; 6];
+        for (pt, tables) in psqt.iter_mut().enumerate() {
+            tables[0][0] = prepare_table(params.mg_psqt[pt], params.mg_material[pt], true);
+            tables[0][1] = prepare_table(params.eg_psqt[pt], params.eg_material[pt], true);
+            tables[1][0] = prepare_table(params.mg_psqt[pt], params.mg_material[pt], false);
+            tables[1][1] = prepare_table(params.eg_psqt[pt], params.eg_material[pt], false);
+        }
+        let builtin_psqt = psqt == PSQT;
+        Self { params, psqt, builtin_psqt, id: NEXT_TABLES_ID.fetch_add(1, Ordering::Relaxed) }
+    }
+
+    /// Unique for every set of tables created, so caches can detect stale entries.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// White relative PSQT + material sums (mg, eg) of `board`. Kept up to date by `Board`
+    /// for the built-in tables, summed up here for any others.
+    pub fn psqt_sums(&self, board: &Board) -> (i32, i32) {
+        if self.builtin_psqt {
+            return (board.psqt_mg, board.psqt_eg);
+        }
+        let (mut mg, mut eg) = (0, 0);
+        for pt in PIECE_TYPES {
+            for color in [Color::White, Color::Black] {
+                let sign = 1 - 2 * color as i32;
+                let tables = &self.psqt[pt as usize][color as usize];
+                let mut pieces = board.pieces[pt as usize][color as usize];
+                while pieces != 0 {
+                    let sq = pieces.trailing_zeros() as usize;
+                    pieces &= pieces - 1;
+                    mg += sign * tables[0][sq];
+                    eg += sign * tables[1][sq];
+                }
+            }
+        }
+        (mg, eg)
+    }
+}
+
+impl Default for EvalTables {
+    fn default() -> Self {
+        Self::new(EvalParams::default())
+    }
+}
@@ -0,0 +1,208 @@
+use crate::board::{Board, Color, PieceType};
+use std::cell::RefCell;
+use std::mem::size_of;
+use super::params::{EvalParams, EvalTables};
+use super::{adjacent_files_mask, forward_ranks, FILE_A_MASK};
+
+// Compiled in defaults, the evaluation reads the active `EvalParams`
+pub const DOUBLED_PENALTY: (i32, i32) = (11, 24);
+pub const ISOLATED_PENALTY: (i32, i32) = (9, 14);
+pub const BACKWARD_PENALTY: (i32, i32) = (7, 10);
+
+// Passed pawn bonus indexed by relative rank (rank 2 = index 1, rank 7 = index 6)
+pub const PASSED_BONUS_MG: [i32; 8] = [0, 3, 7, 12, 24, 42, 65, 0];
+pub const PASSED_BONUS_EG: [i32; 8] = [0, 8, 14, 28, 52, 90, 135, 0];
+
+const PAWN_TABLE_MB: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PawnEntry {
+    pub key: u64,
+    pub mg_score: i32, // White relative
+    pub eg_score: i32, // White relative
+    pub passed: [u64; 2],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PawnHashTable {
+    pub entries: Vec<PawnEntry>,
+    pub size: usize,
+    pub tables_id: u32, // `EvalTables::id` of the tables the entries were computed with
+}
+
+impl PawnHashTable {
+    pub fn new(mb_size: usize) -> Self {
+        let entry_size = size_of::<PawnEntry>();
+        let target_count = ((mb_size * 1024 * 1024) / entry_size).max(1);
+
+        // Round down to nearest power of 2 for fast indexing
+        let size = if target_count.is_power_of_two() {
+            target_count
+        } else {
+            target_count.next_power_of_two() >> 1
+        };
+
+        Self {
+            entries: vec![PawnEntry::default(); size],
+            size,
+            tables_id: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            *entry = PawnEntry::default();
+        }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<PawnEntry> {
+        let entry = self.entries[(key as usize) & (self.size - 1)];
+        // A key of 0 only occurs without any pawns, which is cheap to recompute anyway
+        if entry.key == key && key != 0 {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    pub fn store(&mut self, entry: PawnEntry) {
+        let index = (entry.key as usize) & (self.size - 1);
+        self.entries[index] = entry;
+    }
+}
+
+thread_local! {
+    // Every search thread gets its own table, so probing needs no locking.
+    static PAWN_TABLE: RefCell<PawnHashTable> = RefCell::new(PawnHashTable::new(PAWN_TABLE_MB));
+}
+
+/// Wipes the pawn hash table of the calling thread (e.g. on `ucinewgame`).
+pub fn clear_pawn_table() {
+    PAWN_TABLE.with(|table| table.borrow_mut().clear());
+}
+
+/// Returns the cached pawn structure evaluation for the board, computing and storing it on a miss.
+pub fn probe_pawn_structure(board: &Board, tables: &EvalTables) -> PawnEntry {
+    PAWN_TABLE.with(|table| {
+        let mut table = table.borrow_mut();
+        if table.tables_id != tables.id() {
+            table.clear();
+            table.tables_id = tables.id();
+        }
+        if let Some(entry) = table.probe(board.pawn_hash) {
+            return entry;
+        }
+        let entry = evaluate_pawn_structure(board, &tables.params);
+        table.store(entry);
+        entry
+    })
+}
+
+/// Squares attacked by the pawns in `pawns` of the given color.
+pub fn pawn_attacks(pawns: u64, color: Color) -> u64 {
+    let not_a_file = !FILE_A_MASK;
+    let not_h_file = !(FILE_A_MASK << 7);
+    match color {
+        Color::White => ((pawns & not_a_file) << 7) | ((pawns & not_h_file) << 9),
+        Color::Black => ((pawns & not_a_file) >> 9) | ((pawns & not_h_file) >> 7),
+    }
+}
+
+fn relative_rank(square: usize, color: Color) -> usize {
+    match color {
+        Color::White => square / 8,
+        Color::Black => 7 - square / 8,
+    }
+}
+
+/// Raw pawn structure features of one side, before any weights are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PawnTerms {
+    pub doubled: i32,
+    pub isolated: i32,
+    pub backward: i32,
+    pub passed: [i32; 8], // Passed pawn count per relative rank
+    pub passed_bb: u64,
+}
+
+/// Counts doubled, isolated, backward and passed pawns of `color`.
+pub fn pawn_terms(board: &Board, color: Color) -> PawnTerms {
+    let own_pawns = board.pieces[PieceType::Pawn as usize][color as usize];
+    let enemy_pawns = board.pieces[PieceType::Pawn as usize][!color as usize];
+    let enemy_attacks = pawn_attacks(enemy_pawns, !color);
+
+    let mut terms = PawnTerms::default();
+
+    let mut pawns = own_pawns;
+    while pawns != 0 {
+        let sq = pawns.trailing_zeros() as usize;
+        pawns &= pawns - 1;
+
+        let file = sq % 8;
+        let rank = sq / 8;
+        let file_mask = FILE_A_MASK << file;
+        let neighbours = adjacent_files_mask(file) & !file_mask;
+        let in_front = forward_ranks(rank, color);
+
+        // Doubled: another own pawn further up the same file
+        let doubled = own_pawns & file_mask & in_front != 0;
+        if doubled {
+            terms.doubled += 1;
+        }
+
+        if own_pawns & neighbours == 0 {
+            terms.isolated += 1;
+        } else {
+            // Backward: all neighbours are ahead and the stop square is controlled by an enemy pawn
+            let behind_or_level = !in_front;
+            let stop_square = match color {
+                Color::White => (1_u64 << sq) << 8,
+                Color::Black => (1_u64 << sq) >> 8,
+            };
+            if own_pawns & neighbours & behind_or_level == 0 && stop_square & enemy_attacks != 0 {
+                terms.backward += 1;
+            }
+        }
+
+        // Only the front pawn of a doubled pair counts as passed
+        if !doubled && enemy_pawns & adjacent_files_mask(file) & in_front == 0 {
+            terms.passed_bb |= 1_u64 << sq;
+            terms.passed[relative_rank(sq, color)] += 1;
+        }
+    }
+
+    terms
+}
+
+/// Evaluates doubled, isolated, backward and passed pawns of one side.
+/// Returns (mg, eg, passed pawn bitboard) from the perspective of `color`.
+pub fn evaluate_pawns_for(board: &Board, color: Color, params: &EvalParams) -> (i32, i32, u64) {
+    let terms = pawn_terms(board, color);
+
+    let mut mg = -terms.doubled * params.doubled_penalty.0
+        - terms.isolated * params.isolated_penalty.0
+        - terms.backward * params.backward_penalty.0;
+    let mut eg = -terms.doubled * params.doubled_penalty.1
+        - terms.isolated * params.isolated_penalty.1
+        - terms.backward * params.backward_penalty.1;
+
+    for rank in 0..8 {
+        mg += terms.passed[rank] * params.passed_bonus_mg[rank];
+        eg += terms.passed[rank] * params.passed_bonus_eg[rank];
+    }
+
+    (mg, eg, terms.passed_bb)
+}
+
+/// Computes the full pawn structure entry (white relative scores) without touching the cache.
+pub fn evaluate_pawn_structure(board: &Board, params: &EvalParams) -> PawnEntry {
+    let (white_mg, white_eg, white_passed) = evaluate_pawns_for(board, Color::White, params);
+    let (black_mg, black_eg, black_passed) = evaluate_pawns_for(board, Color::Black, params);
+
+    PawnEntry {
+        key: board.pawn_hash,
+        mg_score: white_mg - black_mg,
+        eg_score: white_eg - black_eg,
+        passed: [white_passed, black_passed],
+    }
+}
@@ -0,0 +1,165 @@
+use crate::board::{Board, Color, PIECE_TYPES};
+use crate::psqt::MAX_PHASE;
+use std::fmt;
+use super::evaluate;
+use super::endgame::{probe_endgame, scale_factor, SCALE_NORMAL};
+use super::nnue::Network;
+use super::params::EvalTables;
+use super::king_safety::{king_attack_penalty, pawn_cover_penalty};
+use super::pawns::evaluate_pawns_for;
+
+/// Middlegame and endgame score of a single evaluation term, per color.
+/// Each color's value is from that color's own perspective.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TermScore {
+    pub mg: [i32; 2],
+    pub eg: [i32; 2],
+}
+
+impl TermScore {
+    /// White relative (mg, eg) difference of the term.
+    pub fn total(&self) -> (i32, i32) {
+        (
+            self.mg[Color::White as usize] - self.mg[Color::Black as usize],
+            self.eg[Color::White as usize] - self.eg[Color::Black as usize],
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalTrace {
+    pub terms: Vec<(&'static str, TermScore)>,
+    pub phase: i32,
+    pub mg_score: i32, // White relative
+    pub eg_score: i32, // White relative, before scaling
+    pub scale: i32,    // Endgame scale factor out of `SCALE_NORMAL`
+    pub endgame: Option<&'static str>, // Known ending that replaced the evaluation
+    pub score: i32,    // White relative, phase blended
+    pub nnue: Option<i32>, // White relative network score the search uses instead of `score`
+}
+
+/// Breaks the static evaluation of `board` down into its individual terms.
+/// The blended `score` always equals `evaluate_board(board, tables)`.
+pub fn trace(board: &Board, tables: &EvalTables) -> EvalTrace {
+    let mut material = TermScore::default();
+    let mut psqt = TermScore::default();
+    let mut pawns = TermScore::default();
+    let mut king_shelter = TermScore::default();
+    let mut king_attacks = TermScore::default();
+
+    for color in [Color::White, Color::Black] {
+        let c = color as usize;
+
+        for pt in PIECE_TYPES {
+            let mut pieces = board.pieces[pt as usize][c];
+            while pieces != 0 {
+                let sq = pieces.trailing_zeros() as usize;
+                pieces &= pieces - 1;
+
+                let mg_material = tables.params.mg_material[pt as usize];
+                let eg_material = tables.params.eg_material[pt as usize];
+                material.mg[c] += mg_material;
+                material.eg[c] += eg_material;
+                psqt.mg[c] += tables.psqt[pt as usize][c][0][sq] - mg_material;
+                psqt.eg[c] += tables.psqt[pt as usize][c][1][sq] - eg_material;
+            }
+        }
+
+        let (pawn_mg, pawn_eg, _) = evaluate_pawns_for(board, color, &tables.params);
+        pawns.mg[c] = pawn_mg;
+        pawns.eg[c] = pawn_eg;
+
+        king_shelter.mg[c] = -pawn_cover_penalty(board, color, &tables.params);
+        king_attacks.mg[c] = -king_attack_penalty(board, color, &tables.params);
+    }
+
+    let terms = vec![
+        ("Material", material),
+        ("PSQT", psqt),
+        ("Pawns", pawns),
+        ("King shelter", king_shelter),
+        ("King attacks", king_attacks),
+    ];
+
+    let (mg_score, eg_score) = terms.iter().fold((0, 0), |(mg, eg), (_, term)| {
+        let (term_mg, term_eg) = term.total();
+        (mg + term_mg, eg + term_eg)
+    });
+
+    let phase = board.phase.min(MAX_PHASE);
+    let scale = scale_factor(board, eg_score, &tables.params);
+    let scaled_eg = eg_score * scale / SCALE_NORMAL;
+    let blended = ((mg_score * phase) + (scaled_eg * (MAX_PHASE - phase))) / MAX_PHASE;
+
+    let (endgame, score) = match probe_endgame(board, &tables.params) {
+        Some((name, score)) => (Some(name), score),
+        None => (None, blended),
+    };
+
+    EvalTrace {
+        terms,
+        phase,
+        mg_score,
+        eg_score,
+        scale,
+        endgame,
+        score,
+        nnue: None,
+    }
+}
+
+/// Like `trace`, with the score of the loaded `network` next to the classical terms. Known
+/// endings are left to the endgame module as in `evaluate`.
+pub fn trace_with_network(board: &Board, tables: &EvalTables, network: Option<&Network>) -> EvalTrace {
+    let mut eval_trace = trace(board, tables);
+    if eval_trace.endgame.is_none() {
+        eval_trace.nnue = network.map(|network| evaluate(board, tables, Some(network)));
+    }
+    eval_trace
+}
+
+fn pawns(centipawns: i32) -> String {
+    format!("{:6.2}", centipawns as f64 / 100.0)
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.nnue.is_some() {
+            writeln!(f, "Classical terms, not used while a network is loaded:")?;
+        }
+        writeln!(f, "     Term     |     White     |     Black     |     Total")?;
+        writeln!(f, "              |   MG     EG   |   MG     EG   |   MG     EG")?;
+        writeln!(f, "--------------+---------------+---------------+--------------")?;
+        for (name, term) in &self.terms {
+            let (mg, eg) = term.total();
+            writeln!(
+                f,
+                "{:>13} | {} {} | {} {} | {} {}",
+                name,
+                pawns(term.mg[Color::White as usize]),
+                pawns(term.eg[Color::White as usize]),
+                pawns(term.mg[Color::Black as usize]),
+                pawns(term.eg[Color::Black as usize]),
+                pawns(mg),
+                pawns(eg),
+            )?;
+        }
+        writeln!(f, "--------------+---------------+---------------+--------------")?;
+        writeln!(f, "{:>13} |               |               | {} {}", "Total", pawns(self.mg_score), pawns(self.eg_score))?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {}/{} (MG weight {}%)", self.phase, MAX_PHASE, self.phase * 100 / MAX_PHASE)?;
+        if self.scale != SCALE_NORMAL {
+            writeln!(f, "Endgame scale: {}/{}", self.scale, SCALE_NORMAL)?;
+        }
+        if let Some(name) = self.endgame {
+            writeln!(f, "Known ending {}, terms above are not used", name)?;
+        }
+        match self.nnue {
+            Some(nnue) => {
+                writeln!(f, "Classical evaluation: {} (white side)", pawns(self.score).trim())?;
+                write!(f, "Final evaluation: {} (white side, NNUE)", pawns(nnue).trim())
+            }
+            None => write!(f, "Final evaluation: {} (white side)", pawns(self.score).trim()),
+        }
+    }
+}
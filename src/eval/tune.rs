@@ -0,0 +1,122 @@
+use crate::board::{Board, Color, PIECE_TYPES};
+use crate::psqt::MAX_PHASE;
+use super::endgame::{scale_factor, SCALE_NORMAL};
+use super::king_safety::king_terms;
+use super::params::*;
+use super::pawns::pawn_terms;
+
+/// Sparse gradient of the evaluation with respect to the tunable parameter vector:
+/// `(parameter index, white relative coefficient)`. Because every parameter is either a
+/// middlegame or an endgame weight, the blended eval is
+/// `(Σ mg_coef * w * phase + Σ eg_coef * w * scale * (24 - phase)) / 24`, where the endgame
+/// scale depends on which side the endgame sum favours.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coefficients {
+    pub entries: Vec<(u16, f32)>,
+    pub phase: i32,
+    pub eg_scale: [f32; 2], // Endgame scale (1.0 is normal) with white resp. black pressing
+}
+
+impl Coefficients {
+    /// Endgame scale under `weights`, picked by the sign of the unscaled endgame sum like
+    /// `scale_factor` does.
+    pub fn eg_scale(&self, weights: &[f64]) -> f64 {
+        let eg: f64 = self
+            .entries
+            .iter()
+            .filter(|&&(index, _)| is_eg_parameter(index as usize))
+            .map(|&(index, coef)| coef as f64 * weights[index as usize])
+            .sum();
+        self.eg_scale[(eg < 0.0) as usize] as f64
+    }
+}
+
+/// Whether the parameter at `index` of the tunable vector is an endgame weight.
+pub fn is_eg_parameter(index: usize) -> bool {
+    (EG_MATERIAL_OFFSET..MG_PSQT_OFFSET).contains(&index)
+        || (EG_PSQT_OFFSET..DOUBLED_OFFSET).contains(&index)
+        || ((DOUBLED_OFFSET..PASSED_MG_OFFSET).contains(&index) && (index - DOUBLED_OFFSET) % 2 == 1)
+        || (PASSED_EG_OFFSET..SHIELD_OFFSET).contains(&index)
+}
+
+/// Extracts the evaluation coefficients of `board`, mirroring `evaluate_board` term by term.
+/// Known endings (see `endgame::probe_endgame`) are not linear and should not be tuned on. The
+/// endgame scale is kept for both sides; its material thresholds stay those of `params`.
+pub fn coefficients(board: &Board, params: &EvalParams) -> Coefficients {
+    let mut dense = vec![0_f32; TUNABLE_COUNT];
+    let attacker_count_scale = &params.attacker_count_scale;
+
+    for color in [Color::White, Color::Black] {
+        let sign = if color == Color::White { 1.0 } else { -1.0 };
+
+        // 1. Material and PSQT (white pieces use the vertically mirrored table)
+        for pt in PIECE_TYPES {
+            let mut pieces = board.pieces[pt as usize][color as usize];
+            while pieces != 0 {
+                let sq = pieces.trailing_zeros() as usize;
+                pieces &= pieces - 1;
+
+                let table_index = if color == Color::White { sq ^ 56 } else { sq };
+                dense[MG_MATERIAL_OFFSET + pt as usize] += sign;
+                dense[EG_MATERIAL_OFFSET + pt as usize] += sign;
+                dense[MG_PSQT_OFFSET + pt as usize * 64 + table_index] += sign;
+                dense[EG_PSQT_OFFSET + pt as usize * 64 + table_index] += sign;
+            }
+        }
+
+        // 2. Pawn structure
+        let pawns = pawn_terms(board, color);
+        dense[DOUBLED_OFFSET] -= sign * pawns.doubled as f32;
+        dense[DOUBLED_OFFSET + 1] -= sign * pawns.doubled as f32;
+        dense[ISOLATED_OFFSET] -= sign * pawns.isolated as f32;
+        dense[ISOLATED_OFFSET + 1] -= sign * pawns.isolated as f32;
+        dense[BACKWARD_OFFSET] -= sign * pawns.backward as f32;
+        dense[BACKWARD_OFFSET + 1] -= sign * pawns.backward as f32;
+        for rank in 0..8 {
+            dense[PASSED_MG_OFFSET + rank] += sign * pawns.passed[rank] as f32;
+            dense[PASSED_EG_OFFSET + rank] += sign * pawns.passed[rank] as f32;
+        }
+
+        // 3. King safety (penalties, middlegame only)
+        let king = king_terms(board, color, params);
+        for distance in 0..5 {
+            dense[SHIELD_OFFSET + distance] -= sign * king.shield[distance] as f32;
+            dense[STORM_OFFSET + distance] -= sign * king.storm[distance] as f32;
+        }
+        dense[SEMI_OPEN_FILE_OFFSET] -= sign * king.semi_open_files as f32;
+        dense[OPEN_FILE_OFFSET] -= sign * king.open_files as f32;
+        dense[SAFETY_TABLE_OFFSET + king.attack_units] -= sign * attacker_count_scale[king.attackers] as f32 / 100.0;
+    }
+
+    // 4. Endgame scaling, for either side pressing as the weights change during tuning
+    let eg_scale = [0, -1].map(|eg_score| scale_factor(board, eg_score, params) as f32 / SCALE_NORMAL as f32);
+
+    let entries = dense
+        .iter()
+        .enumerate()
+        .filter(|(_, coef)| **coef != 0.0)
+        .map(|(index, coef)| (index as u16, *coef))
+        .collect();
+
+    Coefficients {
+        entries,
+        phase: board.phase.min(MAX_PHASE),
+        eg_scale,
+    }
+}
+
+/// Evaluates the linearised eval for the given (possibly fractional) weights. White relative.
+pub fn linear_eval(coefficients: &Coefficients, weights: &[f64]) -> f64 {
+    let mut mg = 0.0;
+    let mut eg = 0.0;
+    for &(index, coef) in &coefficients.entries {
+        if is_eg_parameter(index as usize) {
+            eg += coef as f64 * weights[index as usize];
+        } else {
+            mg += coef as f64 * weights[index as usize];
+        }
+    }
+    let eg = eg * coefficients.eg_scale[(eg < 0.0) as usize] as f64;
+    let phase = coefficients.phase as f64;
+    (mg * phase + eg * (MAX_PHASE as f64 - phase)) / MAX_PHASE as f64
+}
@@ -11,3 +11,10 @@ pub mod tt;
 pub mod zobrist;
 pub mod search;
 pub mod psqt;
+pub mod datagen;
+pub mod syzygy;
+pub mod dtm;
+pub mod book;
+pub mod pgn;
+pub mod epd;
+pub mod perft;
@@ -1,8 +1,31 @@
 use chess_engine::engine::Engine;
 use chess_engine::uci::UCI;
+use std::env;
 
 fn main() {
-    let engine = Engine::new("Yakari".to_string(), "EiSiMo".to_string());
+    let mut engine = Engine::new("Yakari".to_string(), "EiSiMo".to_string());
+
+    let args: Vec<String> = env::args().collect();
+    let path_arg = |flag: &str| {
+        let i = args.iter().position(|a| a == flag)?;
+        let Some(path) = args.get(i + 1) else {
+            eprintln!("{} needs a path", flag);
+            std::process::exit(1);
+        };
+        Some(path.clone())
+    };
+
+    if let Some(path) = path_arg("--eval-params-file")
+        && let Err(e) = engine.load_eval_params_file(&path) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    if let Some(path) = path_arg("--nnue-file")
+        && let Err(e) = engine.load_nnue_file(&path) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
     let mut uci = UCI { engine };
     uci.uci_mainloop();
 }
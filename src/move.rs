@@ -1,7 +1,7 @@
 use std::slice;
 use std::ops::{Index, IndexMut};
 use crate::square::Square;
-use crate::board::PieceType;
+use crate::board::{Color, PieceType};
 use crate::square::SQUARES;
 
 // BIT 0 - 5: FROM SQUARE (0-63)
@@ -118,6 +118,30 @@ impl MoveList {
     pub fn clear(&mut self) { self.count = 0 }
 }
 
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = std::iter::Take<std::array::IntoIter<Move, 256>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.moves.into_iter().take(self.count)
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Index<usize> for MoveList {
     type Output = Move;
 
@@ -134,12 +158,41 @@ impl IndexMut<usize> for MoveList {
     }
 }
 
+/// A piece put on (`added`) or lifted off a square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceChange {
+    pub piece_type: PieceType,
+    pub color: Color,
+    pub square: Square,
+    pub added: bool,
+}
+
+/// The pieces a move lifted and put down, in order. Castling changes the most, four.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PieceChanges {
+    changes: [Option<PieceChange>; 4],
+    count: usize,
+}
+
+impl PieceChanges {
+    #[inline(always)]
+    pub fn push(&mut self, change: PieceChange) {
+        self.changes[self.count] = Some(change);
+        self.count += 1;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PieceChange> {
+        self.changes[..self.count].iter().flatten()
+    }
+}
+
 pub struct UndoMove {
     pub mv: Move,
     pub captured_piece: Option<PieceType>,
     pub old_en_passant_square: Option<Square>,
     pub old_castling_rights: u8,
     pub old_halfmove_clock: u8,
+    pub changes: PieceChanges,
 }
 
 impl UndoMove {
@@ -147,13 +200,15 @@ impl UndoMove {
                captured_piece: Option<PieceType>,
                old_en_passant_square: Option<Square>,
                old_castling_rights: u8,
-               old_halfmove_clock: u8) -> Self {
+               old_halfmove_clock: u8,
+               changes: PieceChanges) -> Self {
         Self {
             mv,
             captured_piece,
             old_en_passant_square,
             old_castling_rights,
-            old_halfmove_clock
+            old_halfmove_clock,
+            changes
         }
 
     }
@@ -0,0 +1,170 @@
+use crate::board::*;
+use crate::r#move::*;
+use crate::movegen::tables::*;
+
+// Attack queries shared by move generation, evaluation and notation. Squares are indices with
+// a1 = 0, sets of squares are bitboards.
+
+pub fn rook_attacks(square: usize, occupied: u64) -> u64 {
+    let magic_index = ((occupied & PREMASKS_ROOK[square]).wrapping_mul(MAGICS_ROOK[square])) >> (64 - RELEVANT_BITS_ROOK[square]);
+    get_rook_attacks()[square][magic_index as usize]
+}
+
+pub fn bishop_attacks(square: usize, occupied: u64) -> u64 {
+    let magic_index = ((occupied & PREMASKS_BISHOP[square]).wrapping_mul(MAGICS_BISHOP[square])) >> (64 - RELEVANT_BITS_BISHOP[square]);
+    get_bishop_attacks()[square][magic_index as usize]
+}
+
+/// Squares a pawn of `color` on `square` attacks.
+pub fn pawn_attacks(color: Color, square: usize) -> u64 {
+    // The squares a pawn attacks are those from which a pawn of the other color would attack it
+    ATTACKING_PAWNS[!color as usize][square]
+}
+
+/// Squares strictly between two squares on a rank, file or diagonal, else 0.
+pub fn between(a: usize, b: usize) -> u64 {
+    if rook_attacks(a, 0) & (1 << b) != 0 {
+        rook_attacks(a, 1 << b) & rook_attacks(b, 1 << a)
+    } else if bishop_attacks(a, 0) & (1 << b) != 0 {
+        bishop_attacks(a, 1 << b) & bishop_attacks(b, 1 << a)
+    } else {
+        0
+    }
+}
+
+/// The whole rank, file or diagonal through two squares, else 0.
+pub fn line(a: usize, b: usize) -> u64 {
+    let ends = 1 << a | 1 << b;
+    if rook_attacks(a, 0) & (1 << b) != 0 {
+        rook_attacks(a, 0) & rook_attacks(b, 0) | ends
+    } else if bishop_attacks(a, 0) & (1 << b) != 0 {
+        bishop_attacks(a, 0) & bishop_attacks(b, 0) | ends
+    } else {
+        0
+    }
+}
+
+impl Board {
+    fn king_square(&self, color: Color) -> usize {
+        self.pieces[PieceType::King as usize][color as usize].trailing_zeros() as usize
+    }
+
+    /// Squares a `piece` on `square` attacks with the given occupancy. Pawns attack for the color
+    /// standing on `square`, for the side to move if it is empty.
+    pub fn attacks_from(&self, piece: PieceType, square: usize, occupied: u64) -> u64 {
+        match piece {
+            PieceType::Pawn => {
+                let them = !self.side_to_move;
+                let color = if self.occupied[them as usize] & (1 << square) != 0 { them } else { self.side_to_move };
+                pawn_attacks(color, square)
+            }
+            PieceType::Knight => KNIGHT_ATTACKS[square],
+            PieceType::Bishop => bishop_attacks(square, occupied),
+            PieceType::Rook => rook_attacks(square, occupied),
+            PieceType::Queen => bishop_attacks(square, occupied) | rook_attacks(square, occupied),
+            PieceType::King => KING_ATTACKS[square],
+        }
+    }
+
+    /// Pieces of both colors attacking `square` with the given occupancy.
+    pub fn attackers_to(&self, square: usize, occupied: u64) -> u64 {
+        let pieces = |pt: PieceType| self.pieces[pt as usize][0] | self.pieces[pt as usize][1];
+        let queens = pieces(PieceType::Queen);
+        (ATTACKING_PAWNS[Color::White as usize][square] & self.pieces[PieceType::Pawn as usize][Color::White as usize])
+            | (ATTACKING_PAWNS[Color::Black as usize][square] & self.pieces[PieceType::Pawn as usize][Color::Black as usize])
+            | (KNIGHT_ATTACKS[square] & pieces(PieceType::Knight))
+            | (KING_ATTACKS[square] & pieces(PieceType::King))
+            | (bishop_attacks(square, occupied) & (pieces(PieceType::Bishop) | queens))
+            | (rook_attacks(square, occupied) & (pieces(PieceType::Rook) | queens))
+    }
+
+    /// Enemy pieces giving check to the side to move.
+    pub fn checkers(&self) -> u64 {
+        let king = self.king_square(self.side_to_move);
+        if king == 64 {
+            return 0;
+        }
+        self.attackers_to(king, self.all_occupied) & self.occupied[!self.side_to_move as usize]
+    }
+
+    /// Pieces of either color that are the only piece between the king of `color` and an enemy
+    /// slider: pinned when they are its own, giving discovered check when they move otherwise.
+    pub fn king_blockers(&self, color: Color) -> u64 {
+        let them = !color as usize;
+        let king = self.king_square(color);
+        if king == 64 {
+            return 0;
+        }
+        let queens = self.pieces[PieceType::Queen as usize][them];
+        let mut snipers = (rook_attacks(king, 0) & (self.pieces[PieceType::Rook as usize][them] | queens))
+            | (bishop_attacks(king, 0) & (self.pieces[PieceType::Bishop as usize][them] | queens));
+
+        let mut blockers = 0;
+        while snipers != 0 {
+            let blocking = between(king, snipers.trailing_zeros() as usize) & self.all_occupied;
+            if blocking.count_ones() == 1 {
+                blockers |= blocking;
+            }
+            snipers &= snipers - 1;
+        }
+        blockers
+    }
+
+    /// Pieces of `color` pinned to their own king by an enemy slider.
+    pub fn pinned(&self, color: Color) -> u64 {
+        self.king_blockers(color) & self.occupied[color as usize]
+    }
+
+    /// Whether the pseudo-legal move `mv` of the side to move checks the enemy king, without
+    /// playing it.
+    pub fn gives_check(&self, mv: Move) -> bool {
+        let us = self.side_to_move;
+        let their_king = self.king_square(!us);
+        if their_king == 64 {
+            return false;
+        }
+        let (from, to) = (mv.get_from() as usize, mv.get_to() as usize);
+        let flags = mv.get_flags();
+        let occupied = (self.all_occupied & !(1 << from)) | (1 << to);
+        let Some(piece) = self.pieces_on_squares[from] else { return false };
+
+        // Castling checks with the rook, on its square next to the king's target
+        if flags & MOVE_MASK_CASTLE == MOVE_FLAG_CASTLE_TRUE {
+            let right = ((flags >> 12) & 0b11) as usize;
+            let (rook_from, rook_to) = (self.castling_rooks[right] as usize, CASTLING_ROOK_TO[right] as usize);
+            let occupied = (self.all_occupied ^ (1 << from) ^ (1 << rook_from)) | (1 << to) | (1 << rook_to);
+            return rook_attacks(rook_to, occupied) & (1 << their_king) != 0;
+        }
+
+        // Direct check by the moved or promoted piece
+        let attacker = if flags & MOVE_MASK_PROMO != 0 {
+            [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen][((flags >> 13) & 0b11) as usize]
+        } else {
+            piece
+        };
+        let direct = match attacker {
+            PieceType::Pawn => pawn_attacks(us, to),
+            PieceType::King => 0,
+            _ => self.attacks_from(attacker, to, occupied),
+        };
+        if direct & (1 << their_king) != 0 {
+            return true;
+        }
+
+        // Discovered check by a piece leaving the line to the king
+        if self.king_blockers(!us) & (1 << from) != 0 && line(from, their_king) & (1 << to) == 0 {
+            return true;
+        }
+
+        // En passant also removes the captured pawn, which may open a line of its own
+        if flags == MOVE_FLAG_EN_PASSANT {
+            let captured = if us == Color::White { to - 8 } else { to + 8 };
+            let occupied = occupied & !(1 << captured);
+            let queens = self.pieces[PieceType::Queen as usize][us as usize];
+            let bishops = self.pieces[PieceType::Bishop as usize][us as usize] | queens;
+            let rooks = self.pieces[PieceType::Rook as usize][us as usize] | queens;
+            return (bishop_attacks(their_king, occupied) & bishops) | (rook_attacks(their_king, occupied) & rooks) != 0;
+        }
+        false
+    }
+}
@@ -0,0 +1,94 @@
+use crate::board::*;
+use crate::movegen::attacks::{between, line, pawn_attacks};
+
+/// Which moves a generator emits. All generators stay pseudo-legal: evasions and quiet checks
+/// may still leave the own king attacked and have to be tested like any other move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenType {
+    All,
+    /// Captures, en passant and every promotion, capturing or not.
+    Captures,
+    /// Non-capturing moves without promotions, castling included.
+    Quiets,
+    /// Every move of a side in check that could resolve it.
+    Evasions,
+    /// Quiet moves giving direct or discovered check.
+    QuietChecks,
+}
+
+impl GenType {
+    /// Whether captures and promotions are generated.
+    pub fn captures(self) -> bool {
+        matches!(self, Self::All | Self::Captures | Self::Evasions)
+    }
+
+    /// Whether non-capturing moves other than promotions are generated.
+    pub fn quiets(self) -> bool {
+        matches!(self, Self::All | Self::Quiets | Self::Evasions | Self::QuietChecks)
+    }
+
+    pub fn castling(self) -> bool {
+        matches!(self, Self::All | Self::Quiets | Self::QuietChecks)
+    }
+
+    /// Squares a knight, bishop, rook, queen or king may move to.
+    pub(crate) fn destinations(self, board: &Board) -> u64 {
+        let enemies = board.occupied[!board.side_to_move as usize];
+        (if self.captures() { enemies } else { 0 }) | (if self.quiets() { board.empty_squares } else { 0 })
+    }
+}
+
+/// Destination squares allowed for the pieces of one type, computed once per generator call.
+pub(crate) struct Targets {
+    squares: u64,
+    /// Pieces that give discovered check by leaving the line to the enemy king.
+    discoverers: u64,
+    their_king: usize,
+}
+
+impl Targets {
+    pub(crate) fn new(board: &Board, gen_type: GenType, piece: PieceType) -> Self {
+        let us = board.side_to_move;
+        let their_king = board.pieces[PieceType::King as usize][!us as usize].trailing_zeros() as usize;
+        let none = Targets { squares: u64::MAX, discoverers: 0, their_king };
+        match gen_type {
+            GenType::All | GenType::Captures | GenType::Quiets => none,
+            GenType::Evasions if piece == PieceType::King => none,
+            GenType::Evasions => {
+                let king = board.pieces[PieceType::King as usize][us as usize].trailing_zeros() as usize;
+                let checkers = board.checkers();
+                // In double check only the king can move
+                let squares = match checkers.count_ones() {
+                    1 => checkers | between(king, checkers.trailing_zeros() as usize),
+                    _ => 0,
+                };
+                Targets { squares, ..none }
+            }
+            GenType::QuietChecks => {
+                if their_king == 64 {
+                    return Targets { squares: 0, ..none };
+                }
+                // Squares from which a piece would attack the king, as seen from the king
+                let squares = match piece {
+                    PieceType::Pawn => pawn_attacks(!us, their_king),
+                    PieceType::King => 0,
+                    _ => board.attacks_from(piece, their_king, board.all_occupied),
+                };
+                Targets { squares, discoverers: board.king_blockers(!us) & board.occupied[us as usize], their_king }
+            }
+        }
+    }
+
+    /// Allowed destinations of the piece on `from`.
+    pub(crate) fn from(&self, from: usize) -> u64 {
+        if self.discoverers & (1 << from) != 0 {
+            self.squares | !line(from, self.their_king)
+        } else {
+            self.squares
+        }
+    }
+
+    pub(crate) fn allows(&self, from: usize, to: usize) -> bool {
+        self.from(from) & (1 << to) != 0
+    }
+}
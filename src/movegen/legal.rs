@@ -0,0 +1,114 @@
+use crate::board::*;
+use crate::r#move::*;
+use crate::movegen::gen_type::GenType;
+use crate::movegen::pawns::generate_pawn_moves;
+use crate::movegen::non_sliders::generate_king_moves;
+use crate::movegen::attacks::{between, bishop_attacks, line, rook_attacks};
+use crate::movegen::tables::KNIGHT_ATTACKS;
+use crate::square::SQUARES;
+
+// Legal move generation: checkers and pinned pieces are found once, then every piece only moves
+// to squares that resolve the check and keep it on its pin line. The king is checked square by
+// square with itself removed from the board, so it cannot step back along a checking ray.
+
+/// Whether capturing en passant leaves the own king attacked. The two pawns leave one rank
+/// together, which can open it for a rook or queen that neither pin mask sees.
+fn en_passant_exposes_king(board: &Board, mv: Move, king: usize) -> bool {
+    let (from, to) = (mv.get_from() as usize, mv.get_to() as usize);
+    let captured = if board.side_to_move == Color::White { to - 8 } else { to + 8 };
+    let occupied = (board.all_occupied ^ (1 << from) ^ (1 << captured)) | (1 << to);
+    let them = board.occupied[!board.side_to_move as usize] & !(1 << captured);
+    board.attackers_to(king, occupied) & them != 0
+}
+
+/// Adds moves of `from` to the `targets` squares, captures flagged.
+fn push_moves(board: &Board, from: usize, mut targets: u64, list: &mut MoveList) {
+    let enemies = board.occupied[!board.side_to_move as usize];
+    while targets != 0 {
+        let to = targets.trailing_zeros() as usize;
+        let flags = if enemies & (1 << to) != 0 { MOVE_FLAG_CAPTURE } else { MOVE_FLAG_QUIET };
+        list.push(Move::new(SQUARES[from], SQUARES[to], flags));
+        targets &= targets - 1;
+    }
+}
+
+pub fn generate_legal_moves(board: &Board, list: &mut MoveList) {
+    let us = board.side_to_move as usize;
+    let own = board.occupied[us];
+    let king_bb = board.pieces[PieceType::King as usize][us];
+    if king_bb == 0 {
+        return;
+    }
+    let king = king_bb.trailing_zeros() as usize;
+    let enemies = board.occupied[!board.side_to_move as usize];
+    let checkers = board.checkers();
+    let pinned = board.pinned(board.side_to_move);
+
+    // King moves and castling; castling is only generated when not in check
+    let mut pseudo = MoveList::new();
+    generate_king_moves(board, &mut pseudo, GenType::All);
+    let without_king = board.all_occupied ^ king_bb;
+    for &mv in pseudo.iter() {
+        // Castling moves are generated legal already
+        let to = mv.get_to() as usize;
+        if mv.get_flags() & MOVE_MASK_CASTLE == MOVE_FLAG_CASTLE_TRUE || board.attackers_to(to, without_king) & enemies == 0 {
+            list.push(mv);
+        }
+    }
+
+    // In double check only the king can move
+    if checkers.count_ones() > 1 {
+        return;
+    }
+    // Squares that capture the checker or block its ray
+    let targets = match checkers {
+        0 => !own,
+        _ => checkers | between(king, checkers.trailing_zeros() as usize),
+    };
+    let allowed = |from: usize| if pinned & (1 << from) != 0 { line(king, from) } else { u64::MAX };
+
+    let pieces = |pt: PieceType| board.pieces[pt as usize][us];
+    let non_king = [
+        (PieceType::Knight, pieces(PieceType::Knight)),
+        (PieceType::Bishop, pieces(PieceType::Bishop)),
+        (PieceType::Rook, pieces(PieceType::Rook)),
+        (PieceType::Queen, pieces(PieceType::Queen)),
+    ];
+    for (piece, mut bb) in non_king {
+        while bb != 0 {
+            let from = bb.trailing_zeros() as usize;
+            let attacks = match piece {
+                PieceType::Knight => KNIGHT_ATTACKS[from],
+                PieceType::Bishop => bishop_attacks(from, board.all_occupied),
+                PieceType::Rook => rook_attacks(from, board.all_occupied),
+                _ => bishop_attacks(from, board.all_occupied) | rook_attacks(from, board.all_occupied),
+            };
+            push_moves(board, from, attacks & !own & targets & allowed(from), list);
+            bb &= bb - 1;
+        }
+    }
+
+    // Pawn moves come from the pseudo-legal generator and go through the same masks
+    pseudo.clear();
+    generate_pawn_moves(board, &mut pseudo, GenType::All);
+    for &mv in pseudo.iter() {
+        let (from, to) = (mv.get_from() as usize, mv.get_to() as usize);
+        let legal = if mv.get_flags() == MOVE_FLAG_EN_PASSANT {
+            !en_passant_exposes_king(board, mv, king)
+        } else {
+            (1 << to) & targets & allowed(from) != 0
+        };
+        if legal {
+            list.push(mv);
+        }
+    }
+}
+
+impl Board {
+    /// All legal moves of the side to move.
+    pub fn legal_moves(&self) -> MoveList {
+        let mut list = MoveList::new();
+        generate_legal_moves(self, &mut list);
+        list
+    }
+}
@@ -1,10 +1,11 @@
 use crate::board::{Board, Color, PieceType};
-use crate::movegen::tables::{get_bishop_attacks, get_rook_attacks, ATTACKING_PAWNS, KING_ATTACKS, KNIGHT_ATTACKS, MAGICS_BISHOP, MAGICS_ROOK, PREMASKS_BISHOP, PREMASKS_ROOK, RELEVANT_BITS_BISHOP, RELEVANT_BITS_ROOK};
+use crate::movegen::attacks::{bishop_attacks, rook_attacks};
+use crate::movegen::tables::{ATTACKING_PAWNS, KING_ATTACKS, KNIGHT_ATTACKS};
 use crate::square::{Square, SQUARES};
 
 pub fn is_current_king_attacked(board: &Board) -> bool {
     let king = board.pieces[PieceType::King as usize][board.side_to_move as usize];
-    is_square_attacked(board, SQUARES[king.trailing_zeros() as usize], board.side_to_move)
+    is_square_attacked(board, SQUARES[king.trailing_zeros() as usize], !board.side_to_move)
 }
 
 pub fn is_other_king_attacked(board: &Board) -> bool {
@@ -38,13 +39,7 @@ pub fn is_square_attacked(board: &Board, square: Square, color: Color) -> bool {
     let queens = board.pieces[PieceType::Queen as usize][color as usize];
 
     // 2.1 Bishop (and part of Queen)
-    let premask_bishop = PREMASKS_BISHOP[square as usize];
-    let magic_bishop = MAGICS_BISHOP[square as usize];
-    let relevant_bits_bishop = RELEVANT_BITS_BISHOP[square as usize];
-    let shift_bishop = 64 - relevant_bits_bishop;
-    let attack_table_bishop = get_bishop_attacks();
-    let magic_index_bishop = ((blockers & premask_bishop).wrapping_mul(magic_bishop)) >> shift_bishop;
-    let bishop_attackable_squares = attack_table_bishop[square as usize][magic_index_bishop as usize];
+    let bishop_attackable_squares = bishop_attacks(square as usize, blockers);
 
     let bishops_and_queens = board.pieces[PieceType::Bishop as usize][color as usize] | queens;
     if (bishop_attackable_squares & bishops_and_queens) != 0 {
@@ -52,13 +47,7 @@ pub fn is_square_attacked(board: &Board, square: Square, color: Color) -> bool {
     }
 
     // 2.2 Rooks (and part of Queen)
-    let premask_rook = PREMASKS_ROOK[square as usize];
-    let magic_rook = MAGICS_ROOK[square as usize];
-    let relevant_bits_rook = RELEVANT_BITS_ROOK[square as usize];
-    let shift_rook = 64 - relevant_bits_rook;
-    let attack_table_rook = get_rook_attacks();
-    let magic_index_rook = ((blockers & premask_rook).wrapping_mul(magic_rook)) >> shift_rook;
-    let rook_attackable_squares = attack_table_rook[square as usize][magic_index_rook as usize];
+    let rook_attackable_squares = rook_attacks(square as usize, blockers);
 
     let rooks_and_queens = board.pieces[PieceType::Rook as usize][color as usize] | queens;
     if (rook_attackable_squares & rooks_and_queens) != 0 {
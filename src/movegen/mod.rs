@@ -3,4 +3,7 @@ pub mod sliders;
 pub mod pawns;
 pub mod tables;
 pub mod legal_check;
-pub mod picker;
\ No newline at end of file
+pub mod picker;
+pub mod legal;
+pub mod gen_type;
+pub mod attacks;
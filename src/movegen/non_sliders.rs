@@ -1,18 +1,21 @@
 use super::tables::{KING_ATTACKS, KNIGHT_ATTACKS};
 use crate::board::*;
 use crate::r#move::*;
-use crate::movegen::legal_check::is_square_attacked;
+use crate::movegen::gen_type::{GenType, Targets};
+use crate::movegen::attacks::between;
+use crate::movegen::legal_check::is_current_king_attacked;
 use crate::square::*;
 
-pub fn generate_knight_moves(board: &Board, list: &mut MoveList) {
+pub fn generate_knight_moves(board: &Board, list: &mut MoveList, gen_type: GenType) {
     let enemy_occupied = board.occupied[!board.side_to_move as usize];
+    let destinations = gen_type.destinations(board);
+    let targets = Targets::new(board, gen_type, PieceType::Knight);
     let mut friendly_knights =
         board.pieces[PieceType::Knight as usize][board.side_to_move as usize];
 
     while friendly_knights != 0 {
         let square = SQUARES[friendly_knights.trailing_zeros() as usize];
-        let mut attacks =
-            KNIGHT_ATTACKS[square as usize] & !board.occupied[board.side_to_move as usize];
+        let mut attacks = KNIGHT_ATTACKS[square as usize] & destinations & targets.from(square as usize);
 
         while attacks != 0 {
             let attack = SQUARES[attacks.trailing_zeros() as usize];
@@ -32,7 +35,7 @@ pub fn generate_knight_moves(board: &Board, list: &mut MoveList) {
     }
 }
 
-pub fn generate_king_moves(board: &Board, list: &mut MoveList) {
+pub fn generate_king_moves(board: &Board, list: &mut MoveList, gen_type: GenType) {
     let enemy_occupied = board.occupied[!board.side_to_move as usize];
     let friendly_king = board.pieces[PieceType::King as usize][board.side_to_move as usize];
 
@@ -43,7 +46,8 @@ pub fn generate_king_moves(board: &Board, list: &mut MoveList) {
     let square = SQUARES[friendly_king.trailing_zeros() as usize];
 
     // 1. Generate standard king moves
-    let mut attacks = KING_ATTACKS[square as usize] & !board.occupied[board.side_to_move as usize];
+    let targets = Targets::new(board, gen_type, PieceType::King);
+    let mut attacks = KING_ATTACKS[square as usize] & gen_type.destinations(board) & targets.from(square as usize);
     while attacks != 0 {
         let attack = SQUARES[attacks.trailing_zeros() as usize];
         let attack_bb = 1u64 << attack as u64;
@@ -59,47 +63,64 @@ pub fn generate_king_moves(board: &Board, list: &mut MoveList) {
         attacks &= attacks - 1;
     }
 
-    // 2. Generate castling king moves
-    if board.side_to_move == Color::White {
-        // King must not be in check to castle
-        if is_square_attacked(board, Square::E1, Color::Black) {
-            return;
+    // 2. Generate castling king moves; checks by the rook are rare enough to be found by playing them
+    match gen_type {
+        GenType::QuietChecks => {
+            let mut castles = MoveList::new();
+            generate_castling_moves(board, &mut castles);
+            for &mv in castles.iter() {
+                let mut after = *board;
+                after.make_move(mv);
+                if is_current_king_attacked(&after) {
+                    list.push(mv);
+                }
+            }
         }
+        _ if gen_type.castling() => generate_castling_moves(board, list),
+        _ => {}
+    }
+}
 
-        // Kingside (OO)
-        if (board.castling_rights & CASTLING_WK_FLAG) != 0
-            && (board.all_occupied & CASTLING_WK_MASK) == 0
-            && !is_square_attacked(board, Square::F1, Color::Black) {
-                    list.push(Move::new(Square::E1, Square::G1, MOVE_FLAG_WK_CASTLE));
-                }
+fn generate_castling_moves(board: &Board, list: &mut MoveList) {
+    let us = board.side_to_move;
+    let king_bb = board.pieces[PieceType::King as usize][us as usize];
+    if king_bb == 0 {
+        return;
+    }
+    let king = king_bb.trailing_zeros() as usize;
+    let enemies = board.occupied[!us as usize];
 
-        // Queenside (OOO)
-        if (board.castling_rights & CASTLING_WQ_FLAG) != 0
-            && (board.all_occupied & CASTLING_WQ_MASK) == 0
-            && !is_square_attacked(board, Square::D1, Color::Black)
-        {
-            list.push(Move::new(Square::E1, Square::C1, MOVE_FLAG_WQ_CASTLE));
+    // King must not be in check to castle
+    if board.attackers_to(king, board.all_occupied) & enemies != 0 {
+        return;
+    }
+
+    for right in [2 * us as usize, 2 * us as usize + 1] {
+        let rook = board.castling_rooks[right] as usize;
+        if board.castling_rights & (1 << right) == 0 || board.pieces[PieceType::Rook as usize][us as usize] & (1 << rook) == 0 {
+            continue;
         }
-    } else {
-        // Black
-        // King must not be in check to castle
-        if is_square_attacked(board, Square::E8, Color::White) {
-            return;
+        let (king_to, rook_to) = (CASTLING_KING_TO[right] as usize, CASTLING_ROOK_TO[right] as usize);
+
+        // Every square either piece crosses or lands on must be empty but for the two of them
+        let king_path = between(king, king_to) | 1 << king_to;
+        let path = (king_path | between(rook, rook_to) | 1 << rook_to) & !(1 << king | 1 << rook);
+        if board.all_occupied & path != 0 {
+            continue;
         }
 
-        // Kingside (OO)
-        if (board.castling_rights & CASTLING_BK_FLAG) != 0
-            && (board.all_occupied & CASTLING_BK_MASK) == 0
-            && !is_square_attacked(board, Square::F8, Color::White)
-        {
-            list.push(Move::new(Square::E8, Square::G8, MOVE_FLAG_BK_CASTLE));
+        // The king may not cross an attacked square. On its target it is tested without the
+        // rook, which in Chess960 may have shielded the square along the back rank.
+        let mut crossed = king_path & !(1 << king_to);
+        let mut attacked = false;
+        while crossed != 0 {
+            attacked |= board.attackers_to(crossed.trailing_zeros() as usize, board.all_occupied) & enemies != 0;
+            crossed &= crossed - 1;
         }
-        // Queenside (OOO)
-        if (board.castling_rights & CASTLING_BQ_FLAG) != 0
-            && (board.all_occupied & CASTLING_BQ_MASK) == 0
-            && !is_square_attacked(board, Square::D8, Color::White)
-        {
-            list.push(Move::new(Square::E8, Square::C8, MOVE_FLAG_BQ_CASTLE));
+        let occupied_after = (board.all_occupied ^ (1 << king) ^ (1 << rook)) | 1 << king_to | 1 << rook_to;
+        if attacked || board.attackers_to(king_to, occupied_after) & enemies != 0 {
+            continue;
         }
+        list.push(Move::new(SQUARES[king], SQUARES[king_to], MOVE_FLAG_WK_CASTLE | (right as u16) << 12));
     }
 }
@@ -1,6 +1,7 @@
 use crate::board::*;
 use crate::r#move::*;
 use crate::square::*;
+use crate::movegen::gen_type::{GenType, Targets};
 
 pub const RANK1_MASK: u64 = 255; // A1 - H1
 pub const RANK2_MASK: u64 = 65280; // A2 - H2
@@ -27,7 +28,12 @@ pub const PAWN_H_SIDE_CAPTURE_PROMOTION_MASK_BLACK: u64 = 32512; // A2 - G2
 
 
 
-pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
+pub fn generate_pawn_moves(board: &Board, list: &mut MoveList, gen_type: GenType) {
+    // Pushes are quiet moves; captures, en passant and promotions are tactical ones
+    let targets = Targets::new(board, gen_type, PieceType::Pawn);
+    let keep = |from: Square, to: Square, tactical: bool| {
+        (if tactical { gen_type.captures() } else { gen_type.quiets() }) && targets.allows(from as usize, to as usize)
+    };
 
     // 1. Withe
     if board.side_to_move == Color::White {
@@ -40,7 +46,9 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while single_push_targets > 0 {
             let to = SQUARES[single_push_targets.trailing_zeros() as usize];
             let from = to - 8;
-            list.push(Move::new(from, to, MOVE_FLAG_QUIET));
+            if keep(from, to, false) {
+                list.push(Move::new(from, to, MOVE_FLAG_QUIET));
+            }
             single_push_targets &= single_push_targets - 1;
         }
 
@@ -52,7 +60,9 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while double_push_targets > 0 {
             let to = SQUARES[double_push_targets.trailing_zeros() as usize];
             let from = to - 16;
-            list.push(Move::new(from, to, MOVE_FLAG_DOUBLE_PAWN));
+            if keep(from, to, false) {
+                list.push(Move::new(from, to, MOVE_FLAG_DOUBLE_PAWN));
+            }
             double_push_targets &= double_push_targets - 1;
         }
 
@@ -63,7 +73,9 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while a_side_capture_targets > 0 {
             let to = SQUARES[a_side_capture_targets.trailing_zeros() as usize];
             let from = to - 7;
-            list.push(Move::new(from, to, MOVE_FLAG_CAPTURE));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_CAPTURE));
+            }
             a_side_capture_targets &= a_side_capture_targets - 1;
         }
 
@@ -73,7 +85,9 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while h_side_capture_targets > 0 {
             let to = SQUARES[h_side_capture_targets.trailing_zeros() as usize];
             let from = to - 9;
-            list.push(Move::new(from, to, MOVE_FLAG_CAPTURE));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_CAPTURE));
+            }
             h_side_capture_targets &= h_side_capture_targets - 1;
         }
 
@@ -84,10 +98,12 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while promotion_targets > 0 {
             let to = SQUARES[promotion_targets.trailing_zeros() as usize];
             let from = to - 8;
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_R));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_B));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_N));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_R));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_B));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_N));
+            }
             promotion_targets &= promotion_targets - 1;
         }
 
@@ -97,10 +113,12 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while promotion_targets_a_side_capture > 0 {
             let to = SQUARES[promotion_targets_a_side_capture.trailing_zeros() as usize];
             let from = to - 7;
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_N_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_B_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_R_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q_CAP));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_N_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_B_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_R_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q_CAP));
+            }
             promotion_targets_a_side_capture &= promotion_targets_a_side_capture - 1;
         }
 
@@ -109,10 +127,12 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while promotion_targets_h_side_capture > 0 {
             let to = SQUARES[promotion_targets_h_side_capture.trailing_zeros() as usize];
             let from = to - 9;
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_N_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_B_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_R_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q_CAP));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_N_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_B_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_R_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q_CAP));
+            }
             promotion_targets_h_side_capture &= promotion_targets_h_side_capture - 1;
         }
 
@@ -121,19 +141,25 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
             // Check if the target square is on the 6th rank (A6=40 to H6=47)
             if (en_passant_target_square >= Square::A6) && (en_passant_target_square <= Square::H6) {
                 let en_passant_target_bb: u64 = 1_u64 << (en_passant_target_square as u64);
+                // An evasion may capture the checking pawn instead of landing on a target
+                let captured = en_passant_target_square - 8;
 
                 // 1. Check A-Side capture (<< 7, e.g., D5 -> C6)
                 let attacker_mask_a_side = (en_passant_target_bb >> 7) & PAWN_A_SIDE_CAPTURE_MASK_WITHE;
                 if (attacker_mask_a_side & friendly_pawns) > 0 {
                     let from = en_passant_target_square - 7;
-                    list.push(Move::new(from, en_passant_target_square, MOVE_FLAG_EN_PASSANT));
+                    if keep(from, en_passant_target_square, true) || keep(from, captured, true) {
+                        list.push(Move::new(from, en_passant_target_square, MOVE_FLAG_EN_PASSANT));
+                    }
                 }
 
                 // 2. Check H-Side capture (<< 9, e.g., B5 -> C6)
                 let attacker_mask_h_side = (en_passant_target_bb >> 9) & PAWN_H_SIDE_CAPTURE_MASK_WITHE;
                 if (attacker_mask_h_side & friendly_pawns) > 0 {
                     let from = en_passant_target_square - 9;
-                    list.push(Move::new(from, en_passant_target_square, MOVE_FLAG_EN_PASSANT));
+                    if keep(from, en_passant_target_square, true) || keep(from, captured, true) {
+                        list.push(Move::new(from, en_passant_target_square, MOVE_FLAG_EN_PASSANT));
+                    }
                 }
             }
         }
@@ -148,7 +174,9 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while single_push_targets > 0 {
             let to = SQUARES[single_push_targets.trailing_zeros() as usize];
             let from = to + 8_u8;
-            list.push(Move::new(from, to, MOVE_FLAG_QUIET));
+            if keep(from, to, false) {
+                list.push(Move::new(from, to, MOVE_FLAG_QUIET));
+            }
             single_push_targets &= single_push_targets - 1;
         }
 
@@ -160,7 +188,9 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while double_push_targets > 0 {
             let to = SQUARES[double_push_targets.trailing_zeros() as usize];
             let from = to + 16_u8;
-            list.push(Move::new(from, to, MOVE_FLAG_DOUBLE_PAWN));
+            if keep(from, to, false) {
+                list.push(Move::new(from, to, MOVE_FLAG_DOUBLE_PAWN));
+            }
             double_push_targets &= double_push_targets - 1;
         }
 
@@ -171,7 +201,9 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while a_side_capture_targets > 0 {
             let to = SQUARES[a_side_capture_targets.trailing_zeros() as usize];
             let from = to + 9_u8;
-            list.push(Move::new(from, to, MOVE_FLAG_CAPTURE));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_CAPTURE));
+            }
             a_side_capture_targets &= a_side_capture_targets - 1;
         }
 
@@ -181,7 +213,9 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while h_side_capture_targets > 0 {
             let to = SQUARES[h_side_capture_targets.trailing_zeros() as usize];
             let from = to + 7_u8;
-            list.push(Move::new(from, to, MOVE_FLAG_CAPTURE));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_CAPTURE));
+            }
             h_side_capture_targets &= h_side_capture_targets - 1;
         }
 
@@ -192,10 +226,12 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while promotion_targets > 0 {
             let to = SQUARES[promotion_targets.trailing_zeros() as usize];
             let from = to + 8_u8;
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_R));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_B));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_N));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_R));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_B));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_N));
+            }
             promotion_targets &= promotion_targets - 1;
         }
 
@@ -205,10 +241,12 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while promotion_targets_a_side_capture > 0 {
             let to = SQUARES[promotion_targets_a_side_capture.trailing_zeros() as usize];
             let from = to + 9_u8;
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_N_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_B_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_R_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q_CAP));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_N_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_B_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_R_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q_CAP));
+            }
             promotion_targets_a_side_capture &= promotion_targets_a_side_capture - 1;
         }
 
@@ -217,10 +255,12 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
         while promotion_targets_h_side_capture > 0 {
             let to = SQUARES[promotion_targets_h_side_capture.trailing_zeros() as usize];
             let from = to + 7_u8;
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_N_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_B_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_R_CAP));
-            list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q_CAP));
+            if keep(from, to, true) {
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_N_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_B_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_R_CAP));
+                list.push(Move::new(from, to, MOVE_FLAG_PROMO_Q_CAP));
+            }
             promotion_targets_h_side_capture &= promotion_targets_h_side_capture - 1;
         }
 
@@ -229,19 +269,25 @@ pub fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
             // Check if the target square is on the 3rd rank (A3=16 to H3=23)
             if (en_passant_target_square >= Square::A3) && (en_passant_target_square <= Square::H3) {
                 let en_passant_target_bb: u64 = 1_u64 << (en_passant_target_square as u64);
+                // An evasion may capture the checking pawn instead of landing on a target
+                let captured = en_passant_target_square + 8_u8;
 
                 // 1. Check A-Side capture (>> 9, e.g., B4 -> A3)
                 let attacker_mask_a_side = (en_passant_target_bb << 9) & PAWN_A_SIDE_CAPTURE_MASK_BLACK;
                 if (attacker_mask_a_side & friendly_pawns) > 0 {
                     let from = en_passant_target_square + 9_u8;
-                    list.push(Move::new(from, en_passant_target_square, MOVE_FLAG_EN_PASSANT));
+                    if keep(from, en_passant_target_square, true) || keep(from, captured, true) {
+                        list.push(Move::new(from, en_passant_target_square, MOVE_FLAG_EN_PASSANT));
+                    }
                 }
 
                 // 2. Check H-Side capture (>> 7, e.g., G4 -> H3)
                 let attacker_mask_h_side = (en_passant_target_bb << 7) & PAWN_H_SIDE_CAPTURE_MASK_BLACK;
                 if (attacker_mask_h_side & friendly_pawns) > 0 {
                     let from = en_passant_target_square + 7_u8;
-                    list.push(Move::new(from, en_passant_target_square, MOVE_FLAG_EN_PASSANT));
+                    if keep(from, en_passant_target_square, true) || keep(from, captured, true) {
+                        list.push(Move::new(from, en_passant_target_square, MOVE_FLAG_EN_PASSANT));
+                    }
                 }
             }
         }
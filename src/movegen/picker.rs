@@ -1,4 +1,5 @@
 use crate::board::Board;
+use crate::movegen::gen_type::GenType;
 use crate::movegen::non_sliders::*;
 use crate::movegen::pawns::*;
 use crate::movegen::sliders::*;
@@ -33,13 +34,29 @@ impl GenStage {
 pub struct MovePicker {
     buffer: MoveList,
     stage: GenStage,
+    gen_type: GenType,
+    /// Generated once `gen_type` is done.
+    then: Option<GenType>,
 }
 
 impl MovePicker {
+    /// All moves, captures and promotions before quiet moves.
     pub fn new() -> Self {
         Self {
             buffer: MoveList::new(),
             stage: GenStage::Pawns,
+            gen_type: GenType::Captures,
+            then: Some(GenType::Quiets),
+        }
+    }
+
+    /// Only the moves of `gen_type`, e.g. captures for a quiescence search or evasions in check.
+    pub fn with_gen_type(gen_type: GenType) -> Self {
+        Self {
+            buffer: MoveList::new(),
+            stage: GenStage::Pawns,
+            gen_type,
+            then: None,
         }
     }
 
@@ -47,12 +64,12 @@ impl MovePicker {
         self.buffer.clear();
 
         match self.stage {
-            GenStage::Pawns => { generate_pawn_moves(board, &mut self.buffer) }
-            GenStage::Knights => { generate_knight_moves(board, &mut self.buffer) }
-            GenStage::Bishops => { generate_bishop_moves(board, &mut self.buffer) }
-            GenStage::Rooks => { generate_rook_moves(board, &mut self.buffer) }
-            GenStage::Queens => { generate_queen_moves(board, &mut self.buffer) }
-            GenStage::King => { generate_king_moves(board, &mut self.buffer) }
+            GenStage::Pawns => { generate_pawn_moves(board, &mut self.buffer, self.gen_type) }
+            GenStage::Knights => { generate_knight_moves(board, &mut self.buffer, self.gen_type) }
+            GenStage::Bishops => { generate_bishop_moves(board, &mut self.buffer, self.gen_type) }
+            GenStage::Rooks => { generate_rook_moves(board, &mut self.buffer, self.gen_type) }
+            GenStage::Queens => { generate_queen_moves(board, &mut self.buffer, self.gen_type) }
+            GenStage::King => { generate_king_moves(board, &mut self.buffer, self.gen_type) }
             GenStage::Done => {}
         }
         if let Some(next_stage) = self.stage.next() {
@@ -63,8 +80,22 @@ impl MovePicker {
     pub fn next(&mut self, board: &Board) -> Option<Move> {
         loop {
             if let Some(mv) = self.buffer.pull() { return Some(mv) }
-            if self.stage == GenStage::Done { return None }
+            if self.stage == GenStage::Done {
+                match self.then.take() {
+                    Some(gen_type) => {
+                        self.gen_type = gen_type;
+                        self.stage = GenStage::Pawns;
+                    }
+                    None => return None,
+                }
+            }
             self.generate_next_batch(board);
         }
     }
+}
+
+impl Default for MovePicker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
\ No newline at end of file
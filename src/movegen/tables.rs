@@ -634,7 +634,7 @@ fn generate_final_rook_attacks() -> Box<[[u64; 4096]; 64]> {
     final_rook_attacks
 }
 
-pub fn get_rook_attacks() -> &'static Box<[[u64; 4096]; 64]> {
+pub fn get_rook_attacks() -> &'static [[u64; 4096]; 64] {
     ROOK_ATTACKS.get_or_init(generate_final_rook_attacks)
 }
 
@@ -730,6 +730,6 @@ fn generate_final_bishop_attacks() -> Box<[[u64; 512]; 64]> {
     final_bishop_attacks
 }
 
-pub fn get_bishop_attacks() -> &'static Box<[[u64; 512]; 64]> {
+pub fn get_bishop_attacks() -> &'static [[u64; 512]; 64] {
     BISHOP_ATTACKS.get_or_init(generate_final_bishop_attacks)
 }
\ No newline at end of file
@@ -1,136 +1,295 @@
+use std::fmt;
 use std::mem;
 use crate::board::*;
+use crate::movegen::legal_check::is_other_king_attacked;
 use crate::r#move::*;
 use crate::square::Square;
 
+/// Why a SAN move could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    Invalid(String),   // Not SAN
+    Illegal(String),   // No legal move matches
+    Ambiguous(String), // More than one legal move matches
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(san) => write!(f, "invalid SAN move '{}'", san),
+            Self::Illegal(san) => write!(f, "illegal move '{}'", san),
+            Self::Ambiguous(san) => write!(f, "ambiguous move '{}'", san),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+/// Why a move in coordinate notation could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveParseError {
+    Invalid(String), // Not coordinate notation
+    NoPiece(String), // No piece of the side to move on the from square
+    Illegal(String), // No legal move matches
+}
+
+impl fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(s) => write!(f, "invalid move '{}'", s),
+            Self::NoPiece(s) => write!(f, "no piece to move in '{}'", s),
+            Self::Illegal(s) => write!(f, "illegal move '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for MoveParseError {}
+
+
+const SAN_PIECES: [(PieceType, char); 5] =
+    [(PieceType::Knight, 'N'), (PieceType::Bishop, 'B'), (PieceType::Rook, 'R'), (PieceType::Queen, 'Q'), (PieceType::King, 'K')];
+
+fn san_piece(c: char) -> Option<PieceType> {
+    SAN_PIECES.iter().find(|&&(_, letter)| letter == c).map(|&(pt, _)| pt)
+}
+
+fn san_letter(pt: PieceType) -> char {
+    SAN_PIECES.iter().find(|&&(p, _)| p == pt).map_or('?', |&(_, letter)| letter)
+}
+
+/// Why a FEN string was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    MissingFields(usize),          // Fewer than the four position fields
+    TooManyFields(usize),
+    RankCount(usize),              // Ranks in the piece placement, must be 8
+    RankLength(u8),                // Rank (1 to 8) not adding up to 8 squares
+    InvalidPiece(char),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    KingCount(Color, u32),         // Each side needs exactly one king
+    PawnOnBackRank(String),
+    CastlingWithoutPieces(char),   // Castling right without king and rook on their squares
+    ImplausibleEnPassant(String),  // No pawn could just have double pushed past the square
+    OpponentInCheck,               // The side not to move is in check
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingFields(n) => write!(f, "expected at least 4 fields, found {}", n),
+            Self::TooManyFields(n) => write!(f, "expected at most 6 fields, found {}", n),
+            Self::RankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            Self::RankLength(rank) => write!(f, "rank {} does not have 8 squares", rank),
+            Self::InvalidPiece(c) => write!(f, "invalid piece '{}'", c),
+            Self::InvalidSideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            Self::InvalidCastling(s) => write!(f, "invalid castling rights '{}'", s),
+            Self::InvalidEnPassant(s) => write!(f, "invalid en passant square '{}'", s),
+            Self::InvalidHalfmoveClock(s) => write!(f, "invalid halfmove clock '{}'", s),
+            Self::InvalidFullmoveNumber(s) => write!(f, "invalid fullmove number '{}'", s),
+            Self::KingCount(color, n) => write!(f, "{:?} has {} kings", color, n),
+            Self::PawnOnBackRank(sq) => write!(f, "pawn on {}", sq),
+            Self::CastlingWithoutPieces(c) => write!(f, "castling right '{}' without king and rook in place", c),
+            Self::ImplausibleEnPassant(sq) => write!(f, "no double pawn push could have passed {}", sq),
+            Self::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// FEN letters of the castling rights, in flag order.
+const CASTLING_LETTERS: [char; 4] = ['K', 'Q', 'k', 'q'];
+
+/// Outermost rook of `color` on its back rank on the kingside or queenside of the king, the rook
+/// an X-FEN "K" or "Q" stands for.
+fn outer_rook(pieces: &[[u64; 2]; 6], color: Color, kingside: bool) -> Option<u8> {
+    let back_rank = 0xFFu64 << (56 * color as u8);
+    let kings = pieces[PieceType::King as usize][color as usize] & back_rank;
+    if kings == 0 {
+        return None;
+    }
+    let below_king = (kings & kings.wrapping_neg()) - 1;
+    let rooks = pieces[PieceType::Rook as usize][color as usize] & back_rank;
+    let side = if kingside { rooks & !below_king & !kings } else { rooks & below_king };
+    match side {
+        0 => None,
+        _ if kingside => Some(63 - side.leading_zeros() as u8),
+        _ => Some(side.trailing_zeros() as u8),
+    }
+}
+
+fn square_name(sq: u8) -> String {
+    format!("{}{}", (b'a' + sq % 8) as char, (b'1' + sq / 8) as char)
+}
+
 impl Board {
+    /// Reads a FEN string, checking its syntax only. Panics on malformed FEN; untrusted input
+    /// goes through `try_from_fen`, which also checks that the position is legal.
     pub fn from_fen(fen: &str) -> Self {
-        let mut parts = fen.split_whitespace();
+        Self::parse_fen(fen).unwrap_or_else(|e| panic!("invalid FEN '{}': {}", fen, e))
+    }
+
+    /// Reads a FEN string of a legal position. The move counters may be left out.
+    pub fn try_from_fen(fen: &str) -> Result<Self, FenError> {
+        let board = Self::parse_fen(fen)?;
+
+        for color in [Color::White, Color::Black] {
+            let kings = board.pieces[PieceType::King as usize][color as usize].count_ones();
+            if kings != 1 {
+                return Err(FenError::KingCount(color, kings));
+            }
+        }
+
+        let pawns = board.pieces[PieceType::Pawn as usize][0] | board.pieces[PieceType::Pawn as usize][1];
+        let back_ranks = 0xFF00_0000_0000_00FF;
+        if pawns & back_ranks != 0 {
+            return Err(FenError::PawnOnBackRank(square_name((pawns & back_ranks).trailing_zeros() as u8)));
+        }
+
+        // The king on its back rank, the rook on the side of the king the right is for
+        for (right, letter) in CASTLING_LETTERS.into_iter().enumerate() {
+            let color = if right < 2 { Color::White } else { Color::Black };
+            let back_rank = 0xFFu64 << (56 * color as u8);
+            let king = board.pieces[PieceType::King as usize][color as usize] & back_rank;
+            let rook = board.castling_rooks[right].to_bitboard();
+            let side = if right % 2 == 0 { rook > king } else { rook < king };
+            let in_place = king != 0 && board.pieces[PieceType::Rook as usize][color as usize] & rook & back_rank != 0 && side;
+            if board.castling_rights & (1 << right) != 0 && !in_place {
+                return Err(FenError::CastlingWithoutPieces(letter));
+            }
+        }
+
+        if let Some(target) = board.en_passant_target {
+            // The pawn that double pushed stands in front of the target, its start and the target are empty
+            let target = target as u8;
+            let (pawn, start) = match board.side_to_move {
+                Color::White => (target - 8, target + 8),
+                Color::Black => (target + 8, target - 8),
+            };
+            let pushed = board.pieces[PieceType::Pawn as usize][!board.side_to_move as usize] & (1 << pawn) != 0;
+            if !pushed || board.all_occupied & (1 << target | 1 << start) != 0 {
+                return Err(FenError::ImplausibleEnPassant(square_name(target)));
+            }
+        }
+
+        if is_other_king_attacked(&board) {
+            return Err(FenError::OpponentInCheck);
+        }
+        Ok(board)
+    }
+
+    fn parse_fen(fen: &str) -> Result<Self, FenError> {
+        let parts: Vec<&str> = fen.split_whitespace().collect();
+        if parts.len() < 4 {
+            return Err(FenError::MissingFields(parts.len()));
+        }
+        if parts.len() > 6 {
+            return Err(FenError::TooManyFields(parts.len()));
+        }
 
         let mut pieces = [[0u64; 2]; 6];
         let mut occupied = [0u64; 2];
         let mut pieces_on_squares = [None; 64];
 
         // Part 1: Piece placement
-        let placement = parts.next().unwrap_or("");
-        let mut rank = 7;
-        let mut file = 0;
-
-        for c in placement.chars() {
-            if c.is_ascii_digit() {
-                file += c.to_digit(10).unwrap_or(0) as usize;
-            } else if c == '/' {
-                rank -= 1;
-                file = 0;
-            } else if c.is_alphabetic() {
-                let sq = (rank * 8 + file) as u8;
-                let mask = 1u64 << sq;
-
-                if c.is_uppercase() {
-                    let color_idx = Color::White as usize;
-                    occupied[color_idx] |= mask;
-                    match c {
-                        'P' => {
-                            pieces[PieceType::Pawn as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Pawn);
-                        }
-                        'N' => {
-                            pieces[PieceType::Knight as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Knight);
-                        }
-                        'B' => {
-                            pieces[PieceType::Bishop as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Bishop);
-                        }
-                        'R' => {
-                            pieces[PieceType::Rook as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Rook);
-                        }
-                        'Q' => {
-                            pieces[PieceType::Queen as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Queen);
-                        }
-                        'K' => {
-                            pieces[PieceType::King as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::King);
-                        }
-                        _ => {}
-                    }
-                } else {
-                    let color_idx = Color::Black as usize;
-                    occupied[color_idx] |= mask;
-                    match c {
-                        'p' => {
-                            pieces[PieceType::Pawn as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Pawn);
-                        }
-                        'n' => {
-                            pieces[PieceType::Knight as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Knight);
-                        }
-                        'b' => {
-                            pieces[PieceType::Bishop as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Bishop);
-                        }
-                        'r' => {
-                            pieces[PieceType::Rook as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Rook);
-                        }
-                        'q' => {
-                            pieces[PieceType::Queen as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::Queen);
-                        }
-                        'k' => {
-                            pieces[PieceType::King as usize][color_idx] |= mask;
-                            pieces_on_squares[sq as usize] = Some(PieceType::King);
-                        }
-                        _ => {}
-                    }
+        let ranks: Vec<&str> = parts[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::RankCount(ranks.len()));
+        }
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i;
+            let mut file = 0;
+            for c in rank_str.chars() {
+                if let Some(empty) = c.to_digit(10).filter(|d| (1..=8).contains(d)) {
+                    file += empty as usize;
+                    continue;
+                }
+                let piece = match c.to_ascii_lowercase() {
+                    'p' => PieceType::Pawn,
+                    'n' => PieceType::Knight,
+                    'b' => PieceType::Bishop,
+                    'r' => PieceType::Rook,
+                    'q' => PieceType::Queen,
+                    'k' => PieceType::King,
+                    _ => return Err(FenError::InvalidPiece(c)),
+                };
+                if file >= 8 {
+                    return Err(FenError::RankLength(rank as u8 + 1));
                 }
+                let sq = rank * 8 + file;
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                occupied[color as usize] |= 1 << sq;
+                pieces[piece as usize][color as usize] |= 1 << sq;
+                pieces_on_squares[sq] = Some(piece);
                 file += 1;
             }
+            if file != 8 {
+                return Err(FenError::RankLength(rank as u8 + 1));
+            }
         }
 
         // Part 2: Active color
-        let side_to_move = match parts.next().unwrap_or("w") {
+        let side_to_move = match parts[1] {
+            "w" => Color::White,
             "b" => Color::Black,
-            _ => Color::White,
+            other => return Err(FenError::InvalidSideToMove(other.to_string())),
         };
 
-        // Part 3: Castling rights
+        // Part 3: Castling rights, as KQkq or as Shredder-FEN rook files (X-FEN mixes both)
         let mut castling_rights = 0u8;
-        if let Some(castle_str) = parts.next() {
-            if castle_str.contains('K') {
-                castling_rights |= CASTLING_WK_FLAG;
-            }
-            if castle_str.contains('Q') {
-                castling_rights |= CASTLING_WQ_FLAG;
-            }
-            if castle_str.contains('k') {
-                castling_rights |= CASTLING_BK_FLAG;
-            }
-            if castle_str.contains('q') {
-                castling_rights |= CASTLING_BQ_FLAG;
+        let mut castling_rooks = CASTLING_ROOKS_STANDARD;
+        if parts[2] != "-" {
+            let invalid = || FenError::InvalidCastling(parts[2].to_string());
+            for c in parts[2].chars() {
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let back_rank = 56 * color as u8;
+                let (kingside, rook) = match c.to_ascii_lowercase() {
+                    'k' => (true, outer_rook(&pieces, color, true)),
+                    'q' => (false, outer_rook(&pieces, color, false)),
+                    file @ 'a'..='h' => {
+                        // The side follows from the king; without one on the back rank the
+                        // right is refused by `try_from_fen`
+                        let king = pieces[PieceType::King as usize][color as usize] >> back_rank & 0xFF;
+                        let file = file as u8 - b'a';
+                        (king == 0 || file > king.trailing_zeros() as u8, Some(back_rank + file))
+                    }
+                    _ => return Err(invalid()),
+                };
+                let right = 2 * color as usize + !kingside as usize;
+                if castling_rights & (1 << right) != 0 {
+                    return Err(invalid());
+                }
+                castling_rights |= 1 << right;
+                if let Some(rook) = rook {
+                    castling_rooks[right] = Square::try_from(rook).map_err(|_| invalid())?;
+                }
             }
         }
 
-        // Part 4: En passant target
-        let en_passant_target = match parts.next().unwrap_or("-") {
-            "-" => None,
-            sq_str => {
-                let chars: Vec<char> = sq_str.chars().collect();
-                let file = chars[0] as u8 - b'a';
-                let rank = chars[1] as u8 - b'1';
-                let sq_index = rank * 8 + file;
-                Some(unsafe { mem::transmute::<u8, Square>(sq_index) })
+        // Part 4: En passant target, on the rank the side to move captures onto
+        let en_passant_target = match parts[3].as_bytes() {
+            b"-" => None,
+            &[file @ b'a'..=b'h', rank] if rank == if side_to_move == Color::White { b'6' } else { b'3' } => {
+                Some(Square::try_from((rank - b'1') * 8 + file - b'a').map_err(|_| FenError::InvalidEnPassant(parts[3].to_string()))?)
             }
+            _ => return Err(FenError::InvalidEnPassant(parts[3].to_string())),
         };
 
         // Part 5: Halfmove clock
-        let halfmove_clock = parts.next().unwrap_or("0").parse::<u8>().unwrap_or(0);
+        let halfmove_clock = match parts.get(4) {
+            Some(clock) => clock.parse::<u8>().map_err(|_| FenError::InvalidHalfmoveClock(clock.to_string()))?,
+            None => 0,
+        };
 
-        // Part 6: Fullmove number
-        let fullmove_number = parts.next().unwrap_or("1").parse::<u16>().unwrap_or(1);
+        // Part 6: Fullmove number, 0 taken as 1
+        let fullmove_number = match parts.get(5) {
+            Some(number) => number.parse::<u16>().map_err(|_| FenError::InvalidFullmoveNumber(number.to_string()))?.max(1),
+            None => 1,
+        };
 
         let all_occupied = occupied[Color::White as usize] | occupied[Color::Black as usize];
         let empty_squares = !all_occupied;
@@ -144,16 +303,46 @@ impl Board {
             all_occupied,
             empty_squares,
             castling_rights,
+            castling_rooks,
             en_passant_target,
             halfmove_clock,
             fullmove_number,
             hash: 0, // Initialize hash to 0
+            pawn_hash: 0,
+            psqt_mg: 0,
+            psqt_eg: 0,
+            phase: 0,
+            chess960: false,
         };
 
         // Calculate the correct initial Zobrist hash based on the parsed FEN
         board.recalculate_hash();
+        board.recalculate_psqt();
 
-        board
+        Ok(board)
+    }
+
+    /// Reads a move in coordinate notation ("e2e4", "e7e8q", "e1g1") and checks that it is legal.
+    /// Castling is expected as the king taking its rook ("e1h1") when `chess960` is set.
+    pub fn parse_uci_move(&self, s: &str) -> Result<Move, MoveParseError> {
+        let bytes = s.as_bytes();
+        let square = |file: u8, rank: u8| (b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank);
+        let well_formed = matches!(bytes.len(), 4 | 5)
+            && square(bytes[0], bytes[1])
+            && square(bytes[2], bytes[3])
+            && bytes.get(4).is_none_or(|p| b"nbrq".contains(p));
+        if !well_formed {
+            return Err(MoveParseError::Invalid(s.to_string()));
+        }
+
+        let from = (bytes[1] - b'1') * 8 + bytes[0] - b'a';
+        if self.occupied[self.side_to_move as usize] & (1 << from) == 0 {
+            return Err(MoveParseError::NoPiece(s.to_string()));
+        }
+        self.legal_moves()
+            .into_iter()
+            .find(|mv| mv.to_uci(self) == s)
+            .ok_or(MoveParseError::Illegal(s.to_string()))
     }
 
     /// Converts the current board state into a FEN string.
@@ -197,17 +386,19 @@ impl Board {
         // Part 3: Castling rights
         fen.push(' ');
         let mut castle_str = String::new();
-        if (self.castling_rights & CASTLING_WK_FLAG) != 0 {
-            castle_str.push('K');
-        }
-        if (self.castling_rights & CASTLING_WQ_FLAG) != 0 {
-            castle_str.push('Q');
-        }
-        if (self.castling_rights & CASTLING_BK_FLAG) != 0 {
-            castle_str.push('k');
-        }
-        if (self.castling_rights & CASTLING_BQ_FLAG) != 0 {
-            castle_str.push('q');
+        for (right, letter) in CASTLING_LETTERS.into_iter().enumerate() {
+            if self.castling_rights & (1 << right) == 0 {
+                continue;
+            }
+            // KQkq for the outermost rook, as in X-FEN, else the file of the rook
+            let color = if right < 2 { Color::White } else { Color::Black };
+            let rook = self.castling_rooks[right] as u8;
+            if outer_rook(&self.pieces, color, right % 2 == 0) == Some(rook) {
+                castle_str.push(letter);
+            } else {
+                let file = (b'a' + rook % 8) as char;
+                castle_str.push(if color == Color::White { file.to_ascii_uppercase() } else { file });
+            }
         }
 
         if castle_str.is_empty() {
@@ -284,6 +475,18 @@ impl Move {
         }
     }
 
+    /// Converts the move to coordinate notation as UCI expects it on `board`: like
+    /// `to_algebraic`, except that in Chess960 castling is written as the king taking its own
+    /// rook (e.g. "b1a1").
+    pub fn to_uci(&self, board: &Board) -> String {
+        if board.chess960 && self.is_castle() {
+            let right = ((self.get_flags() >> 12) & 0b11) as usize;
+            let rook = board.castling_rooks[right] as u16;
+            return format!("{}{}", Self::square_val_to_alg(self.0 & MOVE_FROM_MASK), Self::square_val_to_alg(rook));
+        }
+        self.to_algebraic()
+    }
+
     /// Creates a Move from algebraic notation (e.g., "e2e4") and a board state.
     /// Assumes the move is valid and legal for the given board state.
     pub fn from_algebraic(s: &str, board: &Board) -> Move {
@@ -311,19 +514,17 @@ impl Move {
             }
         }
 
-        // 2. Handle Castling
+        // 2. Handle Castling, written as the king's step to its castling square or, in Chess960,
+        // as the king taking its own rook
         if moving_piece == PieceType::King {
-            let from_idx = from_sq as u8;
-            let to_idx = to_sq as u8;
-
-            // White King Side: e1g1 (idx 4 -> 6)
-            if from_idx == 4 && to_idx == 6 { return Move::new(from_sq, to_sq, MOVE_FLAG_WK_CASTLE); }
-            // White Queen Side: e1c1 (idx 4 -> 2)
-            if from_idx == 4 && to_idx == 2 { return Move::new(from_sq, to_sq, MOVE_FLAG_WQ_CASTLE); }
-            // Black King Side: e8g8 (idx 60 -> 62)
-            if from_idx == 60 && to_idx == 62 { return Move::new(from_sq, to_sq, MOVE_FLAG_BK_CASTLE); }
-            // Black Queen Side: e8c8 (idx 60 -> 58)
-            if from_idx == 60 && to_idx == 58 { return Move::new(from_sq, to_sq, MOVE_FLAG_BQ_CASTLE); }
+            let us = board.side_to_move as usize;
+            for right in [2 * us, 2 * us + 1] {
+                let takes_rook = board.castling_rooks[right] == to_sq;
+                let steps = (from_sq as u8).abs_diff(to_sq as u8) == 2 && CASTLING_KING_TO[right] == to_sq;
+                if board.castling_rights & (1 << right) != 0 && (takes_rook || steps) {
+                    return Move::new(from_sq, CASTLING_KING_TO[right], MOVE_FLAG_WK_CASTLE | (right as u16) << 12);
+                }
+            }
         }
 
         // 3. Handle Pawn Special Moves
@@ -352,4 +553,153 @@ impl Move {
             Move::new(from_sq, to_sq, MOVE_FLAG_QUIET)
         }
     }
-}
\ No newline at end of file
+}
+
+impl Move {
+    fn is_castle(&self) -> bool {
+        self.get_flags() & MOVE_MASK_CASTLE == MOVE_FLAG_CASTLE_TRUE
+    }
+
+    fn is_capture(&self) -> bool {
+        let flags = self.get_flags();
+        flags == MOVE_FLAG_CAPTURE || flags == MOVE_FLAG_EN_PASSANT || (flags & MOVE_MASK_PROMO != 0 && flags & MOVE_FLAG_CAPTURE != 0)
+    }
+
+    fn promotion_piece(&self) -> Option<PieceType> {
+        let flags = self.get_flags();
+        (flags & MOVE_MASK_PROMO != 0)
+            .then(|| [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen][((flags >> 13) & 0b11) as usize])
+    }
+
+    /// Converts a legal move of `board` to Standard Algebraic Notation (e.g. "Nbd7", "exd6",
+    /// "e8=Q+", "O-O-O#").
+    pub fn to_san(&self, board: &Board) -> String {
+        let mut board = *board;
+        let from = self.get_from() as usize;
+        let to = self.get_to() as usize;
+        let mut san = String::with_capacity(8);
+
+        if self.is_castle() {
+            san.push_str(if to % 8 == 6 { "O-O" } else { "O-O-O" });
+        } else {
+            let piece = board.pieces_on_squares[from].unwrap_or(PieceType::Pawn);
+            if piece == PieceType::Pawn {
+                if self.is_capture() {
+                    san.push((b'a' + (from % 8) as u8) as char);
+                }
+            } else {
+                san.push(san_letter(piece));
+
+                // Disambiguate by file, else by rank, else by both
+                let others: Vec<usize> = board.legal_moves()
+                    .iter()
+                    .filter(|mv| mv.get_to() as usize == to && mv.get_from() as usize != from)
+                    .map(|mv| mv.get_from() as usize)
+                    .filter(|&other| board.pieces_on_squares[other] == Some(piece))
+                    .collect();
+                if !others.is_empty() {
+                    let from_str = Self::square_val_to_alg(from as u16);
+                    if others.iter().all(|&other| other % 8 != from % 8) {
+                        san.push_str(&from_str[..1]);
+                    } else if others.iter().all(|&other| other / 8 != from / 8) {
+                        san.push_str(&from_str[1..]);
+                    } else {
+                        san.push_str(&from_str);
+                    }
+                }
+            }
+            if self.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&Self::square_val_to_alg(to as u16));
+            if let Some(promotion) = self.promotion_piece() {
+                san.push('=');
+                san.push(san_letter(promotion));
+            }
+        }
+
+        if board.gives_check(*self) {
+            board.make_move(*self);
+            san.push(if board.legal_moves().is_empty() { '#' } else { '+' });
+        }
+        san
+    }
+
+    /// Reads a move in Standard Algebraic Notation and resolves it against the legal moves of
+    /// `board`. Check, mate and annotation suffixes are optional, "0-0" and "e8Q" are accepted.
+    pub fn from_san(san: &str, board: &Board) -> Result<Move, SanError> {
+        let invalid = || SanError::Invalid(san.to_string());
+        let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let moves = board.legal_moves();
+
+        let castle_file = match text {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle_file {
+            return moves
+                .into_iter()
+                .find(|mv| mv.is_castle() && mv.get_to() as usize % 8 == file)
+                .ok_or_else(|| SanError::Illegal(san.to_string()));
+        }
+
+        let (body, promotion) = match text.split_once('=') {
+            Some((body, promotion)) => {
+                let mut chars = promotion.chars();
+                let piece = chars.next().and_then(|c| san_piece(c.to_ascii_uppercase())).ok_or_else(invalid)?;
+                if chars.next().is_some() {
+                    return Err(invalid());
+                }
+                (body, Some(piece))
+            }
+            None => match text.chars().last().and_then(san_piece) {
+                Some(piece) if text.len() > 2 => (&text[..text.len() - 1], Some(piece)),
+                _ => (text, None),
+            },
+        };
+
+        let piece = body.chars().next().and_then(san_piece);
+        let rest: Vec<char> = body.chars().skip(piece.is_some() as usize).collect();
+        let capture = rest.contains(&'x');
+        let rest: Vec<char> = rest.into_iter().filter(|&c| c != 'x').collect();
+        if rest.len() < 2 || rest.len() > 4 {
+            return Err(invalid());
+        }
+        let (hint, target) = rest.split_at(rest.len() - 2);
+        if !('a'..='h').contains(&target[0]) || !('1'..='8').contains(&target[1]) {
+            return Err(invalid());
+        }
+        let to = (target[1] as u8 - b'1') as usize * 8 + (target[0] as u8 - b'a') as usize;
+        let piece = piece.unwrap_or(PieceType::Pawn);
+        if (promotion.is_some() && piece != PieceType::Pawn) || promotion == Some(PieceType::King) {
+            return Err(invalid());
+        }
+
+        let mut hint_file = None;
+        let mut hint_rank = None;
+        for &c in hint {
+            match c {
+                'a'..='h' if hint_file.is_none() && hint_rank.is_none() => hint_file = Some((c as u8 - b'a') as usize),
+                '1'..='8' if hint_rank.is_none() => hint_rank = Some((c as u8 - b'1') as usize),
+                _ => return Err(invalid()),
+            }
+        }
+
+        let mut candidates = moves.into_iter().filter(|mv| {
+            let from = mv.get_from() as usize;
+            board.pieces_on_squares[from] == Some(piece)
+                && !mv.is_castle()
+                && mv.get_to() as usize == to
+                && mv.promotion_piece() == promotion
+                && (!capture || mv.is_capture())
+                && hint_file.is_none_or(|file| from % 8 == file)
+                && hint_rank.is_none_or(|rank| from / 8 == rank)
+        });
+        let mv = candidates.next().ok_or_else(|| SanError::Illegal(san.to_string()))?;
+        if candidates.next().is_some() {
+            return Err(SanError::Ambiguous(san.to_string()));
+        }
+        Ok(mv)
+    }
+}
@@ -0,0 +1,108 @@
+use crate::board::Board;
+use crate::r#move::Move;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// Perft counts the leaf nodes of the move tree to a fixed depth, the standard check of move
+// generation against published numbers. Moves come from the legal generator, so the last ply is
+// counted without being played.
+
+pub fn perft(board: &mut Board, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = board.legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for &mv in moves.iter() {
+        let undo = board.make_move(mv);
+        nodes += perft(board, depth - 1);
+        board.undo_move(undo);
+    }
+    nodes
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PerftEntry {
+    key: u64,
+    nodes: u64,
+    depth: u8,
+}
+
+/// Leaf counts by position and depth, so transpositions are counted once.
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+}
+
+impl PerftTable {
+    pub fn new(mb_size: usize) -> Self {
+        // Power of two like the transposition table, for indexing with a mask
+        let count = (mb_size * 1024 * 1024 / size_of::<PerftEntry>()).max(1);
+        let size = if count.is_power_of_two() { count } else { count.next_power_of_two() >> 1 };
+        PerftTable { entries: vec![PerftEntry::default(); size] }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+}
+
+/// Perft looking up and storing every subtree of depth 2 or more in `table`.
+pub fn perft_hashed(board: &mut Board, depth: u8, table: &mut PerftTable) -> u64 {
+    if depth <= 1 {
+        return perft(board, depth);
+    }
+    let index = table.index(board.hash);
+    let entry = table.entries[index];
+    if entry.key == board.hash && entry.depth == depth {
+        return entry.nodes;
+    }
+
+    let mut nodes = 0;
+    for &mv in board.legal_moves().iter() {
+        let undo = board.make_move(mv);
+        nodes += perft_hashed(board, depth - 1, table);
+        board.undo_move(undo);
+    }
+    table.entries[index] = PerftEntry { key: board.hash, nodes, depth };
+    nodes
+}
+
+/// Leaf counts below every root move, in generation order. The root moves are shared out to
+/// `threads` threads, each counting with its own table of `hash_mb` (none for 0).
+pub fn divide(board: &Board, depth: u8, threads: usize, hash_mb: usize) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let moves: Vec<Move> = board.legal_moves().into_iter().collect();
+    let counts = Mutex::new(vec![0; moves.len()]);
+    let next_move = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, moves.len().max(1)) {
+            let (moves, counts, next_move) = (&moves, &counts, &next_move);
+            scope.spawn(move || {
+                let mut table = (hash_mb > 0).then(|| PerftTable::new(hash_mb));
+                let mut board = *board;
+                loop {
+                    let index = next_move.fetch_add(1, Ordering::Relaxed);
+                    let Some(&mv) = moves.get(index) else { break };
+
+                    let undo = board.make_move(mv);
+                    let nodes = match &mut table {
+                        Some(table) => perft_hashed(&mut board, depth - 1, table),
+                        None => perft(&mut board, depth - 1),
+                    };
+                    board.undo_move(undo);
+                    counts.lock().unwrap()[index] = nodes;
+                }
+            });
+        }
+    });
+    moves.into_iter().zip(counts.into_inner().unwrap()).collect()
+}
@@ -0,0 +1,500 @@
+use crate::board::{Board, Color};
+use crate::datagen::GameResult;
+use crate::parsing::{FenError, SanError};
+use crate::r#move::Move;
+use std::fmt;
+use std::io::BufRead;
+
+// Portable Game Notation: tag pairs followed by the move text. Variations are kept on the move
+// they replace, so a variation of move n starts from the position before move n.
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Tags written first and always, in this order ("Seven Tag Roster").
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] =
+    [("Event", "?"), ("Site", "?"), ("Date", "????.??.??"), ("Round", "?"), ("White", "?"), ("Black", "?"), ("Result", "*")];
+
+/// Longest line of written move text.
+pub const LINE_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    Io(String),
+    Syntax(String),
+    Fen(FenError),
+    Move { ply: usize, san: String, error: SanError },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "unable to read PGN: {}", e),
+            Self::Syntax(e) => write!(f, "PGN syntax error: {}", e),
+            Self::Fen(e) => write!(f, "invalid FEN tag: {}", e),
+            Self::Move { ply, error, .. } => write!(f, "move {}: {}", ply / 2 + 1, error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// A move of the game with its annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnMove {
+    pub mv: Move,
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    /// Alternatives to this move, each starting from the position before it.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(mv: Move) -> Self {
+        PgnMove { mv, nags: Vec::new(), comment: None, variations: Vec::new() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// Comment before the first move.
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+    /// "1-0", "0-1", "1/2-1/2" or "*".
+    pub result: String,
+}
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        PgnGame { tags: Vec::new(), comment: None, moves: Vec::new(), result: "*".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+fn is_result(symbol: &str) -> bool {
+    matches!(symbol, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+/// Characters that end a symbol of the move text.
+const DELIMITERS: &str = "{}()[];$";
+
+/// A symbol without its move number: "12.", "12...", also glued to the move as in "12.e4".
+fn strip_move_number(symbol: &str) -> &str {
+    let digits = symbol.len() - symbol.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if symbol[digits..].starts_with('.') {
+        symbol[digits..].trim_start_matches('.')
+    } else {
+        symbol
+    }
+}
+
+/// Traditional suffix annotations and their NAGs.
+const SUFFIXES: [(&str, u8); 6] = [("!!", 3), ("??", 4), ("!?", 5), ("?!", 6), ("!", 1), ("?", 2)];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        if c == '%' && line_start {
+            chars.by_ref().find(|&c| c == '\n');
+            continue;
+        }
+        line_start = c == '\n';
+        match c {
+            _ if c.is_whitespace() => {}
+            '[' => {
+                let name: String = std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && *c != '"' && *c != ']')).collect();
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next() != Some('"') {
+                    return Err(PgnError::Syntax(format!("tag {} has no quoted value", name)));
+                }
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => value.extend(chars.next()),
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(PgnError::Syntax(format!("unterminated tag {}", name))),
+                    }
+                }
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next() != Some(']') {
+                    return Err(PgnError::Syntax(format!("tag {} is not closed", name)));
+                }
+                tokens.push(Token::Tag(name, value));
+            }
+            '{' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                tokens.push(Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" ")));
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                line_start = true;
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '(' => tokens.push(Token::VariationStart),
+            ')' => tokens.push(Token::VariationEnd),
+            '$' => {
+                let digits: String = std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_digit())).collect();
+                let nag = digits.parse().map_err(|_| PgnError::Syntax(format!("invalid NAG ${}", digits)))?;
+                tokens.push(Token::Nag(nag));
+            }
+            _ => {
+                let mut symbol = String::from(c);
+                symbol.extend(std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && !DELIMITERS.contains(*c))));
+
+                let rest = strip_move_number(&symbol);
+                if rest.is_empty() {
+                    continue;
+                }
+                if is_result(rest) {
+                    tokens.push(Token::Result(rest.to_string()));
+                    continue;
+                }
+                let san = rest.trim_end_matches(['!', '?']);
+                tokens.push(Token::Move(san.to_string()));
+                let suffix = &rest[san.len()..];
+                if let Some(&(_, nag)) = SUFFIXES.iter().find(|&&(s, _)| s == suffix) {
+                    tokens.push(Token::Nag(nag));
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a line of moves played from `board` until the end of the variation or game.
+fn parse_line(tokens: &[Token], pos: &mut usize, board: &Board, ply: usize, comment: &mut Option<String>) -> Result<Vec<PgnMove>, PgnError> {
+    let mut moves: Vec<PgnMove> = Vec::new();
+    let mut board = *board;
+    let mut before_last = board;
+
+    while let Some(token) = tokens.get(*pos) {
+        *pos += 1;
+        match token {
+            Token::Move(san) => {
+                let ply = ply + moves.len();
+                let mv = Move::from_san(san, &board).map_err(|error| PgnError::Move { ply, san: san.clone(), error })?;
+                before_last = board;
+                board.make_move(mv);
+                moves.push(PgnMove::new(mv));
+            }
+            Token::Nag(nag) => {
+                if let Some(last) = moves.last_mut() {
+                    last.nags.push(*nag);
+                }
+            }
+            Token::Comment(text) => {
+                let target = match moves.last_mut() {
+                    Some(last) => &mut last.comment,
+                    None => &mut *comment,
+                };
+                match target {
+                    Some(existing) => {
+                        existing.push(' ');
+                        existing.push_str(text);
+                    }
+                    None => *target = Some(text.clone()),
+                }
+            }
+            Token::VariationStart => {
+                let Some(last) = moves.len().checked_sub(1) else {
+                    return Err(PgnError::Syntax("variation before the first move".to_string()));
+                };
+                let mut variation_comment = None;
+                let mut variation = parse_line(tokens, pos, &before_last, ply + last, &mut variation_comment)?;
+                if tokens.get(*pos - 1) != Some(&Token::VariationEnd) {
+                    return Err(PgnError::Syntax("unterminated variation".to_string()));
+                }
+                // A comment before the first move of a variation goes with that move
+                if let (Some(text), Some(first)) = (variation_comment, variation.first_mut()) {
+                    first.comment = Some(first.comment.take().map_or(text.clone(), |c| format!("{} {}", text, c)));
+                }
+                moves[last].variations.push(variation);
+            }
+            Token::VariationEnd | Token::Result(_) => return Ok(moves),
+            Token::Tag(name, _) => return Err(PgnError::Syntax(format!("tag {} inside the move text", name))),
+        }
+    }
+    Ok(moves)
+}
+
+impl PgnGame {
+    /// A game without moves from `fen`, or from the start position for `None`.
+    pub fn new(fen: Option<&str>) -> Self {
+        let mut game = PgnGame::default();
+        if let Some(fen) = fen {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", fen);
+        }
+        game
+    }
+
+    /// Parses a single game.
+    pub fn parse(text: &str) -> Result<Self, PgnError> {
+        let tokens = tokenize(text)?;
+        let mut game = PgnGame::default();
+        let mut pos = 0;
+        while let Some(Token::Tag(name, value)) = tokens.get(pos) {
+            game.tags.push((name.clone(), value.clone()));
+            pos += 1;
+        }
+
+        let board = game.start_board()?;
+        let ply = (board.fullmove_number.max(1) as usize - 1) * 2 + board.side_to_move as usize;
+        game.moves = parse_line(&tokens, &mut pos, &board, ply, &mut game.comment)?;
+        match tokens.get(pos.wrapping_sub(1)) {
+            Some(Token::Result(_)) if pos < tokens.len() => return Err(PgnError::Syntax("move text after the result".to_string())),
+            Some(Token::Result(result)) => game.result = result.clone(),
+            Some(Token::VariationEnd) => return Err(PgnError::Syntax("unmatched ')'".to_string())),
+            _ => game.result = game.tag("Result").filter(|r| is_result(r)).unwrap_or("*").to_string(),
+        }
+        Ok(game)
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The position of the FEN tag, else the standard start position.
+    pub fn start_board(&self) -> Result<Board, PgnError> {
+        Board::try_from_fen(self.tag("FEN").unwrap_or(START_FEN)).map_err(PgnError::Fen)
+    }
+
+    /// The moves of the main line.
+    pub fn mainline(&self) -> Vec<Move> {
+        self.moves.iter().map(|m| m.mv).collect()
+    }
+
+    /// The position after the main line.
+    pub fn end_board(&self) -> Result<Board, PgnError> {
+        let mut board = self.start_board()?;
+        for mv in self.mainline() {
+            board.make_move(mv);
+        }
+        Ok(board)
+    }
+
+    /// Appends a move (legal in `end_board`) to the main line.
+    pub fn push(&mut self, mv: Move) {
+        self.moves.push(PgnMove::new(mv));
+    }
+
+    pub fn set_result(&mut self, result: Option<GameResult>) {
+        self.result = match result {
+            Some(GameResult::WhiteWin) => "1-0",
+            Some(GameResult::BlackWin) => "0-1",
+            Some(GameResult::Draw) => "1/2-1/2",
+            None => "*",
+        }
+        .to_string();
+        self.set_tag("Result", &self.result.clone());
+    }
+
+    /// The decided result, `None` for unfinished games.
+    pub fn game_result(&self) -> Option<GameResult> {
+        match self.result.as_str() {
+            "1-0" => Some(GameResult::WhiteWin),
+            "0-1" => Some(GameResult::BlackWin),
+            "1/2-1/2" => Some(GameResult::Draw),
+            _ => None,
+        }
+    }
+
+    /// Writes the game as PGN: the seven tag roster first, move text wrapped at `LINE_WIDTH`.
+    pub fn to_pgn(&self) -> String {
+        let mut out = String::new();
+        let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"");
+        for (name, default) in SEVEN_TAG_ROSTER {
+            let value = if name == "Result" { self.result.as_str() } else { self.tag(name).unwrap_or(default) };
+            out.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.iter().any(|(n, _)| n == name) {
+                out.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+            }
+        }
+        out.push('\n');
+
+        let mut tokens = Vec::new();
+        if let Some(comment) = &self.comment {
+            push_comment(&mut tokens, comment);
+        }
+        // Without a valid start position there is no board to write the moves in SAN from
+        if let Ok(board) = self.start_board() {
+            write_line(&mut tokens, &self.moves, &board);
+        }
+        tokens.push(self.result.clone());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                out.push_str(&line);
+                out.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        out.push_str(&line);
+        out.push('\n');
+        out
+    }
+}
+
+/// Comment words as separate tokens, so long comments wrap like the moves.
+fn push_comment(tokens: &mut Vec<String>, comment: &str) {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+    if words.is_empty() {
+        tokens.push("{}".to_string());
+        return;
+    }
+    for (i, word) in words.iter().enumerate() {
+        let open = if i == 0 { "{" } else { "" };
+        let close = if i == words.len() - 1 { "}" } else { "" };
+        tokens.push(format!("{}{}{}", open, word, close));
+    }
+}
+
+fn write_line(tokens: &mut Vec<String>, moves: &[PgnMove], board: &Board) {
+    let mut board = *board;
+    let mut need_number = true;
+    for pgn_move in moves {
+        let number = board.fullmove_number;
+        if board.side_to_move == Color::White {
+            tokens.push(format!("{}.", number));
+        } else if need_number {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(pgn_move.mv.to_san(&board));
+        need_number = false;
+        for nag in &pgn_move.nags {
+            tokens.push(format!("${}", nag));
+        }
+        if let Some(comment) = &pgn_move.comment {
+            push_comment(tokens, comment);
+            need_number = true;
+        }
+        for variation in &pgn_move.variations {
+            // No space after "(" and before ")"
+            let open = tokens.len();
+            write_line(tokens, variation, &board);
+            if tokens.len() == open {
+                tokens.push("()".to_string());
+            } else {
+                tokens[open].insert(0, '(');
+                tokens.last_mut().unwrap().push(')');
+            }
+            need_number = true;
+        }
+        board.make_move(pgn_move.mv);
+    }
+}
+
+/// Reads the games of a PGN file one at a time.
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    pending: String, // Text already read for the next game
+    line: usize,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        PgnReader { reader, pending: String::new(), line: 0 }
+    }
+
+    /// Text of the next game: its tags and the move text up to its result, or up to the next
+    /// tag section for a game without one.
+    fn next_text(&mut self) -> Result<Option<String>, PgnError> {
+        let mut text = String::new();
+        let mut in_movetext = false;
+        let mut in_comment = false;
+        let mut depth = 0_usize; // Of variations
+        loop {
+            let mut line = std::mem::take(&mut self.pending);
+            if line.is_empty() {
+                let read = self.reader.read_line(&mut line).map_err(|e| PgnError::Io(format!("line {}: {}", self.line + 1, e)))?;
+                if read == 0 {
+                    return Ok((!text.trim().is_empty()).then_some(text));
+                }
+                self.line += 1;
+            }
+            let trimmed = line.trim_start();
+            if !in_comment && (trimmed.starts_with('[') || trimmed.starts_with('%')) {
+                if in_movetext && trimmed.starts_with('[') {
+                    self.pending = line;
+                    return Ok(Some(text));
+                }
+                text.push_str(&line);
+                continue;
+            }
+            if !trimmed.is_empty() && !in_comment {
+                in_movetext = true;
+            }
+
+            // A result outside comments and variations ends the game, the rest of the line
+            // belongs to the next one
+            let mut symbol_start = None;
+            for (i, c) in line.char_indices().chain(std::iter::once((line.len(), '\n'))) {
+                if in_comment {
+                    in_comment = c != '}';
+                    continue;
+                }
+                if !c.is_whitespace() && !DELIMITERS.contains(c) {
+                    symbol_start.get_or_insert(i);
+                    continue;
+                }
+                if let Some(start) = symbol_start.take()
+                    && depth == 0
+                    && is_result(strip_move_number(&line[start..i]))
+                {
+                    self.pending = line[i..].to_string();
+                    text.push_str(&line[..i]);
+                    text.push('\n');
+                    return Ok(Some(text));
+                }
+                match c {
+                    '{' => in_comment = true,
+                    '(' => depth += 1,
+                    ')' => depth = depth.saturating_sub(1),
+                    ';' => break,
+                    _ => {}
+                }
+            }
+            text.push_str(&line);
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_text() {
+            Ok(Some(text)) => Some(PgnGame::parse(&text)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
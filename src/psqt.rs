@@ -11,6 +11,14 @@ const EG_QUEEN_VAL: i32 = 936;
 const MG_KING_VAL: i32 = 0;
 const EG_KING_VAL: i32 = 0;
 
+// Plain material values indexed by PieceType
+pub const MG_MATERIAL: [i32; 6] = [MG_PAWN_VAL, MG_KNIGHT_VAL, MG_BISHOP_VAL, MG_ROOK_VAL, MG_QUEEN_VAL, MG_KING_VAL];
+pub const EG_MATERIAL: [i32; 6] = [EG_PAWN_VAL, EG_KNIGHT_VAL, EG_BISHOP_VAL, EG_ROOK_VAL, EG_QUEEN_VAL, EG_KING_VAL];
+
+// Contribution of each piece type to the game phase (24 = all pieces on the board)
+pub const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+pub const MAX_PHASE: i32 = 24;
+
 pub const MG_PAWN_TABLE: [i32; 64] = [
     0,   0,   0,   0,   0,   0,   0,   0,
     98, 134,  61,  95,  68, 126,  34, -11,
@@ -145,7 +153,7 @@ pub const EG_KING_TABLE: [i32; 64] = [
 
 /// Combines logic: mirrors (if needed) AND adds material value.
 /// This happens at compile-time, so no runtime cost.
-const fn prepare_table(table: [i32; 64], material: i32, mirror: bool) -> [i32; 64] {
+pub const fn prepare_table(table: [i32; 64], material: i32, mirror: bool) -> [i32; 64] {
     let mut out = [0; 64];
     let mut i = 0;
     while i < 64 {
@@ -1,15 +1,52 @@
 use crate::board::{Board, Color};
-use crate::eval::evaluate_board;
+use crate::dtm::{Dtm, DtmTables};
+use crate::eval::evaluate_with;
+use crate::eval::nnue::AccumulatorStack;
+use crate::eval::params::EvalTables;
+use crate::eval::kpk::probe_kpk;
 use crate::movegen::legal_check::*;
 use crate::r#move::Move;
+use crate::movegen::gen_type::GenType;
 use crate::movegen::picker::MovePicker;
+use crate::syzygy::{Tablebases, Wdl};
 use crate::tt::{TranspositionTable, NodeType};
 use std::time::{Instant, Duration};
 
 const MATE_SCORE: i32 = 1_000_000;
 
-fn evaluate_board_relative(board: &Board) -> i32 {
-    let static_eval = evaluate_board(board);
+/// Score of a tablebase win, below the mate range so the TT does not adjust it.
+pub const TB_WIN_SCORE: i32 = MATE_SCORE / 2;
+
+/// Evaluation weights and endgame tables read by the search, owned by the `Engine`.
+#[derive(Clone, Copy)]
+pub struct SearchTables<'a> {
+    pub eval: &'a EvalTables,
+    pub syzygy: Option<&'a Tablebases>,
+    pub dtm: Option<&'a DtmTables>,
+}
+
+/// Side to move relative score of a tablebase result `ply` plies from the root.
+/// Wins and losses the 50 move rule saves count as draws.
+pub fn tablebase_score(wdl: Wdl, ply: u8) -> i32 {
+    match wdl {
+        Wdl::Win => TB_WIN_SCORE - ply as i32,
+        Wdl::Loss => -TB_WIN_SCORE + ply as i32,
+        _ => 0,
+    }
+}
+
+/// Side to move relative score of a distance to mate `ply` plies from the root, in the
+/// regular mate range.
+pub fn dtm_score(dtm: Dtm, ply: u8) -> i32 {
+    match dtm {
+        Dtm::Win(plies) => MATE_SCORE - (ply as i32 + plies as i32),
+        Dtm::Loss(plies) => -MATE_SCORE + ply as i32 + plies as i32,
+        Dtm::Draw => 0,
+    }
+}
+
+fn evaluate_board_relative(board: &Board, tables: &EvalTables, accumulators: &AccumulatorStack) -> i32 {
+    let static_eval = evaluate_with(board, tables, accumulators);
     match board.side_to_move {
         Color::White => static_eval,
         Color::Black => -static_eval,
@@ -36,6 +73,12 @@ fn score_from_tt(score: i32, ply: u8) -> i32 {
     }
 }
 
+#[inline(always)]
+fn out_of_budget(nodes: u64, node_limit: u64, start_time: Instant, time_limit: Duration) -> bool {
+    nodes >= node_limit || (nodes.is_multiple_of(4096) && start_time.elapsed() > time_limit)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn alpha_beta(
     board: &mut Board,
     depth: u8,
@@ -44,11 +87,13 @@ pub fn alpha_beta(
     beta: i32,
     start_time: Instant,
     time_limit: Duration,
+    node_limit: u64,
     nodes: &mut u64,
     tt: &mut TranspositionTable,
+    accumulators: &mut AccumulatorStack,
+    tables: SearchTables,
 ) -> (Option<Move>, i32) {
-    if (*nodes).is_multiple_of(4096)
-        && start_time.elapsed() > time_limit {
+    if out_of_budget(*nodes, node_limit, start_time, time_limit) {
         return (None, 0);
     }
     *nodes += 1;
@@ -66,23 +111,42 @@ pub fn alpha_beta(
 
             match entry.node_type {
                 NodeType::Exact => return (Some(entry.bm), tt_score),
-                NodeType::Alpha => {
-                    if tt_score <= alpha {
-                        return (Some(entry.bm), tt_score);
-                    }
+                NodeType::Alpha if tt_score <= alpha => {
+                    return (Some(entry.bm), tt_score);
                 }
-                NodeType::Beta => {
-                    if tt_score >= beta {
-                        return (Some(entry.bm), tt_score);
-                    }
+                NodeType::Beta if tt_score >= beta => {
+                    return (Some(entry.bm), tt_score);
                 }
                 _ => {}
             }
         }
     }
 
+    // Solved endings are exact, including the distance to mate
+    if ply > 0
+        && let Some(dtm_tables) = tables.dtm
+        && let Some(dtm) = dtm_tables.probe(board)
+    {
+        return (None, dtm_score(dtm, ply));
+    }
+
+    // Drawn king and pawn endings need no search (the root still has to pick a move)
+    if ply > 0 && board.phase == 0 && probe_kpk(board) == Some(false) {
+        return (None, 0);
+    }
+
+    // Tablebase result right after a capture or pawn move, the root is ranked by DTZ instead
+    if ply > 0
+        && board.halfmove_clock == 0
+        && let Some(tb) = tables.syzygy
+        && tb.covers(board)
+        && let Some(wdl) = tb.probe_wdl(board)
+    {
+        return (None, tablebase_score(wdl, ply));
+    }
+
     if depth == 0 {
-        return (None, evaluate_board_relative(board));
+        return (None, evaluate_board_relative(board, tables.eval, accumulators));
     }
 
     let mut best_move: Option<Move> = None;
@@ -90,15 +154,17 @@ pub fn alpha_beta(
     let mut legal_moves_found = false;
     let alpha_orig = alpha;
 
-    let mut picker = MovePicker::new();
+    // In check only evasions can be legal
+    let in_check = is_current_king_attacked(board);
+    let mut picker = if in_check { MovePicker::with_gen_type(GenType::Evasions) } else { MovePicker::new() };
     let mut moves_tried = 0;
 
     loop {
         // Move selection logic:
         // 1. Try TT move first (if exists)
         // 2. Then use the picker for the rest
-        let mv = if moves_tried == 0 && tt_move.is_some() {
-            tt_move.unwrap()
+        let mv = if moves_tried == 0 && let Some(m) = tt_move {
+            m
         } else {
             match picker.next(board) {
                 Some(m) => {
@@ -123,9 +189,11 @@ pub fn alpha_beta(
         }
         legal_moves_found = true;
 
-        let (_, score) = alpha_beta(board, depth - 1, ply + 1, -beta, -alpha, start_time, time_limit, nodes, tt);
+        accumulators.push(board, &undo_mv.changes);
+        let (_, score) = alpha_beta(board, depth - 1, ply + 1, -beta, -alpha, start_time, time_limit, node_limit, nodes, tt, accumulators, tables);
+        accumulators.pop();
 
-        if (*nodes).is_multiple_of(4096) && start_time.elapsed() > time_limit {
+        if out_of_budget(*nodes, node_limit, start_time, time_limit) {
             board.undo_move(undo_mv);
             return (None, 0);
         }
@@ -149,7 +217,7 @@ pub fn alpha_beta(
     }
 
     if !legal_moves_found {
-        if is_current_king_attacked(board) {
+        if in_check {
             return (None, -MATE_SCORE + (ply as i32));
         } else {
             return (None, 0);
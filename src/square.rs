@@ -91,7 +91,7 @@ impl TryFrom<u8> for Square {
     type Error = &'static str;
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         if value <= 63 {
-            Ok(unsafe { std::mem::transmute(value) })
+            Ok(unsafe { std::mem::transmute::<u8, Square>(value) })
         } else {
             Err("Square index out of bounds")
         }
@@ -0,0 +1,402 @@
+pub mod table;
+
+use crate::board::{Board, PieceType};
+use crate::eval::endgame::material_key;
+use crate::movegen::legal_check::is_current_king_attacked;
+use crate::r#move::{Move, MOVE_FLAG_CAPTURE, MOVE_FLAG_EN_PASSANT, MOVE_MASK_PROMO};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use table::{Lookup, Table, TableInfo, TableKind};
+
+/// Largest DTZ value, root moves are ranked relative to it.
+const MAX_DTZ: i32 = 1 << 18;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyzygyError {
+    Io(String),
+    NoTables(String),
+    Corrupt(String),
+}
+
+impl fmt::Display for SyzygyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "unable to read tablebase: {}", e),
+            Self::NoTables(path) => write!(f, "no tablebase files found in {}", path),
+            Self::Corrupt(e) => write!(f, "corrupt tablebase file {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SyzygyError {}
+
+/// Win/draw/loss for the side to move. Cursed wins and blessed losses are wins and losses
+/// that the 50 move rule turns into draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_i32(value: i32) -> Self {
+        match value {
+            ..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    /// The result for the other side.
+    pub fn flip(self) -> Self {
+        Self::from_i32(-(self as i32))
+    }
+}
+
+/// Outcome of a root probe: the move to play, the expected result and its distance to zeroing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootProbe {
+    pub best_move: Move,
+    pub wdl: Wdl,
+    pub dtz: i32,
+}
+
+// Progress of a probe, mirrors the states of the reference implementation
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProbeState {
+    Fail,
+    Ok,
+    ChangeStm,
+    ZeroingBestMove,
+}
+
+struct TableEntry {
+    info: TableInfo,
+    wdl_path: PathBuf,
+    dtz_path: Option<PathBuf>,
+    // Opened on first use, `None` if the file turned out to be unusable
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+/// The tables found in the `SyzygyPath` directories.
+pub struct Tablebases {
+    entries: Vec<TableEntry>,
+    by_key: HashMap<u64, usize>,
+    max_pieces: usize,
+    hits: AtomicU64,
+}
+
+
+fn is_capture(mv: Move) -> bool {
+    let flags = mv.get_flags();
+    flags == MOVE_FLAG_CAPTURE || flags == MOVE_FLAG_EN_PASSANT || (flags & MOVE_MASK_PROMO != 0 && flags & MOVE_FLAG_CAPTURE != 0)
+}
+
+fn is_zeroing(board: &Board, mv: Move) -> bool {
+    is_capture(mv) || board.pieces_on_squares[mv.get_from() as usize] == Some(PieceType::Pawn)
+}
+
+/// DTZ of a position whose best move is a zeroing move.
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    match wdl {
+        2 => 1,
+        1 => 101,
+        -1 => -101,
+        -2 => -1,
+        _ => 0,
+    }
+}
+
+impl Tablebases {
+    /// Scans the directories of `path` (separated like `PATH`) for `.rtbw` files and their
+    /// `.rtbz` counterparts. Files are only read once a position needs them.
+    pub fn open(path: &str) -> Result<Self, SyzygyError> {
+        let mut tablebases = Tablebases { entries: Vec::new(), by_key: HashMap::new(), max_pieces: 0, hits: AtomicU64::new(0) };
+
+        for dir in env::split_paths(path) {
+            let files = fs::read_dir(&dir).map_err(|e| SyzygyError::Io(format!("{}: {}", dir.display(), e)))?;
+            for file in files.flatten() {
+                let file_path = file.path();
+                if file_path.extension().and_then(|e| e.to_str()) != Some(TableKind::Wdl.extension()) {
+                    continue;
+                }
+                let Some(info) = file_path.file_stem().and_then(|s| s.to_str()).and_then(TableInfo::from_name) else {
+                    continue;
+                };
+                if tablebases.by_key.contains_key(&info.key) {
+                    continue;
+                }
+
+                let dtz_path = file_path.with_extension(TableKind::Dtz.extension());
+                let index = tablebases.entries.len();
+                tablebases.by_key.insert(info.key, index);
+                tablebases.by_key.insert(info.key2, index);
+                tablebases.max_pieces = tablebases.max_pieces.max(info.piece_count);
+                tablebases.entries.push(TableEntry {
+                    info,
+                    wdl_path: file_path,
+                    dtz_path: dtz_path.is_file().then_some(dtz_path),
+                    wdl: OnceLock::new(),
+                    dtz: OnceLock::new(),
+                });
+            }
+        }
+
+        if tablebases.entries.is_empty() {
+            return Err(SyzygyError::NoTables(path.to_string()));
+        }
+        Ok(tablebases)
+    }
+
+    /// Number of WDL tables found.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Largest piece count (kings included) covered by some table.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Successful probes so far, reported as `tbhits`.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Whether `board` could be in the tables: few enough pieces and no castling rights.
+    pub fn covers(&self, board: &Board) -> bool {
+        board.castling_rights == 0 && board.all_occupied.count_ones() as usize <= self.max_pieces
+    }
+
+    fn table(&self, board: &Board, kind: TableKind) -> Option<(&Table, u64)> {
+        let key = material_key(board);
+        let entry = &self.entries[*self.by_key.get(&key)?];
+        let table = match kind {
+            TableKind::Wdl => entry.wdl.get_or_init(|| Table::load(&entry.wdl_path, &entry.info, kind).ok()),
+            TableKind::Dtz => entry
+                .dtz
+                .get_or_init(|| entry.dtz_path.as_ref().and_then(|path| Table::load(path, &entry.info, kind).ok())),
+        };
+        table.as_ref().map(|table| (table, key))
+    }
+
+    fn probe_table(&self, board: &Board, kind: TableKind, wdl: i32, state: &mut ProbeState) -> i32 {
+        // Bare kings have no table
+        if board.all_occupied.count_ones() == 2 {
+            return 0;
+        }
+        let Some((table, key)) = self.table(board, kind) else {
+            *state = ProbeState::Fail;
+            return 0;
+        };
+        match table.probe(board, key, wdl) {
+            Some(Lookup::Value(value)) => value,
+            Some(Lookup::ChangeStm) => {
+                *state = ProbeState::ChangeStm;
+                0
+            }
+            None => {
+                *state = ProbeState::Fail;
+                0
+            }
+        }
+    }
+
+    /// Tables may store anything for positions where a capture (or with `zeroing_moves` a
+    /// pawn move) is best, and nothing about en passant. So those moves are searched and
+    /// the best of their results and the stored value is the real one.
+    fn search(&self, board: &mut Board, zeroing_moves: bool, state: &mut ProbeState) -> i32 {
+        let moves = board.legal_moves();
+        let mut best = -2;
+        let mut searched = 0;
+
+        for &mv in &moves {
+            if !(is_capture(mv) || (zeroing_moves && is_zeroing(board, mv))) {
+                continue;
+            }
+            searched += 1;
+
+            let undo = board.make_move(mv);
+            let value = -self.search(board, false, state);
+            board.undo_move(undo);
+
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+            if value > best {
+                best = value;
+                if value >= 2 {
+                    *state = ProbeState::ZeroingBestMove;
+                    return value;
+                }
+            }
+        }
+
+        // All moves searched, the stored value may be wrong (en passant) and is not needed
+        let no_more_moves = searched > 0 && searched == moves.len();
+        let value = if no_more_moves {
+            best
+        } else {
+            let value = self.probe_table(board, TableKind::Wdl, 0, state);
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+            value
+        };
+
+        if best >= value {
+            *state = if best > 0 || no_more_moves { ProbeState::ZeroingBestMove } else { ProbeState::Ok };
+            return best;
+        }
+        *state = ProbeState::Ok;
+        value
+    }
+
+    fn dtz(&self, board: &mut Board, state: &mut ProbeState) -> i32 {
+        *state = ProbeState::Ok;
+        let wdl = self.search(board, true, state);
+        if *state == ProbeState::Fail || wdl == 0 {
+            return 0;
+        }
+        if *state == ProbeState::ZeroingBestMove {
+            return dtz_before_zeroing(wdl);
+        }
+
+        let dtz = self.probe_table(board, TableKind::Dtz, wdl, state);
+        if *state == ProbeState::Fail {
+            return 0;
+        }
+        if *state != ProbeState::ChangeStm {
+            let cursed = if wdl == 1 || wdl == -1 { 100 } else { 0 };
+            return (dtz + cursed) * wdl.signum();
+        }
+
+        // Only the other side to move is stored: one ply search for the best DTZ
+        let mut min_dtz = i32::MAX;
+        for &mv in board.legal_moves().iter() {
+            let zeroing = is_zeroing(board, mv);
+            let undo = board.make_move(mv);
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(board, false, state))
+            } else {
+                -self.dtz(board, state)
+            };
+            if dtz == 1 && is_current_king_attacked(board) && board.legal_moves().is_empty() {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+            board.undo_move(undo);
+
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+        }
+        if min_dtz == i32::MAX { -1 } else { min_dtz }
+    }
+
+    /// WDL result of `board` for the side to move, `None` if no table covers it.
+    /// Assumes the 50 move counter was just reset, as after a capture or pawn move.
+    pub fn probe_wdl(&self, board: &mut Board) -> Option<Wdl> {
+        if !self.covers(board) {
+            return None;
+        }
+        let mut state = ProbeState::Ok;
+        let value = self.search(board, false, &mut state);
+        if state == ProbeState::Fail {
+            return None;
+        }
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(Wdl::from_i32(value))
+    }
+
+    /// Distance to zeroing in plies, positive when the side to move wins; 0 for draws.
+    /// The value may be off by one ply for positions where the previous move zeroed.
+    pub fn probe_dtz(&self, board: &mut Board) -> Option<i32> {
+        if !self.covers(board) {
+            return None;
+        }
+        let mut state = ProbeState::Ok;
+        let dtz = self.dtz(board, &mut state);
+        if state == ProbeState::Fail {
+            return None;
+        }
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(dtz)
+    }
+
+    /// Picks the root move by DTZ: a win that zeroes before the 50 move rule, preferably the
+    /// fastest, else a draw, else the loss that takes longest. `None` if any move is not covered.
+    pub fn probe_root(&self, board: &mut Board) -> Option<RootProbe> {
+        if !self.covers(board) {
+            return None;
+        }
+        let halfmove_clock = board.halfmove_clock as i32;
+        let mut best: Option<(i32, i32, Move)> = None;
+
+        for &mv in board.legal_moves().iter() {
+            let undo = board.make_move(mv);
+            let mut state = ProbeState::Ok;
+            let mut dtz = if board.halfmove_clock == 0 {
+                dtz_before_zeroing(-self.search(board, false, &mut state))
+            } else {
+                let dtz = -self.dtz(board, &mut state);
+                dtz + dtz.signum()
+            };
+            if dtz == 2 && is_current_king_attacked(board) && board.legal_moves().is_empty() {
+                dtz = 1;
+            }
+            board.undo_move(undo);
+            if state == ProbeState::Fail {
+                return None;
+            }
+
+            // Wins within the 50 move rule rank equally, beyond it the sooner the better;
+            // losses the other way round
+            let rank = if dtz > 0 {
+                if dtz + halfmove_clock <= 99 { MAX_DTZ } else { MAX_DTZ - (dtz + halfmove_clock) }
+            } else if dtz < 0 {
+                if -dtz * 2 + halfmove_clock < 100 { -MAX_DTZ } else { -MAX_DTZ + (-dtz + halfmove_clock) }
+            } else {
+                0
+            };
+            if best.is_none_or(|(best_rank, best_dtz, _)| (rank, -dtz) > (best_rank, -best_dtz)) {
+                best = Some((rank, dtz, mv));
+            }
+        }
+
+        let (rank, dtz, best_move) = best?;
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        let wdl = if rank == MAX_DTZ {
+            Wdl::Win
+        } else if rank > 0 {
+            Wdl::CursedWin
+        } else if rank == 0 {
+            Wdl::Draw
+        } else if rank == -MAX_DTZ {
+            Wdl::Loss
+        } else {
+            Wdl::BlessedLoss
+        };
+        Some(RootProbe { best_move, wdl, dtz })
+    }
+}
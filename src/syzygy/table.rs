@@ -0,0 +1,831 @@
+use crate::board::{Board, Color, PieceType};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+use super::SyzygyError;
+
+// Decoder for the Syzygy table format (.rtbw win/draw/loss, .rtbz distance to zeroing).
+// A table is a Huffman coded, "recursive pairing" compressed array of values indexed by a
+// perfect hash of the piece placement. Pawnful tables are split in four by the file of the
+// leading pawn; WDL tables of asymmetric material store both sides to move.
+
+pub const TB_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// First read of a header, doubled until the whole header is in
+const HEADER_READ: usize = 4096;
+
+// Per table (or file slice) flags
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+impl TableKind {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Wdl => "rtbw",
+            Self::Dtz => "rtbz",
+        }
+    }
+}
+
+/// Result of a table lookup. DTZ tables only store one side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Value(i32),
+    ChangeStm,
+}
+
+/// Square maps and binomials used by the position index.
+struct Encoding {
+    binomial: [[u64; 64]; TB_PIECES],
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+    map_b1h1h7: [u64; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[u64; 64]; 10],
+}
+
+fn off_a1h8(sq: usize) -> i32 {
+    (sq / 8) as i32 - (sq % 8) as i32
+}
+
+fn king_zone(sq: usize) -> u64 {
+    let mut zone = 0;
+    for rank in (sq / 8).saturating_sub(1)..=(sq / 8 + 1).min(7) {
+        for file in (sq % 8).saturating_sub(1)..=(sq % 8 + 1).min(7) {
+            zone |= 1 << (rank * 8 + file);
+        }
+    }
+    zone
+}
+
+impl Encoding {
+    fn new() -> Self {
+        let mut e = Encoding {
+            binomial: [[0; 64]; TB_PIECES],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+        };
+
+        // Squares below the a1-h8 diagonal
+        let mut code = 0;
+        for sq in 0..64 {
+            if off_a1h8(sq) < 0 {
+                e.map_b1h1h7[sq] = code;
+                code += 1;
+            }
+        }
+
+        // The a1-d1-d4 triangle, diagonal squares last
+        let mut code = 0;
+        let mut diagonal = Vec::new();
+        for sq in 0..28 {
+            if off_a1h8(sq) < 0 && sq % 8 <= 3 {
+                e.map_a1d1d4[sq] = code;
+                code += 1;
+            } else if off_a1h8(sq) == 0 && sq % 8 <= 3 {
+                diagonal.push(sq);
+            }
+        }
+        for sq in diagonal {
+            e.map_a1d1d4[sq] = code;
+            code += 1;
+        }
+
+        // The 462 legal king pairs with the first king in the triangle; when it is on the
+        // diagonal the second one is not above it. Both kings on the diagonal come last.
+        let mut code = 0;
+        let mut both_on_diagonal = Vec::new();
+        for idx in 0..10 {
+            for s1 in 0..28 {
+                if e.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+                for s2 in 0..64 {
+                    if king_zone(s1) & (1 << s2) != 0 || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0) {
+                        continue;
+                    }
+                    if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        e.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            e.map_kk[idx][s2] = code;
+            code += 1;
+        }
+        debug_assert_eq!(code, 462);
+
+        e.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..TB_PIECES.min(n + 1) {
+                e.binomial[k][n] = if k > 0 { e.binomial[k - 1][n - 1] } else { 0 } + if k < n { e.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // a2-h7 mapped to 0..47, the highest value goes to the leading pawn: nearest to the
+        // edge and then lowest rank
+        let mut available = 47;
+        for lead_count in 1..6 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let sq = rank * 8 + file;
+                    if lead_count == 1 {
+                        e.map_pawns[sq] = available;
+                        e.map_pawns[sq ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    e.lead_pawn_idx[lead_count][sq] = idx;
+                    idx += e.binomial[lead_count - 1][e.map_pawns[sq]];
+                }
+                e.lead_pawns_size[lead_count][file] = idx;
+            }
+        }
+        e
+    }
+}
+
+fn encoding() -> &'static Encoding {
+    static ENCODING: OnceLock<Encoding> = OnceLock::new();
+    ENCODING.get_or_init(Encoding::new)
+}
+
+/// Material and layout of a table, derived from its name ("KRPvKR").
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    pub name: String,
+    pub key: u64,  // Material key with the first side white
+    pub key2: u64, // ... and black
+    pub piece_count: usize,
+    pub has_pawns: bool,
+    has_unique_pieces: bool,
+    pawn_count: [usize; 2], // Leading color first
+}
+
+impl TableInfo {
+    /// Parses a table name, `None` if it is not a valid "KxxvKyy" code.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (first, second) = name.split_once('v')?;
+        if !first.starts_with('K') || !second.starts_with('K') {
+            return None;
+        }
+        let code = format!("{}{}", first, second);
+        let valid = code[1..].chars().filter(|&c| c == 'K').count() == 1
+            && code.chars().all(|c| "KQRBNP".contains(c))
+            && code.len() <= TB_PIECES;
+        if !valid {
+            return None;
+        }
+
+        let count = |side: &str, c: char| side.chars().filter(|&x| x == c).count();
+        let has_unique_pieces = "QRBNP".chars().any(|c| count(first, c) == 1 || count(second, c) == 1);
+        let (white_pawns, black_pawns) = (count(first, 'P'), count(second, 'P'));
+        // The side with fewer pawns leads, it compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] };
+
+        Some(TableInfo {
+            name: name.to_string(),
+            key: crate::eval::endgame::material_key_from_code(&code, Color::White),
+            key2: crate::eval::endgame::material_key_from_code(&code, Color::Black),
+            piece_count: code.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count,
+        })
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.key == self.key2
+    }
+}
+
+/// Decoding data of one table slice (side to move and leading pawn file).
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    pieces: [u8; TB_PIECES],
+    group_len: [usize; TB_PIECES + 1],
+    group_idx: [u64; TB_PIECES + 1],
+    block_size: usize,
+    span: u64,
+    blocks_num: usize,
+    max_sym_len: u8,
+    min_sym_len: u8, // The stored value for single value tables
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    btree: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    block_length: usize,
+    block_length_size: usize,
+    data: usize,
+    map_idx: [usize; 4],
+}
+
+/// An open table file. Only the header is kept in memory, the index and the compressed
+/// blocks are read as positions are probed.
+pub struct Table {
+    info: TableInfo,
+    kind: TableKind,
+    file: File,
+    size: usize,
+    header: Vec<u8>, // Everything before the sparse index: pieces, sizes, symbols and DTZ map
+    items: Vec<Vec<PairsData>>, // [side][leading pawn file]
+    map: usize,
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], at: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, at)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut at: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, at)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            read => {
+                buf = &mut buf[read..];
+                at += read as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Offsets come from the file, so every read is checked and `None` past its end
+
+fn read_u8(bytes: &[u8], at: usize) -> Option<usize> {
+    bytes.get(at).map(|&byte| byte as usize)
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<usize> {
+    Some(u16::from_le_bytes(bytes.get(at..at.checked_add(2)?)?.try_into().ok()?) as usize)
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<usize> {
+    Some(u32::from_le_bytes(bytes.get(at..at.checked_add(4)?)?.try_into().ok()?) as usize)
+}
+
+fn read_be_u32(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u32::from_be_bytes(bytes.get(at..at.checked_add(4)?)?.try_into().ok()?) as u64)
+}
+
+impl Table {
+    /// Opens a table and reads its header.
+    pub fn load(path: &Path, info: &TableInfo, kind: TableKind) -> Result<Self, SyzygyError> {
+        let io_error = |e: io::Error| SyzygyError::Io(format!("{}: {}", path.display(), e));
+        let corrupt = |what: &str| SyzygyError::Corrupt(format!("{}: {}", path.display(), what));
+        let file = File::open(path).map_err(io_error)?;
+        let size = file.metadata().map_err(io_error)?.len() as usize;
+
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        let mut table = Table { info: info.clone(), kind, file, size, header: Vec::new(), items: Vec::new(), map: 0 };
+        let mut len = size.min(HEADER_READ);
+        loop {
+            table.header = vec![0; len];
+            read_exact_at(&table.file, &mut table.header, 0).map_err(io_error)?;
+            let header = &table.header;
+            if len < 16 || header[0..4] != magic {
+                return Err(corrupt("bad magic"));
+            }
+            if ((header[4] & 2) != 0) != info.has_pawns || (kind == TableKind::Wdl && ((header[4] & 1) != 0) == info.is_symmetric()) {
+                return Err(corrupt(&format!("header does not match {}", info.name)));
+            }
+
+            // The header's length is only known once it has been parsed
+            if table.parse().is_some() {
+                break;
+            }
+            if len == size {
+                return Err(corrupt("truncated or inconsistent table"));
+            }
+            len = (2 * len).min(size);
+        }
+        table.header.truncate(table.items[0][0].sparse_index);
+        table.header.shrink_to_fit();
+        Ok(table)
+    }
+
+    /// `len` bytes of the file from `at`, fewer at its end. `None` past the end or if the read
+    /// fails.
+    fn read(&self, at: usize, len: usize) -> Option<Vec<u8>> {
+        let end = at.checked_add(len)?.min(self.size);
+        if at >= end {
+            return None;
+        }
+        let mut buf = vec![0; end - at];
+        read_exact_at(&self.file, &mut buf, at as u64).ok()?;
+        Some(buf)
+    }
+
+    fn sides(&self) -> usize {
+        if self.kind == TableKind::Wdl && !self.info.is_symmetric() { 2 } else { 1 }
+    }
+
+    /// Reads the header and the layout of every slice, `None` if the file is inconsistent or
+    /// the header read so far stops short.
+    fn parse(&mut self) -> Option<()> {
+        let sides = self.sides();
+        let files = if self.info.has_pawns { 4 } else { 1 };
+        let both_pawns = self.info.has_pawns && self.info.pawn_count[1] > 0;
+        let bytes = std::mem::take(&mut self.header);
+        let mut at = 5;
+
+        self.items = vec![vec![PairsData::default(); files]; sides];
+        for file in 0..files {
+            let first = read_u8(&bytes, at)?;
+            let second = if both_pawns { read_u8(&bytes, at + 1)? } else { 0xFF };
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            at += 1 + both_pawns as usize;
+
+            for k in 0..self.info.piece_count {
+                let byte = read_u8(&bytes, at)? as u8;
+                for side in 0..sides {
+                    self.items[side][file].pieces[k] = if side == 0 { byte & 0xF } else { byte >> 4 };
+                }
+                at += 1;
+            }
+            for (side, order) in order.iter().enumerate().take(sides) {
+                self.set_groups(side, file, *order)?;
+            }
+        }
+        at += at & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                at = Self::set_sizes(&bytes, &mut self.items[side][file], at)?;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            self.map = at;
+            for file in 0..files {
+                let d = &mut self.items[0][file];
+                if d.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if d.flags & FLAG_WIDE != 0 {
+                    at += at & 1;
+                    for i in 0..4 {
+                        d.map_idx[i] = (at - self.map) / 2 + 1;
+                        at += 2 * read_u16(&bytes, at)? + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        d.map_idx[i] = at - self.map + 1;
+                        at += read_u8(&bytes, at)? + 1;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        // The header ends here, the rest is read as positions are probed
+        if at > bytes.len() {
+            return None;
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side][file];
+                d.sparse_index = at;
+                at = at.checked_add(d.sparse_index_size.checked_mul(6)?)?;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side][file];
+                d.block_length = at;
+                at = at.checked_add(d.block_length_size.checked_mul(2)?)?;
+            }
+        }
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side][file];
+                at = at.checked_add(0x3F)? & !0x3F;
+                d.data = at;
+                at = at.checked_add(d.blocks_num.checked_mul(d.block_size)?)?;
+            }
+        }
+
+        self.header = bytes;
+        (at <= self.size).then_some(())
+    }
+
+    /// Splits the pieces into groups encoded together and computes each group's index factor.
+    /// The leading group is the leading pawns, else three unique pieces (or the two kings);
+    /// the others hold equal pieces. The table's `order` decides which group is the most
+    /// significant.
+    fn set_groups(&mut self, side: usize, file: usize, order: [usize; 2]) -> Option<()> {
+        let info = &self.info;
+        let e = encoding();
+        let d = &mut self.items[side][file];
+
+        let mut n = 0;
+        let mut first_len: i32 = if info.has_pawns { 0 } else if info.has_unique_pieces { 3 } else { 2 };
+        d.group_len[0] = 1;
+        for i in 1..info.piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        let both_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+        let mut idx = 1;
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                d.group_idx[0] = idx;
+                idx *= if info.has_pawns {
+                    e.lead_pawns_size.get(d.group_len[0])?[file]
+                } else if info.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                d.group_idx[1] = idx;
+                idx *= e.binomial.get(d.group_len[1])?[48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= e.binomial.get(d.group_len[next])?[free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        d.group_idx[n] = idx;
+        Some(())
+    }
+
+    /// Reads the block layout and Huffman code of one slice, returns the offset after it.
+    fn set_sizes(bytes: &[u8], d: &mut PairsData, mut at: usize) -> Option<usize> {
+        d.flags = read_u8(bytes, at)? as u8;
+        at += 1;
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            d.min_sym_len = read_u8(bytes, at)? as u8;
+            return Some(at + 1);
+        }
+
+        let groups = d.group_len.iter().position(|&len| len == 0)?;
+        let table_size = d.group_idx[groups];
+
+        d.block_size = 1_usize.checked_shl(read_u8(bytes, at)? as u32)?;
+        d.span = 1_u64.checked_shl(read_u8(bytes, at + 1)? as u32)?;
+        d.sparse_index_size = table_size.div_ceil(d.span) as usize;
+        let padding = read_u8(bytes, at + 2)?;
+        d.blocks_num = read_u32(bytes, at + 3)?;
+        // Padded so that the sparse index never points out of range
+        d.block_length_size = d.blocks_num + padding;
+        d.max_sym_len = read_u8(bytes, at + 7)? as u8;
+        d.min_sym_len = read_u8(bytes, at + 8)? as u8;
+        at += 9;
+        d.lowest_sym = at;
+
+        // Canonical Huffman code: longer symbols have lower values. base64[l] is the lowest
+        // code of length min_sym_len + l, left aligned in 64 bits.
+        if d.min_sym_len == 0 || d.max_sym_len > 64 {
+            return None;
+        }
+        let lengths = d.max_sym_len.checked_sub(d.min_sym_len)? as usize + 1;
+        d.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16(bytes, d.lowest_sym + 2 * i)? as u64;
+            let next_lowest = read_u16(bytes, d.lowest_sym + 2 * (i + 1))? as u64;
+            d.base64[i] = (d.base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest)) / 2;
+        }
+        for (i, base) in d.base64.iter_mut().enumerate() {
+            *base = base.checked_shl(64 - i as u32 - d.min_sym_len as u32).unwrap_or(0);
+        }
+        at += lengths * 2;
+
+        let symbols = read_u16(bytes, at)?;
+        at += 2;
+        d.btree = at;
+        d.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                d.symlen[sym] = Self::set_symlen(bytes, d, sym, &mut visited)?;
+            }
+        }
+        Some(at + symbols * 3 + (symbols & 1))
+    }
+
+    fn btree_left(bytes: &[u8], d: &PairsData, sym: usize) -> Option<usize> {
+        let at = d.btree + 3 * sym;
+        Some(((read_u8(bytes, at + 1)? & 0xF) << 8) | read_u8(bytes, at)?)
+    }
+
+    fn btree_right(bytes: &[u8], d: &PairsData, sym: usize) -> Option<usize> {
+        let at = d.btree + 3 * sym;
+        Some((read_u8(bytes, at + 2)? << 4) | (read_u8(bytes, at + 1)? >> 4))
+    }
+
+    /// Number of values (minus one) a symbol expands to; leaves have a right child of 0xFFF.
+    fn set_symlen(bytes: &[u8], d: &mut PairsData, sym: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[sym] = true;
+        let right = Self::btree_right(bytes, d, sym)?;
+        if right == 0xFFF {
+            return Some(0);
+        }
+        let left = Self::btree_left(bytes, d, sym)?;
+        for child in [left, right] {
+            if !*visited.get(child)? {
+                d.symlen[child] = Self::set_symlen(bytes, d, child, visited)?;
+            }
+        }
+        Some(d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1))
+    }
+
+    /// Value stored at `idx` of a slice, `None` if the slice does not decode.
+    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> Option<i32> {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as i32);
+        }
+        let header = &self.header[..];
+
+        // The sparse index entry k describes value k * span + span / 2: its block and
+        // offset inside the block. Walk to the block holding idx from there.
+        let k = (idx / d.span) as usize;
+        if k >= d.sparse_index_size {
+            return None;
+        }
+        let entry = self.read(d.sparse_index + 6 * k, 6)?;
+        let mut block = read_u32(&entry, 0)?;
+        let mut offset = read_u16(&entry, 4)? as i64;
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+        let block_length = |block: usize| {
+            if block >= d.block_length_size {
+                return None;
+            }
+            read_u16(&self.read(d.block_length + 2 * block, 2)?, 0).map(|length| length as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        loop {
+            let length = block_length(block)?;
+            if offset <= length {
+                break;
+            }
+            offset -= length + 1;
+            block += 1;
+        }
+        if block >= d.blocks_num {
+            return None;
+        }
+
+        // Decode symbols from the start of the block until the one covering offset. The
+        // decoder reads ahead in 32 bit steps, so take a little more than the block.
+        let bytes = self.read(d.data + block * d.block_size, d.block_size + 8)?;
+        let mut at = 0;
+        let mut buf64 = (read_be_u32(&bytes, at)? << 32) | read_be_u32(&bytes, at + 4)?;
+        at += 8;
+        let mut buf64_size = 64_usize;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buf64 < *d.base64.get(len)? {
+                len += 1;
+            }
+            sym = ((buf64 - d.base64[len]) >> (64 - len - d.min_sym_len as usize)) as usize;
+            sym += read_u16(header, d.lowest_sym + 2 * len)?;
+
+            let sym_len = *d.symlen.get(sym)? as i64;
+            if offset < sym_len + 1 {
+                break;
+            }
+            offset -= sym_len + 1;
+            len += d.min_sym_len as usize;
+            buf64 = buf64.checked_shl(len as u32).unwrap_or(0);
+            buf64_size = buf64_size.checked_sub(len)?;
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= read_be_u32(&bytes, at)? << (64 - buf64_size);
+                at += 4;
+            }
+        }
+
+        // Expand the pair tree down to the value. Children expand to fewer values than their
+        // parent, which also keeps a corrupt tree from looping.
+        while d.symlen[sym] != 0 {
+            let left = Self::btree_left(header, d, sym)?;
+            let left_len = *d.symlen.get(left)? as i64;
+            let child = if offset < left_len + 1 {
+                left
+            } else {
+                offset -= left_len + 1;
+                Self::btree_right(header, d, sym)?
+            };
+            if *d.symlen.get(child)? >= d.symlen[sym] {
+                return None;
+            }
+            sym = child;
+        }
+        Self::btree_left(header, d, sym).map(|value| value as i32)
+    }
+
+    /// Converts a stored value: WDL -2..2, DTZ plies (before the 1 ply correction of callers).
+    fn map_score(&self, file: usize, value: i32, wdl: i32) -> Option<i32> {
+        if self.kind == TableKind::Wdl {
+            return Some(value - 2);
+        }
+
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let d = &self.items[0][file];
+        let mut value = value as usize;
+        if d.flags & FLAG_MAPPED != 0 {
+            let idx = d.map_idx[WDL_MAP[(wdl + 2) as usize]];
+            value = if d.flags & FLAG_WIDE != 0 {
+                read_u16(&self.header, self.map + 2 * (idx + value))?
+            } else {
+                read_u8(&self.header, self.map + idx + value)?
+            };
+        }
+        let mut value = value as i32;
+
+        // Stored in moves unless the flags say plies
+        if (wdl == 2 && d.flags & FLAG_WIN_PLIES == 0) || (wdl == -2 && d.flags & FLAG_LOSS_PLIES == 0) || wdl == 1 || wdl == -1 {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+
+    /// Looks up `board`; `wdl` is the known result, only used to decode DTZ values.
+    /// The caller has checked that `board` has this table's material. `None` if the table
+    /// turns out to be corrupt.
+    pub fn probe(&self, board: &Board, material_key: u64, wdl: i32) -> Option<Lookup> {
+        let e = encoding();
+        let info = &self.info;
+
+        // Tables are built with the first side of the name as white. Positions with the colors
+        // the other way round are mirrored, as are symmetric positions with black to move.
+        let black_to_move = board.side_to_move == Color::Black;
+        let flip = (info.is_symmetric() && black_to_move) || material_key != info.key;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip ^ black_to_move) as usize;
+
+        let mut squares = [0_usize; TB_PIECES];
+        let mut pieces = [0_u8; TB_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+
+        if info.has_pawns {
+            let lead_color = if (self.items[0][0].pieces[0] ^ flip_color) & 8 == 0 { Color::White } else { Color::Black };
+            lead_pawns = board.pieces[PieceType::Pawn as usize][lead_color as usize];
+            let mut pawns = lead_pawns;
+            while pawns != 0 {
+                squares[size] = pawns.trailing_zeros() as usize ^ flip_squares;
+                pawns &= pawns - 1;
+                size += 1;
+            }
+            let lead = (0..size).max_by_key(|&i| e.map_pawns[squares[i]])?;
+            squares.swap(0, lead);
+            file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+        let lead_count = size;
+
+        let d = &self.items[if self.sides() == 2 { stm } else { 0 }][file];
+        let one_sided = info.has_pawns || !info.is_symmetric();
+        if self.kind == TableKind::Dtz && one_sided && (d.flags & FLAG_STM) as usize != stm {
+            return Some(Lookup::ChangeStm);
+        }
+
+        let mut occupied = board.all_occupied ^ lead_pawns;
+        while occupied != 0 {
+            let sq = occupied.trailing_zeros() as usize;
+            occupied &= occupied - 1;
+            let color = if board.occupied[Color::White as usize] & (1 << sq) != 0 { 0 } else { 8 };
+            squares[size] = sq ^ flip_squares;
+            pieces[size] = (board.pieces_on_squares[sq].unwrap() as u8 + 1 + color) ^ flip_color;
+            size += 1;
+        }
+
+        // Same piece order as the table
+        for i in lead_count..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|&j| d.pieces[i] == pieces[j]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // Leading piece on files a-d
+        if squares[0] % 8 > 3 {
+            for sq in &mut squares[..size] {
+                *sq ^= 7;
+            }
+        }
+
+        let mut idx;
+        if info.has_pawns {
+            idx = e.lead_pawn_idx[lead_count][squares[0]];
+            squares[1..lead_count].sort_by_key(|&sq| e.map_pawns[sq]);
+            for (i, &sq) in squares.iter().enumerate().take(lead_count).skip(1) {
+                idx += e.binomial[i][e.map_pawns[sq]];
+            }
+        } else {
+            // Leading piece on ranks 1-4, then below the a1-h8 diagonal
+            if squares[0] / 8 > 3 {
+                for sq in &mut squares[..size] {
+                    *sq ^= 56;
+                }
+            }
+            for i in 0..d.group_len[0] {
+                let off = off_a1h8(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for sq in &mut squares[i..size] {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+            let rank = |sq: usize| (sq / 8) as u64;
+            if info.has_unique_pieces {
+                let adjust1 = (s1 > s0) as u64;
+                let adjust2 = (s2 > s0) as u64 + (s2 > s1) as u64;
+                idx = if off_a1h8(s0) != 0 {
+                    (e.map_a1d1d4[s0] as u64 * 63 + (s1 as u64 - adjust1)) * 62 + s2 as u64 - adjust2
+                } else if off_a1h8(s1) != 0 {
+                    (6 * 63 + rank(s0) * 28 + e.map_b1h1h7[s1]) * 62 + s2 as u64 - adjust2
+                } else if off_a1h8(s2) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + rank(s0) * 7 * 28 + (rank(s1) - adjust1) * 28 + e.map_b1h1h7[s2]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(s0) * 7 * 6 + (rank(s1) - adjust1) * 6 + (rank(s2) - adjust2)
+                };
+            } else {
+                idx = e.map_kk[e.map_a1d1d4[s0]][s1];
+            }
+        }
+
+        // Remaining groups, squares taken by earlier groups are skipped
+        idx *= d.group_idx[0];
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[group_start..group_start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|&&s| sq > s).count();
+                n += e.binomial[i + 1][(sq - adjust).checked_sub(if remaining_pawns { 8 } else { 0 })?];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start += len;
+            next += 1;
+        }
+
+        let value = self.decompress_pairs(d, idx)?;
+        Some(Lookup::Value(self.map_score(file, value, wdl)?))
+    }
+}
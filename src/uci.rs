@@ -1,5 +1,13 @@
 use std::io::{self, BufRead};
+use crate::book::BookSelection;
 use crate::engine::Engine;
+use crate::eval::pawns::clear_pawn_table;
+use crate::eval::trace_with_network;
+use crate::perft::divide;
+use std::thread;
+
+/// Perft table size per thread for `go perft`.
+const PERFT_HASH_MB: usize = 16;
 
 pub struct UCI {
     // TODO lifetime specifier
@@ -21,31 +29,54 @@ impl UCI {
                     "uci" => {
                         println!("id name {}", self.engine.name);
                         println!("id author {}", self.engine.author);
+                        println!("option name EvalParamsFile type string default <empty>");
+                        println!("option name NnueFile type string default <empty>");
+                        println!("option name SyzygyPath type string default <empty>");
+                        println!("option name DtmPath type string default <empty>");
+                        println!("option name OwnBook type check default false");
+                        println!("option name BookFile type string default <empty>");
+                        println!("option name BookBestMove type check default false");
+                        println!("option name UCI_Chess960 type check default false");
                         println!("uciok");
                     }
                     "isready" => {
                         println!("readyok");
                     }
+                    "setoption" => {
+                        let (name, value) = Self::parse_setoption(&tokens[1..]);
+                        self.set_option(&name, &value);
+                    }
                     "ucinewgame" => {
-                        // not yet implemented
+                        self.engine.tt.clear();
+                        clear_pawn_table();
                     }
-                    "position" => {
-                        if tokens.len() > 1 {
-                            if tokens[1] == "fen" {
-                                let fen = tokens[2..].join(" ");
-                                self.engine.setpos_fen(&fen);
-                            } else if tokens[1] == "startpos" {
-                                if tokens.len() > 2 && tokens[2] == "moves" {
-                                    self.engine.setpos_startpos(&tokens[3..]);
-                                } else {
-                                    self.engine.setpos_startpos(&[]);
-                                }
-                            }
+                    "position" if tokens.len() > 1 => {
+                        let end = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+                        let moves = tokens.get(end + 1..).unwrap_or(&[]);
+                        let result = match tokens[1] {
+                            "fen" => self.engine.setpos_fen(&tokens[2..end].join(" "), moves),
+                            "startpos" => self.engine.setpos_startpos(moves),
+                            _ => Ok(()),
+                        };
+                        if let Err(e) = result {
+                            println!("info string position rejected, keeping the previous one: {}", e);
+                        }
+                    }
+                    "go" if tokens.get(1) == Some(&"perft") => {
+                        match tokens.get(2).and_then(|d| d.parse().ok()) {
+                            Some(depth) => self.perft(depth),
+                            None => println!("info string go perft needs a depth"),
                         }
                     }
                     "go" => {
                         println!("bestmove {}", self.engine.search(1000_u64));
                     }
+                    "eval" => {
+                        // Debug command: print the evaluation breakdown of the current position,
+                        // and the network's score when one is loaded
+                        let engine = &self.engine;
+                        println!("{}", trace_with_network(&engine.board, &engine.eval_tables, engine.network.as_deref()));
+                    }
                     "stop" => {
                         // TODO stop search as soon as possible
                     }
@@ -60,13 +91,79 @@ impl UCI {
         }
     }
 
+    /// Prints the leaf count below every root move of the current position and their sum,
+    /// counted on all cores.
+    fn perft(&self, depth: u8) {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let counts = divide(&self.engine.board, depth, threads, PERFT_HASH_MB);
+        for (mv, nodes) in &counts {
+            println!("{}: {}", mv.to_uci(&self.engine.board), nodes);
+        }
+        let nodes: u64 = if depth == 0 { 1 } else { counts.iter().map(|(_, n)| n).sum() };
+        println!();
+        println!("Nodes searched: {}", nodes);
+    }
+
+    /// Splits the arguments of `setoption name <id> [value <x>]`; both may contain spaces.
+    fn parse_setoption(tokens: &[&str]) -> (String, String) {
+        let value_index = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+        let name_start = if tokens.first() == Some(&"name") { 1 } else { 0 };
+        let name = tokens[name_start.min(value_index)..value_index].join(" ");
+        let value = tokens.get(value_index + 1..).map(|v| v.join(" ")).unwrap_or_default();
+        (name, value)
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) {
+        match name.to_lowercase().as_str() {
+            "evalparamsfile" => match self.engine.load_eval_params_file(value) {
+                Ok(()) => println!("info string EvalParamsFile loaded: {}", if value.is_empty() { "<empty>" } else { value }),
+                Err(e) => println!("info string EvalParamsFile rejected, keeping previous parameters: {}", e),
+            },
+            "nnuefile" => match self.engine.load_nnue_file(value) {
+                Ok(()) if value.is_empty() || value == "<empty>" => println!("info string NnueFile cleared, using classical eval"),
+                Ok(()) => println!("info string NnueFile loaded: {}", value),
+                Err(e) => println!("info string NnueFile rejected, keeping previous network: {}", e),
+            },
+            "syzygypath" => match self.engine.set_syzygy_path(value) {
+                Ok(0) => println!("info string SyzygyPath cleared, tablebases disabled"),
+                Ok(count) => println!("info string SyzygyPath: found {} tables", count),
+                Err(e) => println!("info string SyzygyPath rejected, keeping previous tables: {}", e),
+            },
+            "dtmpath" => match self.engine.set_dtm_path(value) {
+                Ok(0) => println!("info string DtmPath cleared, DTM tables disabled"),
+                Ok(count) => println!("info string DtmPath: found {} tables", count),
+                Err(e) => println!("info string DtmPath rejected, keeping previous tables: {}", e),
+            },
+            "ownbook" => {
+                self.engine.own_book = value.eq_ignore_ascii_case("true");
+                if self.engine.own_book && self.engine.book.is_none() {
+                    println!("info string OwnBook enabled, but no BookFile is loaded");
+                }
+            }
+            "bookfile" => match self.engine.load_book(value) {
+                Ok(0) if value.is_empty() || value == "<empty>" => println!("info string BookFile cleared"),
+                Ok(count) => println!("info string BookFile loaded: {} entries", count),
+                Err(e) => println!("info string BookFile rejected, keeping previous book: {}", e),
+            },
+            "bookbestmove" => {
+                self.engine.book_selection = if value.eq_ignore_ascii_case("true") { BookSelection::Best } else { BookSelection::Weighted };
+            }
+            "uci_chess960" => {
+                self.engine.chess960 = value.eq_ignore_ascii_case("true");
+                self.engine.board.chess960 = self.engine.chess960;
+            }
+            _ => println!("info string unknown option: {}", name),
+        }
+    }
+
     pub fn send_info(
         depth: Option<usize>,
         nodes: Option<u64>,
         time: Option<u64>,
         nps: Option<u64>,
         score_cp: Option<i32>,
-        pv: Option<&str>
+        pv: Option<&str>,
+        tbhits: Option<u64>,
     ) {
         let mut output = String::from("info");
 
@@ -90,6 +187,10 @@ impl UCI {
             output.push_str(&format!(" time {}", t));
         }
 
+        if let Some(h) = tbhits {
+            output.push_str(&format!(" tbhits {}", h));
+        }
+
         if let Some(p) = pv {
             output.push_str(&format!(" pv {}", p));
         }
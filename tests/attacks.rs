@@ -0,0 +1,93 @@
+use chess_engine::board::{Board, Color, PieceType};
+use chess_engine::epd::Epd;
+use chess_engine::movegen::attacks::{between, line};
+use chess_engine::movegen::legal_check::is_current_king_attacked;
+
+/// Bitboard of squares given by name.
+fn squares(names: &[&str]) -> u64 {
+    names.iter().map(|name| {
+        let bytes = name.as_bytes();
+        1u64 << ((bytes[1] - b'1') * 8 + (bytes[0] - b'a'))
+    }).fold(0, |bb, square| bb | square)
+}
+
+fn square(name: &str) -> usize {
+    squares(&[name]).trailing_zeros() as usize
+}
+
+#[test]
+fn test_attackers_and_attacks_from() {
+    let board = Board::from_fen("4k3/8/2n5/8/3P4/2B2q2/1N6/4K2R w K - 0 1");
+    let occupied = board.all_occupied;
+    assert_eq!(board.attackers_to(square("e5"), occupied), squares(&["d4", "c6"]));
+    assert_eq!(board.attackers_to(square("d1"), occupied), squares(&["e1", "f3", "b2"]));
+    // Removing the bishop lets the queen through to a3
+    assert_eq!(board.attackers_to(square("a3"), occupied), 0);
+    assert_eq!(board.attackers_to(square("a3"), occupied ^ squares(&["c3"])), squares(&["f3"]));
+
+    assert_eq!(board.attacks_from(PieceType::Pawn, square("d4"), occupied), squares(&["c5", "e5"]));
+    assert_eq!(board.attacks_from(PieceType::Knight, square("c6"), occupied).count_ones(), 8);
+    assert_eq!(board.attacks_from(PieceType::Rook, square("h1"), occupied), squares(&["g1", "f1", "e1", "h2", "h3", "h4", "h5", "h6", "h7", "h8"]));
+    // Black pawns attack downwards, whoever is to move
+    let black_pawn = Board::from_fen("4k3/8/8/3p4/8/8/8/4K3 w - - 0 1");
+    assert_eq!(black_pawn.attacks_from(PieceType::Pawn, square("d5"), black_pawn.all_occupied), squares(&["c4", "e4"]));
+
+    assert_eq!(between(square("a1"), square("d4")), squares(&["b2", "c3"]));
+    assert_eq!(between(square("a1"), square("b3")), 0);
+    assert_eq!(line(square("b2"), square("c3")).count_ones(), 8);
+}
+
+#[test]
+fn test_checkers_and_pinned() {
+    let board = Board::from_fen("4k3/8/8/8/1b6/8/3N4/r3K2R w K - 0 1");
+    assert_eq!(board.checkers(), squares(&["a1"]));
+    assert_eq!(board.pinned(Color::White), squares(&["d2"]));
+    assert_eq!(board.pinned(Color::Black), 0);
+
+    // Double check, and a pinned piece of the side not to move
+    let board = Board::from_fen("4k3/4r3/8/8/8/3n4/8/4K3 w - - 0 1");
+    assert_eq!(board.checkers(), squares(&["e7", "d3"]));
+    let board = Board::from_fen("4k3/4r3/8/8/3n4/8/4Q3/4K3 b - - 0 1");
+    assert_eq!(board.pinned(Color::Black), squares(&["e7"]));
+    assert_eq!(board.king_blockers(Color::White), squares(&["e2"]), "Blockers of either color count");
+    assert_eq!(board.pinned(Color::White), squares(&["e2"]));
+    assert_eq!(board.checkers(), 0);
+}
+
+/// `gives_check` agrees with playing the move for every move of the perft suite, two plies deep.
+fn check_gives_check(board: &mut Board, depth: u8) {
+    for &mv in board.legal_moves().iter() {
+        let expected = {
+            let mut after = *board;
+            after.make_move(mv);
+            is_current_king_attacked(&after)
+        };
+        assert_eq!(board.gives_check(mv), expected, "{} in {}", mv.to_algebraic(), board.to_fen());
+        if depth > 0 {
+            let undo = board.make_move(mv);
+            check_gives_check(board, depth - 1);
+            board.undo_move(undo);
+        }
+    }
+}
+
+#[test]
+fn test_gives_check() {
+    for suite in [
+        concat!(env!("CARGO_MANIFEST_DIR"), "/helper_scripts/perft_suite.epd"),
+        concat!(env!("CARGO_MANIFEST_DIR"), "/helper_scripts/chess960_perft.epd"),
+    ] {
+        for epd in Epd::load(suite).unwrap() {
+            check_gives_check(&mut epd.board(), 2);
+        }
+    }
+    // Castling checks with the rook, en passant by opening a rank
+    for (fen, uci) in [("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1"), ("8/8/8/k2pP2R/8/8/8/4K3 w - d6 0 1", "e5d6")] {
+        let board = Board::from_fen(fen);
+        assert!(board.gives_check(board.parse_uci_move(uci).unwrap()), "{}", uci);
+    }
+    // Chess960 castling with the king staying on g1
+    let mut board = Board::from_fen("5k2/8/8/8/8/8/8/6KR w K - 0 1");
+    board.chess960 = true;
+    assert!(board.gives_check(board.parse_uci_move("g1h1").unwrap()));
+}
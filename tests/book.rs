@@ -0,0 +1,154 @@
+use chess_engine::board::Board;
+use chess_engine::book::builder::{BookBuilder, MoveStats};
+use chess_engine::book::{decode_move, encode_move, polyglot_key, BookEntry, BookError, BookSelection, PolyglotBook};
+use chess_engine::datagen::GameResult;
+use chess_engine::r#move::Move;
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+fn play(moves: &[&str]) -> Board {
+    let mut board = Board::from_fen(START_FEN);
+    for mv in moves {
+        let mv = Move::from_algebraic(mv, &board);
+        board.make_move(mv);
+    }
+    board
+}
+
+fn square(name: &str) -> u16 {
+    let bytes = name.as_bytes();
+    ((bytes[1] - b'1') * 8 + bytes[0] - b'a') as u16
+}
+
+fn raw(from: &str, to: &str) -> u16 {
+    square(from) << 6 | square(to)
+}
+
+#[test]
+fn test_polyglot_keys() {
+    // The reference keys of the Polyglot format description
+    let cases: [(&[&str], u64); 9] = [
+        (&[], 0x463b96181691fc9c),
+        (&["e2e4"], 0x823c9b50fd114196),
+        (&["e2e4", "d7d5"], 0x0756b94461c50fb0),
+        (&["e2e4", "d7d5", "e4e5"], 0x662fafb965db29d4),
+        (&["e2e4", "d7d5", "e4e5", "f7f5"], 0x22a48b5a8e47ff78),
+        (&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"], 0x652a607ca3f242c1),
+        (&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"], 0x00fdd303c946bdd9),
+        (&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"], 0x3c8123ea7b067637),
+        (&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"], 0x5c3f9b829b279560),
+    ];
+    for (moves, expected) in cases {
+        assert_eq!(polyglot_key(&play(moves)), expected, "Key after {:?}", moves);
+    }
+}
+
+#[test]
+fn test_move_translation() {
+    let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    let short = decode_move(raw("e1", "h1"), &mut board).unwrap();
+    let long = decode_move(raw("e1", "a1"), &mut board).unwrap();
+    assert_eq!((short.to_algebraic().as_str(), long.to_algebraic().as_str()), ("e1g1", "e1c1"));
+    assert_eq!(short, Move::from_algebraic("e1g1", &board));
+    assert_eq!((encode_move(short, &board), encode_move(long, &board)), (raw("e1", "h1"), raw("e1", "a1")));
+
+    // A rook move from the king's square is not castling
+    let mut rooks = Board::from_fen("4k3/8/8/8/8/8/8/R3K2R w - - 0 1");
+    assert_eq!(decode_move(raw("e1", "h1"), &mut rooks), None);
+
+    // Chess960: the king still lands on g1/c1 and the move is written with the castling rook's
+    // square, so it differs from the king's plain step to g1
+    let mut chess960 = Board::from_fen("1r3k1r/pppppppp/8/8/8/8/PPPPPPPP/1R3K1R w KQkq - 0 1");
+    let short = decode_move(raw("f1", "h1"), &mut chess960).unwrap();
+    let long = decode_move(raw("f1", "b1"), &mut chess960).unwrap();
+    let step = decode_move(raw("f1", "g1"), &mut chess960).unwrap();
+    assert_eq!((short.to_algebraic().as_str(), long.to_algebraic().as_str()), ("f1g1", "f1c1"));
+    assert_ne!(short, step);
+    let encoded = [short, long, step].map(|mv| encode_move(mv, &chess960));
+    assert_eq!(encoded, [raw("f1", "h1"), raw("f1", "b1"), raw("f1", "g1")]);
+    let mut no_rights = Board::from_fen("1r3k1r/pppppppp/8/8/8/8/PPPPPPPP/1R3K1R w - - 0 1");
+    assert_eq!(decode_move(raw("f1", "h1"), &mut no_rights), None);
+
+    let mut promotion = Board::from_fen("8/4P1k1/8/8/8/8/8/4K3 w - - 0 1");
+    let queen = decode_move(4 << 12 | raw("e7", "e8"), &mut promotion).unwrap();
+    let knight = decode_move(1 << 12 | raw("e7", "e8"), &mut promotion).unwrap();
+    assert_eq!((queen.to_algebraic().as_str(), knight.to_algebraic().as_str()), ("e7e8q", "e7e8n"));
+    assert_eq!(encode_move(knight, &promotion), 1 << 12 | raw("e7", "e8"));
+    assert_eq!(decode_move(raw("e7", "e8"), &mut promotion), None, "Promotion piece missing");
+}
+
+#[test]
+fn test_book_selection() {
+    let start = polyglot_key(&play(&[]));
+    let entry = |key, raw_move, weight| BookEntry { key, raw_move, weight, learn: 0 };
+    let book = PolyglotBook::from_entries(vec![
+        entry(start, raw("e2", "e4"), 10),
+        entry(start + 1, raw("a2", "a3"), 500),
+        entry(start, raw("e2", "e5"), 100), // Not legal
+        entry(start, raw("d2", "d4"), 30),
+        entry(start, raw("h2", "h3"), 0),
+        entry(start - 1, raw("b2", "b3"), 500),
+    ]);
+    assert_eq!(book.len(), 6);
+    assert_eq!(book.entries(start).len(), 4);
+    assert!(book.entries(12345).is_empty());
+
+    let mut board = play(&[]);
+    let moves: Vec<(String, u16)> = book.moves(&mut board).iter().map(|(mv, w)| (mv.to_algebraic(), *w)).collect();
+    assert_eq!(moves, [("d2d4".to_string(), 30), ("e2e4".to_string(), 10), ("h2h3".to_string(), 0)]);
+
+    let pick = |selection, random| book.pick(&mut play(&[]), selection, random).map(|mv| mv.to_algebraic());
+    assert_eq!(pick(BookSelection::Best, 0).as_deref(), Some("d2d4"));
+    assert_eq!(pick(BookSelection::Weighted, 0).as_deref(), Some("d2d4"));
+    assert_eq!(pick(BookSelection::Weighted, 29).as_deref(), Some("d2d4"));
+    assert_eq!(pick(BookSelection::Weighted, 30).as_deref(), Some("e2e4"));
+    assert_eq!(pick(BookSelection::Weighted, 79).as_deref(), Some("e2e4"));
+    assert_eq!(book.pick(&mut play(&["e2e4"]), BookSelection::Best, 0), None, "Out of book");
+
+    let bytes = book.to_bytes();
+    assert_eq!(bytes.len(), 6 * 16);
+    assert_eq!(PolyglotBook::from_bytes(&bytes), Ok(book));
+}
+
+#[test]
+fn test_book_errors() {
+    assert_eq!(PolyglotBook::from_bytes(&[0; 17]), Err(BookError::BadSize(17)));
+    assert!(matches!(PolyglotBook::load("/nonexistent/book.bin"), Err(BookError::Io(_))));
+    assert!(PolyglotBook::from_bytes(&[]).unwrap().is_empty());
+}
+
+#[test]
+fn test_book_builder() {
+    let start = play(&[]);
+    let line = |moves: &[&str]| {
+        let mut board = start;
+        moves
+            .iter()
+            .map(|s| {
+                let mv = Move::from_algebraic(s, &board);
+                board.make_move(mv);
+                mv
+            })
+            .collect::<Vec<Move>>()
+    };
+
+    let mut builder = BookBuilder::new(2);
+    builder.add_game(&start, &line(&["e2e4", "e7e5", "g1f3"]), GameResult::WhiteWin);
+    builder.add_game(&start, &line(&["e2e4", "c7c5"]), GameResult::Draw);
+    builder.add_game(&start, &line(&["e2e4", "c7c5"]), GameResult::BlackWin);
+    builder.add_game(&start, &line(&["d2d4", "d7d5"]), GameResult::BlackWin);
+    assert_eq!((builder.games(), builder.len()), (4, 5), "Moves past the max ply are ignored");
+
+    let e4 = Move::from_algebraic("e2e4", &start);
+    assert_eq!(builder.stats(&start, e4), Some(MoveStats { wins: 1, draws: 1, losses: 1 }));
+    let after_e4 = play(&["e2e4"]);
+    let c5 = Move::from_algebraic("c7c5", &after_e4);
+    assert_eq!(builder.stats(&after_e4, c5), Some(MoveStats { wins: 1, draws: 1, losses: 0 }));
+
+    // e4: 2 * 1 + 1 = 3, e5: 0 (never scored), c5: 3, d4: 0, d5: 2
+    let book = builder.build(1, 0.0);
+    assert_eq!(book.len(), 3);
+    assert_eq!(book.moves(&mut play(&[])).iter().map(|&(mv, w)| (mv.to_algebraic(), w)).collect::<Vec<_>>(), [("e2e4".to_string(), 3)]);
+    assert_eq!(builder.build(2, 0.0).len(), 2, "e4 and c5 were played twice");
+    assert_eq!(builder.build(1, 0.8).len(), 1, "Only d5 scored over 75%");
+}
@@ -0,0 +1,95 @@
+use chess_engine::board::Board;
+use chess_engine::engine::{Engine, PositionError};
+use chess_engine::epd::Epd;
+use chess_engine::parsing::MoveParseError;
+use chess_engine::perft::perft;
+
+const CHESS960_SUITE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/helper_scripts/chess960_perft.epd");
+
+#[test]
+fn test_chess960_perft() {
+    let suite = Epd::load(CHESS960_SUITE).unwrap();
+    assert_eq!(suite.len(), 6);
+    for epd in &suite {
+        // The D5 counts are left to the perft binary
+        for (depth, nodes) in epd.perft_counts().into_iter().filter(|&(depth, _)| depth <= 4) {
+            assert_eq!(perft(&mut epd.board(), depth), nodes, "{} D{}", epd.id().unwrap(), depth);
+        }
+    }
+}
+
+/// Taking back every move restores the board, and the hash kept up during the move is the one
+/// computed from scratch.
+fn check_make_undo(board: &mut Board, depth: u8) {
+    let before = *board;
+    for &mv in board.legal_moves().iter() {
+        let undo = board.make_move(mv);
+        let mut fresh = *board;
+        fresh.recalculate_hash();
+        assert_eq!(board.hash, fresh.hash, "{} in {}", mv.to_algebraic(), before.to_fen());
+        if depth > 0 {
+            check_make_undo(board, depth - 1);
+        }
+        board.undo_move(undo);
+        assert_eq!(*board, before, "{} in {}", mv.to_algebraic(), before.to_fen());
+    }
+}
+
+#[test]
+fn test_chess960_make_undo() {
+    for epd in Epd::load(CHESS960_SUITE).unwrap() {
+        check_make_undo(&mut epd.board(), 2);
+    }
+}
+
+fn play(fen: &str, uci: &str) -> Result<String, MoveParseError> {
+    let mut board = Board::from_fen(fen);
+    board.chess960 = true;
+    let mv = board.parse_uci_move(uci)?;
+    board.make_move(mv);
+    Ok(board.to_fen())
+}
+
+#[test]
+fn test_chess960_castling() {
+    // King already on its target, rook on the king's target, both pieces swapping sides
+    assert_eq!(play("4k3/8/8/8/8/8/8/6KR w K - 0 1", "g1h1"), Ok("4k3/8/8/8/8/8/8/5RK1 b - - 1 1".to_string()));
+    assert_eq!(play("4k3/8/8/8/8/8/8/4K1R1 w K - 0 1", "e1g1"), Ok("4k3/8/8/8/8/8/8/5RK1 b - - 1 1".to_string()));
+    assert_eq!(play("4k3/8/8/8/8/8/8/1R1K4 w Q - 0 1", "d1b1"), Ok("4k3/8/8/8/8/8/8/2KR4 b - - 1 1".to_string()));
+    assert_eq!(play("1rk5/8/8/8/8/8/8/4K3 b q - 0 1", "c8b8"), Ok("2kr4/8/8/8/8/8/8/4K3 w - - 1 2".to_string()));
+
+    // The rook leaving b1 would uncover the a1 rook on the king's target
+    assert_eq!(play("4k3/8/8/8/8/8/8/rR1K4 w Q - 0 1", "d1b1"), Err(MoveParseError::Illegal("d1b1".to_string())));
+    // The king may not cross an attacked square, nor any piece stand in the way of king or rook
+    assert_eq!(play("4kr2/8/8/8/8/8/8/1K5R w K - 0 1", "b1h1"), Err(MoveParseError::Illegal("b1h1".to_string())));
+    assert_eq!(play("4k3/8/8/8/8/8/8/1K3N1R w K - 0 1", "b1h1"), Err(MoveParseError::Illegal("b1h1".to_string())));
+    assert_eq!(play("4k3/8/8/8/8/8/8/RNK5 w Q - 0 1", "c1a1"), Err(MoveParseError::Illegal("c1a1".to_string())));
+
+    // Moving a castling rook ends its right only
+    let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+    assert_eq!(play(fen, "f1f2"), Ok("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1PRPP/BQ1BN1KR b Kkq - 3 9".to_string()));
+}
+
+#[test]
+fn test_chess960_notation() {
+    let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    let short = board.parse_uci_move("e1g1").unwrap();
+    assert_eq!(short.to_uci(&board), "e1g1");
+    assert_eq!(short.to_san(&board), "O-O");
+    assert_eq!(board.parse_uci_move("e1h1"), Err(MoveParseError::Illegal("e1h1".to_string())));
+
+    // With UCI_Chess960 castling is the king taking its own rook, also in standard positions
+    board.chess960 = true;
+    assert_eq!(board.parse_uci_move("e1h1"), Ok(short));
+    assert_eq!(short.to_uci(&board), "e1h1");
+    assert_eq!(board.parse_uci_move("e1a1").unwrap().to_san(&board), "O-O-O");
+    assert_eq!(board.parse_uci_move("e1g1"), Err(MoveParseError::Illegal("e1g1".to_string())));
+
+    let mut engine = Engine::with_hash("Yakari".to_string(), "EiSiMo".to_string(), 1);
+    let fen = "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9";
+    assert_eq!(engine.setpos_fen(fen, &["g1h1"]), Err(PositionError::Move(MoveParseError::Illegal("g1h1".to_string()))));
+    engine.chess960 = true;
+    engine.setpos_fen(fen, &["g1h1", "g8h8"]).unwrap();
+    assert_eq!(engine.board.to_fen(), "qnbnrrk1/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1RRK1 w - - 3 10");
+    assert!(engine.board.chess960);
+}
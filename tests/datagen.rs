@@ -0,0 +1,61 @@
+use chess_engine::board::Board;
+use chess_engine::datagen::{is_insufficient_material, GameResult, TrainingPosition, RECORD_SIZE};
+use chess_engine::engine::Engine;
+
+#[test]
+fn test_training_records() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 3 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq - 3 27",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 300",
+        "1rk1r1r1/pppppppp/8/8/8/8/PPPPPPPP/1RK1R1R1 w EQeq - 0 1",
+    ];
+    for (i, fen) in fens.iter().enumerate() {
+        let position = TrainingPosition {
+            board: Board::from_fen(fen),
+            score: -250 + 100 * i as i16,
+            result: [GameResult::BlackWin, GameResult::Draw, GameResult::WhiteWin][i % 3],
+        };
+        let bytes = position.to_bytes();
+        assert_eq!(bytes.len(), RECORD_SIZE);
+        assert_eq!(TrainingPosition::from_bytes(&bytes), Some(position), "Binary round trip: {}", fen);
+        assert!(position.to_text().starts_with(&format!("{} | ", fen)), "Text record: {}", fen);
+    }
+
+    let mut corrupt = TrainingPosition {
+        board: Board::from_fen(fens[0]),
+        score: 0,
+        result: GameResult::Draw,
+    }
+    .to_bytes();
+    corrupt[26] = 7;
+    assert_eq!(TrainingPosition::from_bytes(&corrupt), None, "Invalid result");
+    corrupt[26] = 1;
+    corrupt[29] = 20;
+    assert_eq!(TrainingPosition::from_bytes(&corrupt), None, "En passant square off the sixth rank");
+
+    let mut corrupt = TrainingPosition {
+        board: Board::from_fen(fens[3]),
+        score: 0,
+        result: GameResult::Draw,
+    }
+    .to_bytes();
+    corrupt[28] = 1;
+    assert_eq!(TrainingPosition::from_bytes(&corrupt), None, "Castling rights without king and rook");
+
+    assert!(is_insufficient_material(&Board::from_fen("8/8/4k3/8/8/3NK3/8/8 w - - 0 1")));
+    assert!(!is_insufficient_material(&Board::from_fen("8/8/4k3/8/8/3BK1B1/8/8 w - - 0 1")));
+    assert!(!is_insufficient_material(&Board::from_fen("8/8/4k3/8/8/4K3/4P3/8 w - - 0 1")));
+}
+
+#[test]
+fn test_fixed_node_search() {
+    let mut engine = Engine::with_hash("Yakari".to_string(), "EiSiMo".to_string(), 1);
+    engine.setpos_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", &[]).unwrap();
+    let (best_move, score) = engine.search_nodes(20_000);
+    assert_eq!(best_move.map(|mv| mv.to_algebraic()), Some("a1a8".to_string()), "Back rank mate");
+    assert!(score > 100_000, "Mate score");
+}
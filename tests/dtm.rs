@@ -0,0 +1,165 @@
+use chess_engine::board::{Board, Color};
+use chess_engine::dtm::generate::{dependencies, generate, generation_order};
+use chess_engine::dtm::{Dtm, DtmError, DtmTable, DtmTables, Material};
+use chess_engine::engine::Engine;
+use chess_engine::eval::kpk::probe_kpk;
+use chess_engine::movegen::legal_check::is_other_king_attacked;
+use std::env;
+use std::fs;
+use std::sync::Arc;
+
+fn solve(codes: &[&str]) -> DtmTables {
+    let mut tables = DtmTables::from_tables(Vec::new());
+    for code in codes {
+        tables.insert(generate(code, &tables).unwrap().0);
+    }
+    tables
+}
+
+#[test]
+fn test_materials() {
+    let material = Material::from_code("KRvKB").unwrap();
+    assert_eq!(material.size(), 10 * 64 * 64 * 64 * 2);
+    assert_eq!(Material::from_code("KPvK").unwrap().size(), 32 * 64 * 64 * 2);
+    for code in ["KRKB", "KRvB", "KRRvKB", "KXvK"] {
+        assert!(matches!(Material::from_code(code), Err(DtmError::InvalidCode(_))), "Invalid code: {}", code);
+    }
+
+    // Mirror images share an index
+    let a = material.index(&[0, 63, 7, 56], Color::White);
+    let b = material.index(&[7, 56, 0, 63], Color::White);
+    assert_eq!(a, b);
+    let (squares, side_to_move) = material.decode(a);
+    assert_eq!(material.index(&squares, side_to_move), a);
+
+    assert_eq!(dependencies("KPvK").unwrap(), ["KBvK", "KNvK", "KQvK", "KRvK"]);
+    assert_eq!(dependencies("KRvKP").unwrap(), ["KBvK", "KNvK", "KPvK", "KQvK", "KQvKR", "KRvK", "KRvKB", "KRvKN", "KRvKR"]);
+    let order = generation_order(&["KRvKP".to_string()]).unwrap();
+    assert!(order.iter().position(|c| c == "KQvKR") < order.iter().position(|c| c == "KRvKP"));
+    assert_eq!(order.last().map(String::as_str), Some("KRvKP"));
+}
+
+#[test]
+fn test_generate_mates() {
+    let tables = solve(&["KQvK", "KRvK"]);
+
+    let cases = [
+        ("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1", Dtm::Win(1)),
+        ("k5Q1/8/1K6/8/8/8/8/8 b - - 0 1", Dtm::Loss(0)),
+        ("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1", Dtm::Draw),
+        ("8/8/4k3/8/8/4K3/8/8 w - - 0 1", Dtm::Draw),
+        ("8/8/8/8/8/8/8/kR1K4 b - - 0 1", Dtm::Draw),
+        ("4k3/8/4K3/8/8/8/8/7R w - - 0 1", Dtm::Win(1)),
+    ];
+    for (fen, expected) in cases {
+        let mut board = Board::from_fen(fen);
+        let before = board;
+        assert_eq!(tables.probe(&mut board), Some(expected), "DTM: {}", fen);
+        assert_eq!(board, before, "Board restored: {}", fen);
+    }
+
+    // Black material probes the same table with the colours swapped
+    let mut black = Board::from_fen("8/8/8/8/8/1K6/8/kq6 w - - 0 1");
+    assert!(matches!(tables.probe(&mut black), Some(Dtm::Loss(_))));
+    assert_eq!(tables.probe(&mut Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1")), None, "Castling rights");
+    assert_eq!(tables.probe(&mut Board::from_fen("4k3/8/8/8/8/8/8/3BK3 w - - 0 1")), None, "Missing table");
+
+    // Longest mates: KQK in 10 moves, KRK in 16
+    let mut longest = Vec::new();
+    for code in ["KQvK", "KRvK"] {
+        let (_, stats) = generate(code, &tables).unwrap();
+        longest.push(stats.longest);
+    }
+    assert_eq!(longest, [20, 32]);
+
+    let mut board = Board::from_fen("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1");
+    let (mv, value) = tables.best_move(&mut board).unwrap();
+    assert_eq!((mv.to_algebraic().as_str(), value), ("g1g8", Dtm::Win(1)));
+
+    // Following the best moves mates in exactly the promised number of plies
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/K6R w - - 0 1");
+    let Some((_, Dtm::Win(plies))) = tables.best_move(&mut board) else { panic!("KRK is won") };
+    for _ in 0..plies {
+        let (mv, _) = tables.best_move(&mut board).unwrap();
+        board.make_move(mv);
+    }
+    assert_eq!(tables.probe(&mut board), Some(Dtm::Loss(0)));
+}
+
+#[test]
+fn test_generate_kpk() {
+    let tables = solve(&["KQvK", "KRvK", "KBvK", "KNvK", "KPvK"]);
+    let table_path = env::temp_dir().join(format!("yakari-dtm-kpk-{}.ykdtm", std::process::id()));
+
+    // The KPK bitbase agrees on every position
+    let material = Material::from_code("KPvK").unwrap();
+    let empty = Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1");
+    let mut checked = 0;
+    for index in 0..material.size() {
+        let (squares, side_to_move) = material.decode(index);
+        let squares = &squares[..3];
+        let occupied = squares.iter().fold(0_u64, |occ, &sq| occ | 1 << sq);
+        if occupied.count_ones() != 3 || squares[2] < 8 || squares[2] >= 56 || material.index(squares, side_to_move) != index {
+            continue;
+        }
+        let mut board = material.board(&empty, squares, side_to_move);
+        if is_other_king_attacked(&board) {
+            continue;
+        }
+        let dtm = tables.probe(&mut board).unwrap();
+        let pawn_side_wins = match side_to_move {
+            Color::White => matches!(dtm, Dtm::Win(_)),
+            Color::Black => matches!(dtm, Dtm::Loss(_)),
+        };
+        assert_eq!(probe_kpk(&board), Some(pawn_side_wins), "KPK index {}", index);
+        checked += 1;
+    }
+    assert!(checked > 100_000);
+
+    // Without KQvK the fastest win, the promotion, cannot be probed
+    let (table, _) = generate("KPvK", &tables).unwrap();
+    let mut board = Board::from_fen("8/4P3/8/8/8/k7/8/K7 w - - 0 1");
+    let (mv, _) = tables.best_move(&mut board).unwrap();
+    assert_eq!(mv.to_algebraic(), "e7e8q");
+    let pawn_only = DtmTables::from_tables(vec![table.clone()]);
+    assert!(matches!(pawn_only.probe(&mut board), Some(Dtm::Win(_))));
+    assert_eq!(pawn_only.best_move(&mut board), None);
+
+    // Round trip through a file
+    table.save(&table_path).unwrap();
+    let loaded = DtmTable::load(&table_path).unwrap();
+    assert_eq!(loaded, table);
+
+    let mut bytes = fs::read(&table_path).unwrap();
+    bytes.truncate(bytes.len() - 1);
+    assert!(matches!(DtmTable::from_bytes(&bytes), Err(DtmError::WrongSize { .. })));
+    bytes[0] = b'X';
+    assert_eq!(DtmTable::from_bytes(&bytes), Err(DtmError::BadMagic));
+    fs::remove_file(&table_path).unwrap();
+}
+
+#[test]
+fn test_open_errors() {
+    let dir = env::temp_dir().join(format!("yakari-dtm-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    assert!(matches!(DtmTables::open(dir.to_str().unwrap()), Err(DtmError::Io(_))), "Missing directory");
+
+    fs::create_dir_all(&dir).unwrap();
+    assert!(matches!(DtmTables::open(dir.to_str().unwrap()), Err(DtmError::NoTables(_))), "No tables");
+
+    // Broken files are found but never probed
+    fs::write(dir.join("KQvK.ykdtm"), b"YKDT").unwrap();
+    let tables = DtmTables::open(dir.to_str().unwrap()).unwrap();
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables.probe(&mut Board::from_fen("8/8/4k3/8/8/4K3/8/Q7 w - - 0 1")), None);
+
+    assert!(matches!(generate("KPvK", &tables), Err(DtmError::MissingTable(_))), "Promotion needs KQvK");
+
+    // Clearing DtmPath frees the old set
+    let mut engine = Engine::with_hash("Yakari".to_string(), "EiSiMo".to_string(), 1);
+    assert_eq!(engine.set_dtm_path(dir.to_str().unwrap()).unwrap(), 1);
+    let old = Arc::downgrade(engine.dtm_tables.as_ref().unwrap());
+    engine.set_dtm_path("<empty>").unwrap();
+    assert!(old.upgrade().is_none());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,117 @@
+use chess_engine::board::{Board, Color};
+use chess_engine::epd::{Epd, EpdError};
+use chess_engine::parsing::{FenError, SanError};
+use chess_engine::r#move::Move;
+
+const STS: &str = r#"1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - - bm f5; id "STS(v1.0) Undermine.001"; c0 "f5=100, Bf2=46, Bg4=23, fxg5=22, Bg7=19, Kh1=10, Be3=5, b3=1, h3=1, h4=1"; c7 "f5 Bf2 Bg4 fxg5 Bg7 Kh1 Be3 b3 h3 h4"; c8 "100 46 23 22 19 10 5 1 1 1"; c9 "f4f5 d4f2 f3g4 f4g5 d4g7 g1h1 d4e3 b2b3 h2h3 h2h4"; Ae "Stockish 15";"#;
+
+#[test]
+fn test_parse_epd() {
+    let epd = Epd::parse(STS).unwrap();
+    assert_eq!(epd.position, "1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - -");
+    assert_eq!(epd.operations.len(), 7);
+    assert_eq!(epd.id(), Some("STS(v1.0) Undermine.001"));
+    assert_eq!(epd.comment(8), Some("100 46 23 22 19 10 5 1 1 1"));
+    assert_eq!(epd.string("Ae"), Some("Stockish 15"));
+    assert_eq!(epd.get("bm"), Some(&["f5".to_string()][..]));
+    let board = epd.board();
+    assert_eq!(epd.best_moves(), Ok(vec![Move::from_algebraic("f4f5", &board)]));
+    assert_eq!(epd.avoid_moves(), Ok(vec![]));
+    assert_eq!(epd.to_string(), STS, "Written back unchanged");
+
+    let mate = Epd::parse("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8#; am Rd7 h3; dm 1; acd 12; ce 32000; hmvc 4; fmvn 30;").unwrap();
+    let board = mate.board();
+    assert_eq!((board.halfmove_clock, board.fullmove_number), (4, 30));
+    assert_eq!(mate.direct_mate(), Some(1));
+    assert_eq!((mate.depth(), mate.centipawns()), (Some(12), Some(32000)));
+    assert_eq!(mate.avoid_moves().unwrap().len(), 2);
+    assert_eq!(mate.id(), None);
+
+    // Operations without a final ';', quoted semicolons, and empty operations
+    let loose = Epd::parse("8/8/8/8/8/8/8/K6k b - - c0 \"a; b\"; noop;  id x").unwrap();
+    assert_eq!(loose.comment(0), Some("a; b"));
+    assert_eq!(loose.get("noop"), Some(&[][..]));
+    assert_eq!(loose.id(), Some("x"));
+    assert_eq!(loose.to_string(), "8/8/8/8/8/8/8/K6k b - - c0 \"a; b\"; noop; id \"x\";");
+}
+
+#[test]
+fn test_annotate_epd() {
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    let mut epd = Epd::from_board(&board);
+    assert_eq!(epd.position, "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -");
+
+    let line: Vec<Move> = {
+        let mut board = board;
+        ["f1b5", "a7a6", "b5a4"]
+            .iter()
+            .map(|s| {
+                let mv = Move::from_algebraic(s, &board);
+                board.make_move(mv);
+                mv
+            })
+            .collect()
+    };
+    epd.set_moves("bm", &line[..1]);
+    epd.set_pv(&line);
+    epd.set("acd", vec!["14".to_string()]);
+    epd.set("ce", vec!["35".to_string()]);
+    epd.set("acd", vec!["15".to_string()]);
+    assert_eq!(epd.to_string(), "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5; pv Bb5 a6 Ba4; acd 15; ce 35;");
+
+    let parsed = Epd::parse(&epd.to_string()).unwrap();
+    assert_eq!(parsed, epd);
+    assert_eq!(parsed.pv(), Ok(line));
+    epd.remove("pv");
+    assert_eq!(epd.get("pv"), None);
+}
+
+#[test]
+fn test_epd_errors() {
+    assert_eq!(Epd::parse("8/8/8/8/8/8/8/K6k w -"), Err(EpdError::BadPosition("8/8/8/8/8/8/8/K6k w -".to_string())));
+    assert_eq!(Epd::parse("8/8/8/8/8/8/8/K6k w - bm Kb1;"), Err(EpdError::Fen(FenError::InvalidEnPassant("bm".to_string()))));
+    assert_eq!(Epd::parse("8/8/8/8/8/8/8/K6K w - - bm Kb1;"), Err(EpdError::Fen(FenError::KingCount(Color::White, 2))));
+    assert!(matches!(Epd::parse("8/8/8/8/8/8/8/K6k w - - id \"open"), Err(EpdError::Unterminated(_))));
+    assert_eq!(Epd::parse("8/8/8/8/8/8/8/K6k w - - hmvc x;"), Err(EpdError::Fen(FenError::InvalidHalfmoveClock("x".to_string()))));
+    assert_eq!(Epd::parse("8/8/8/8/8/8/8/K6k w - - hmvc 300;"), Err(EpdError::Fen(FenError::InvalidHalfmoveClock("300".to_string()))));
+    assert_eq!(Epd::parse("8/8/8/8/8/8/8/K6k w - - fmvn -2;"), Err(EpdError::Fen(FenError::InvalidFullmoveNumber("-2".to_string()))));
+
+    let epd = Epd::parse("8/8/8/8/8/8/8/K6k w - - bm Kc1; pv Kb1 Kb2;").unwrap();
+    assert_eq!(epd.best_moves(), Err(EpdError::Move { opcode: "bm".to_string(), error: SanError::Illegal("Kc1".to_string()) }));
+    assert_eq!(epd.pv(), Err(EpdError::Move { opcode: "pv".to_string(), error: SanError::Illegal("Kb2".to_string()) }));
+
+    let error = Epd::parse_all("# suite\n\n8/8/8/8/8/8/8/K6k w - -\n8/8\n").unwrap_err();
+    assert_eq!(error.to_string(), "line 4: EPD needs a position of four FEN fields: '8/8'");
+    assert!(matches!(Epd::load("/nonexistent/suite.epd"), Err(EpdError::Io(_))));
+}
+
+#[test]
+fn test_sts_file() {
+    let suite = Epd::load(concat!(env!("CARGO_MANIFEST_DIR"), "/helper_scripts/STS1-STS15_LAN_v6.epd")).unwrap();
+    assert_eq!(suite.len(), 1188);
+    for epd in &suite {
+        let best = epd.best_moves().unwrap();
+        let coordinates = epd.comment(9).unwrap();
+        assert_eq!(best.len(), 1, "{}", epd);
+        assert!(coordinates.starts_with(&best[0].to_algebraic()), "{}", epd);
+        assert_eq!(Epd::parse(&epd.to_string()).as_ref(), Ok(epd));
+    }
+}
+
+#[test]
+fn test_points() {
+    let sts = Epd::parse(STS).unwrap();
+    let points = |epd: &Epd, uci: &str| epd.points(Move::from_algebraic(uci, &epd.board()));
+    assert_eq!(sts.max_points(), 100);
+    assert_eq!(points(&sts, "f4f5"), Ok(100));
+    assert_eq!(points(&sts, "d4f2"), Ok(46));
+    assert_eq!(points(&sts, "h2h4"), Ok(1));
+    assert_eq!(points(&sts, "g1f2"), Ok(0));
+
+    let plain = Epd::parse("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8#;").unwrap();
+    assert_eq!(plain.max_points(), 100);
+    assert_eq!(points(&plain, "d1d8"), Ok(100));
+    assert_eq!(points(&plain, "d1d7"), Ok(0));
+    let avoid = Epd::parse("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - am h3;").unwrap();
+    assert_eq!((points(&avoid, "h2h3"), points(&avoid, "h2h4")), (Ok(0), Ok(100)));
+}
@@ -0,0 +1,233 @@
+use chess_engine::board::{Board, Color, PieceType};
+use chess_engine::eval::{evaluate_board, trace};
+use chess_engine::eval::endgame::{material_key, material_key_from_code, KNOWN_WIN, SCALE_NORMAL};
+use chess_engine::eval::kpk::probe_kpk;
+use chess_engine::eval::params::{EvalParams, EvalTables, ParamsError};
+use chess_engine::eval::tune::{coefficients, linear_eval};
+use chess_engine::eval::king_safety::{evaluate_king_safety, king_attack_penalty, pawn_cover_penalty};
+use chess_engine::eval::pawns::{evaluate_pawn_structure, probe_pawn_structure, PawnEntry, PawnHashTable};
+use chess_engine::movegen::picker::MovePicker;
+
+#[test]
+fn test_king_safety() {
+    let params = &EvalParams::default();
+
+    // Intact shield vs. a king whose g- and h-pawn are gone
+    let sheltered = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+    let exposed = Board::from_fen("6k1/5ppp/8/8/8/8/5P2/6K1 w - - 0 1");
+    assert!(pawn_cover_penalty(&exposed, Color::White, params) > pawn_cover_penalty(&sheltered, Color::White, params), "Missing shield pawns");
+    assert_eq!(pawn_cover_penalty(&sheltered, Color::White, params), pawn_cover_penalty(&sheltered, Color::Black, params), "Symmetric shield");
+
+    // Advanced enemy pawns in front of the king
+    let stormed = Board::from_fen("6k1/5p2/8/8/6pp/8/5PPP/6K1 w - - 0 1");
+    assert!(pawn_cover_penalty(&stormed, Color::White, params) > pawn_cover_penalty(&sheltered, Color::White, params), "Pawn storm");
+
+    // A lone attacker does not count, queen + knight does
+    let lone_queen = Board::from_fen("6k1/5ppp/8/8/8/5q2/5PPP/6K1 w - - 0 1");
+    let queen_and_knight = Board::from_fen("6k1/5ppp/8/8/5n2/5q2/5PPP/6K1 w - - 0 1");
+    assert_eq!(king_attack_penalty(&lone_queen, Color::White, params), 0, "Single attacker");
+    assert!(king_attack_penalty(&queen_and_knight, Color::White, params) > 0, "Two attackers");
+
+    assert!(evaluate_king_safety(&exposed, Color::White, params) < evaluate_king_safety(&sheltered, Color::White, params), "Overall king safety");
+}
+
+#[test]
+fn test_pawn_structure() {
+    let tables = EvalTables::default();
+    let params = &tables.params;
+
+    // White: d5 stopped by e6, doubled and isolated h-pawns of which only h3 is passed
+    let board = Board::from_fen("4k3/8/4p3/3P4/8/7P/7P/4K3 w - - 0 1");
+    let entry = evaluate_pawn_structure(&board, params);
+    assert_eq!(entry.passed[Color::White as usize], 1 << 23, "White passed pawns");
+    assert_eq!(entry.passed[Color::Black as usize], 0, "Black passed pawns");
+    assert_eq!(probe_pawn_structure(&board, &tables), entry, "Cached entry");
+
+    // Sizes that are already a power of two are kept whole
+    let entries = 1024 * 1024 / std::mem::size_of::<PawnEntry>();
+    assert!(entries.is_power_of_two());
+    assert_eq!(PawnHashTable::new(1).size, entries);
+    assert_eq!(PawnHashTable::new(3).size, 2 * entries);
+    assert_eq!(PawnHashTable::new(0).size, 1);
+
+    // The pawn hash is maintained incrementally and ignores piece moves
+    let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let original = board;
+    let mut picker = MovePicker::new();
+    while let Some(mv) = picker.next(&board) {
+        let undo = board.make_move(mv);
+        let mut recalculated = board;
+        recalculated.recalculate_hash();
+        assert_eq!(board.pawn_hash, recalculated.pawn_hash, "Incremental pawn hash after {}", mv);
+        if board.pieces[PieceType::Pawn as usize] == original.pieces[PieceType::Pawn as usize] {
+            assert_eq!(board.pawn_hash, original.pawn_hash, "Pawn hash changed by {}", mv);
+        }
+        board.undo_move(undo);
+    }
+    assert_eq!(board, original);
+}
+
+#[test]
+fn test_incremental_psqt() {
+    let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    let original = board;
+    let mut picker = MovePicker::new();
+    while let Some(mv) = picker.next(&board) {
+        let undo = board.make_move(mv);
+        let mut recalculated = board;
+        recalculated.recalculate_psqt();
+        assert_eq!((board.psqt_mg, board.psqt_eg, board.phase), (recalculated.psqt_mg, recalculated.psqt_eg, recalculated.phase), "Incremental PSQT after {}", mv);
+        board.undo_move(undo);
+    }
+    assert_eq!(board, original);
+
+    // Mirrored positions evaluate to the negated score
+    let white = Board::from_fen("4k3/8/8/8/8/8/4P3/1N2K3 w - - 0 1");
+    let black = Board::from_fen("1n2k3/4p3/8/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(white.psqt_mg, -black.psqt_mg, "Mirrored MG");
+    assert_eq!(white.psqt_eg, -black.psqt_eg, "Mirrored EG");
+    assert_eq!(white.phase, 1, "Phase");
+}
+
+#[test]
+fn test_eval_trace() {
+    let tables = EvalTables::default();
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "6k1/5p2/8/8/5npp/5q2/5PPP/6K1 w - - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+    for fen in fens {
+        let board = Board::from_fen(fen);
+        let eval_trace = trace(&board, &tables);
+        assert_eq!(eval_trace.score, evaluate_board(&board, &tables), "Trace matches eval: {}", fen);
+        assert!(!eval_trace.to_string().is_empty());
+    }
+
+    let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let eval_trace = trace(&board, &tables);
+    assert_eq!(eval_trace.phase, 24, "Start position phase");
+    assert_eq!(eval_trace.score, 0, "Start position is symmetric");
+}
+
+#[test]
+fn test_tuning_coefficients() {
+    let tables = EvalTables::default();
+    let params = &tables.params;
+    assert_eq!(&EvalParams::from_vector(&params.to_vector()), params, "Vector round trip");
+
+    let weights: Vec<f64> = params.to_vector().iter().map(|&v| v as f64).collect();
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "6k1/5p2/8/8/5npp/5q2/5PPP/6K1 w - - 0 1",
+        "4k3/8/4p3/3P4/8/7P/7P/4K3 w - - 0 1",
+    ];
+    for fen in fens {
+        let board = Board::from_fen(fen);
+        let linear = linear_eval(&coefficients(&board, params), &weights);
+        // Only integer rounding in the real eval may differ
+        assert!((linear - evaluate_board(&board, &tables) as f64).abs() <= 2.0, "Linear eval {} vs {} for {}", linear, evaluate_board(&board, &tables), fen);
+    }
+
+    // Opposite bishops scale the endgame of the side pressing; with black pressing instead the
+    // pawnless rule applies, under the linear eval as in the real one
+    let board = Board::from_fen("4k3/8/4b3/8/8/2B5/PP6/4K3 w - - 0 1");
+    let board_coefficients = coefficients(&board, params);
+    assert_eq!(board_coefficients.eg_scale, [22.0 / 64.0, 0.0]);
+    let mut weak_pawns = params.clone();
+    weak_pawns.eg_material[PieceType::Pawn as usize] = -200;
+    let weak_weights: Vec<f64> = weak_pawns.to_vector().iter().map(|&v| v as f64).collect();
+    for (weights, tables) in [(&weights, &tables), (&weak_weights, &EvalTables::new(weak_pawns.clone()))] {
+        let linear = linear_eval(&board_coefficients, weights);
+        assert!((linear - evaluate_board(&board, tables) as f64).abs() <= 2.0, "Linear eval {} vs {}", linear, evaluate_board(&board, tables));
+    }
+}
+
+#[test]
+fn test_params_file() {
+    let mut params = EvalParams { doubled_penalty: (17, 31), ..Default::default() };
+    params.mg_psqt[PieceType::Knight as usize][27] = -42;
+    let text = params.to_text();
+    assert_eq!(EvalParams::from_text(&text), Ok(params), "Text round trip");
+
+    let body = text.lines().filter(|l| !l.starts_with("version")).collect::<Vec<_>>().join("\n");
+    assert_eq!(EvalParams::from_text(&body), Err(ParamsError::MissingVersion));
+    assert_eq!(EvalParams::from_text(&text.replace("version 1", "version 2")), Err(ParamsError::UnsupportedVersion("2".to_string())));
+    assert!(matches!(EvalParams::from_text(&format!("{}\nmobility_bonus 4\n", text)), Err(ParamsError::UnknownKey { .. })));
+    assert!(matches!(EvalParams::from_text(&format!("{}\nopen_file_penalty 4\n", text)), Err(ParamsError::DuplicateKey { .. })));
+    assert!(matches!(EvalParams::from_text(&text.replace("doubled_penalty 17 31", "doubled_penalty 17")), Err(ParamsError::MissingValues { expected: 2, found: 1, .. })));
+    assert!(matches!(EvalParams::from_text(&text.replace("doubled_penalty 17 31", "doubled_penalty 17 3.5")), Err(ParamsError::InvalidValue { .. })));
+    assert!(matches!(EvalParams::from_text(&text.replace("doubled_penalty 17 31", "doubled_penalty 17 99999")), Err(ParamsError::OutOfRange { .. })));
+    assert_eq!(EvalParams::from_text(&text.replace("doubled_penalty 17 31", "")), Err(ParamsError::MissingKey("doubled_penalty".to_string())));
+
+    // `Board` keeps the PSQT sums of the built-in tables, others are summed up from scratch
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1");
+    assert_eq!(EvalTables::default().psqt_sums(&board), (board.psqt_mg, board.psqt_eg));
+    let mut heavy_knights = EvalParams::default();
+    heavy_knights.mg_material[PieceType::Knight as usize] += 10;
+    let tables = EvalTables::new(heavy_knights);
+    assert_eq!(tables.psqt_sums(&board), (board.psqt_mg + 10, board.psqt_eg));
+    assert_eq!(trace(&board, &tables).score, evaluate_board(&board, &tables), "Trace with other tables");
+}
+
+#[test]
+fn test_endgames() {
+    let tables = EvalTables::default();
+    let eval = |fen: &str| evaluate_board(&Board::from_fen(fen), &tables);
+
+    for fen in ["8/8/4k3/8/8/4K3/8/8 w - - 0 1", "8/8/4k3/8/8/3NK3/8/8 w - - 0 1", "8/8/4k3/8/8/4K3/3b4/8 b - - 0 1", "8/8/4k3/8/8/2NNK3/8/8 w - - 0 1"] {
+        assert_eq!(eval(fen), 0, "Insufficient material: {}", fen);
+    }
+
+    let kbnk = Board::from_fen("8/8/4k3/8/8/3BK3/4N3/8 w - - 0 1");
+    assert_eq!(material_key(&kbnk), material_key_from_code("KBNK", Color::White));
+    assert_ne!(material_key(&kbnk), material_key_from_code("KBNK", Color::Black));
+    // d3 is a light square bishop, so the mate happens on a8 or h1
+    assert!(eval("k7/8/8/8/8/3BK3/4N3/8 w - - 0 1") > eval("7k/8/8/8/8/3BK3/4N3/8 w - - 0 1"), "KBNK wrong corner");
+    assert!(eval("k7/8/8/8/8/3BK3/4N3/8 w - - 0 1") > eval("8/8/3k4/8/8/3BK3/4N3/8 w - - 0 1"), "KBNK centre");
+
+    let krk_edge = eval("3k4/8/3K4/8/8/8/8/7R w - - 0 1");
+    let krk_centre = eval("8/8/8/3k4/8/8/1K6/7R w - - 0 1");
+    assert!(krk_centre > KNOWN_WIN && krk_edge > krk_centre, "KRK mop-up");
+    assert!(eval("8/8/8/3K4/8/8/1k6/7q w - - 0 1") < -KNOWN_WIN, "KQK for black");
+
+    // Light squared bishop cannot control h8
+    assert_eq!(eval("6k1/8/8/7P/8/8/8/4KB2 w - - 0 1"), 0, "Wrong colour bishop");
+    assert!(eval("6k1/8/8/7P/8/8/8/2B1K3 w - - 0 1") > 0, "Right colour bishop");
+    assert!(eval("8/8/8/2k4P/8/8/8/4KB2 w - - 0 1") > 0, "Defending king too far away");
+
+    let opposite_bishops = trace(&Board::from_fen("4k3/5p2/4b3/1p6/1P6/2B5/5P2/4K3 w - - 0 1"), &tables);
+    assert!(opposite_bishops.scale < SCALE_NORMAL, "Opposite coloured bishops");
+    let same_bishops = trace(&Board::from_fen("4k3/5p2/3b4/1p6/1P6/2B5/5P2/4K3 w - - 0 1"), &tables);
+    assert_eq!(same_bishops.scale, SCALE_NORMAL, "Same coloured bishops");
+    let krkb = trace(&Board::from_fen("4k3/8/3b4/8/8/8/8/R3K3 w - - 0 1"), &tables);
+    assert!(krkb.scale < SCALE_NORMAL / 4, "Pawnless rook vs bishop");
+    assert_eq!(krkb.score, evaluate_board(&Board::from_fen("4k3/8/3b4/8/8/8/8/R3K3 w - - 0 1"), &tables));
+}
+
+#[test]
+fn test_kpk_bitbase() {
+    let tables = EvalTables::default();
+    let probe = |fen: &str| probe_kpk(&Board::from_fen(fen));
+
+    // King on the sixth in front of the pawn wins with either side to move
+    assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), Some(true));
+    assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), Some(true));
+    assert_eq!(probe("3k4/8/3K4/3P4/8/8/8/8 b - - 0 1"), Some(true), "Mirrored files");
+    assert_eq!(probe("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1"), Some(true), "Black pawn");
+
+    // Pawn on the seventh: stalemate with black to move, Kf6-f7 wins with white to move
+    assert_eq!(probe("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Some(false));
+    assert_eq!(probe("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1"), Some(true));
+
+    assert_eq!(probe("k7/8/8/8/8/8/P7/K7 w - - 0 1"), Some(false), "Rook pawn");
+    assert_eq!(probe("8/8/8/8/8/8/3kP3/7K b - - 0 1"), Some(false), "Pawn is lost");
+    assert_eq!(probe("8/P7/8/8/8/8/8/K6k b - - 0 1"), Some(true), "Unstoppable pawn");
+    assert_eq!(probe("8/8/4k3/8/8/4K3/4P3/8 w - - 0 1"), Some(true), "Opposition");
+    assert_eq!(probe("8/8/4k3/8/8/4K3/4PP2/8 w - - 0 1"), None, "Not KPK");
+
+    assert!(evaluate_board(&Board::from_fen("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1"), &tables) < -KNOWN_WIN);
+    assert_eq!(evaluate_board(&Board::from_fen("k7/8/8/8/8/8/P7/K7 w - - 0 1"), &tables), 0);
+}
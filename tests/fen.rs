@@ -0,0 +1,101 @@
+use chess_engine::board::{Board, Color};
+use chess_engine::parsing::FenError;
+use chess_engine::square::Square;
+
+#[test]
+fn test_valid_fens() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        "8/8/8/8/8/8/8/K6k b - - 99 200",
+    ];
+    for fen in fens {
+        let board = Board::try_from_fen(fen).unwrap_or_else(|e| panic!("{}: {}", fen, e));
+        assert_eq!(board.to_fen(), fen);
+    }
+
+    // The move counters may be left out, and a fullmove number of 0 is read as 1
+    let board = Board::try_from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+    assert_eq!((board.side_to_move, board.halfmove_clock, board.fullmove_number), (Color::Black, 0, 1));
+    assert_eq!(Board::try_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0").unwrap().fullmove_number, 1);
+}
+
+#[test]
+fn test_fen_errors() {
+    let cases = [
+        ("", FenError::MissingFields(0)),
+        ("4k3/8/8/8/8/8/8/4K3 w -", FenError::MissingFields(3)),
+        ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 moves", FenError::TooManyFields(7)),
+        ("4k3/8/8/8/8/8/4K3 w - - 0 1", FenError::RankCount(7)),
+        ("4k3/8/8/8/8/8/8/8/4K3 w - - 0 1", FenError::RankCount(9)),
+        ("4k3/8/8/8/8/8/8/4K4 w - - 0 1", FenError::RankLength(1)),
+        ("4k2/8/8/8/8/8/8/4K3 w - - 0 1", FenError::RankLength(8)),
+        ("4k3/8/8/8/8/8/8/4K2R1 w - - 0 1", FenError::RankLength(1)),
+        ("4k3/8/8/8/8/8/8/4K2X w - - 0 1", FenError::InvalidPiece('X')),
+        ("4k3/8/8/8/8/8/8/4K02 w - - 0 1", FenError::InvalidPiece('0')),
+        ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenError::InvalidSideToMove("x".to_string())),
+        ("4k3/8/8/8/8/8/8/4K3 w KK - 0 1", FenError::InvalidCastling("KK".to_string())),
+        ("4k3/8/8/8/8/8/8/4K3 w Kx - 0 1", FenError::InvalidCastling("Kx".to_string())),
+        ("4k3/8/8/8/8/8/8/R3K2R w KH - 0 1", FenError::InvalidCastling("KH".to_string())),
+        ("4k3/8/8/8/8/8/8/4K3 w - e4 0 1", FenError::InvalidEnPassant("e4".to_string())),
+        ("4k3/8/8/8/8/8/8/4K3 w - e3 0 1", FenError::InvalidEnPassant("e3".to_string())),
+        ("4k3/8/8/8/8/8/8/4K3 w - i6 0 1", FenError::InvalidEnPassant("i6".to_string())),
+        ("4k3/8/8/8/8/8/8/4K3 w - - x 1", FenError::InvalidHalfmoveClock("x".to_string())),
+        ("4k3/8/8/8/8/8/8/4K3 w - - 0 -1", FenError::InvalidFullmoveNumber("-1".to_string())),
+        ("8/8/8/8/8/8/8/4K3 w - - 0 1", FenError::KingCount(Color::Black, 0)),
+        ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", FenError::KingCount(Color::White, 2)),
+        ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", FenError::PawnOnBackRank("a1".to_string())),
+        ("4k2p/8/8/8/8/8/8/4K3 w - - 0 1", FenError::PawnOnBackRank("h8".to_string())),
+        ("4k3/8/8/8/8/8/8/4K3 w K - 0 1", FenError::CastlingWithoutPieces('K')),
+        ("r3k3/8/8/8/8/8/8/R3K2R w KQkq - 0 1", FenError::CastlingWithoutPieces('k')),
+        ("4k3/8/8/8/8/8/8/3K3R w Q - 0 1", FenError::CastlingWithoutPieces('Q')),
+        ("4k3/8/8/8/8/8/8/4K3 w A - 0 1", FenError::CastlingWithoutPieces('Q')),
+        ("4k3/8/8/8/8/8/8/R3K2R w B - 0 1", FenError::CastlingWithoutPieces('Q')),
+        ("4k3/8/8/8/8/8/4K3/R6R w K - 0 1", FenError::CastlingWithoutPieces('K')),
+        ("4k3/8/8/8/8/8/8/4K3 w - e6 0 1", FenError::ImplausibleEnPassant("e6".to_string())),
+        ("4k3/4p3/8/4p3/8/8/8/4K3 w - e6 0 1", FenError::ImplausibleEnPassant("e6".to_string())),
+        ("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1", FenError::InvalidEnPassant("e3".to_string())),
+        ("4k3/8/8/8/4P3/8/4P3/4K3 b - e3 0 1", FenError::ImplausibleEnPassant("e3".to_string())),
+        ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", FenError::OpponentInCheck),
+        ("4k3/8/8/8/8/8/3p4/4K3 b - - 0 1", FenError::OpponentInCheck),
+    ];
+    for (fen, error) in cases {
+        assert_eq!(Board::try_from_fen(fen).map(|b| b.to_fen()), Err(error), "{:?}", fen);
+    }
+    assert_eq!(
+        Board::try_from_fen("4k3/8/8/8/8/8/8/4K3 w - e6 0 1").unwrap_err().to_string(),
+        "no double pawn push could have passed e6"
+    );
+}
+
+#[test]
+fn test_chess960_fens() {
+    // Shredder-FEN rook files are written back as KQkq where the rook is the outermost one
+    let board = Board::try_from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9").unwrap();
+    assert_eq!(board.castling_rooks, [Square::H1, Square::F1, Square::H8, Square::F8]);
+    assert_eq!(board.to_fen(), "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9");
+    assert_eq!(Board::try_from_fen(&board.to_fen()).unwrap(), board);
+
+    // An inner rook keeps its file, X-FEN mixes both forms
+    let fens = [
+        ("4k3/8/8/8/8/8/8/R1RK4 w C - 0 1", [Square::H1, Square::C1, Square::H8, Square::A8]),
+        ("r1rk3r/8/8/8/8/8/8/R1RK4 w Qkc - 0 1", [Square::H1, Square::A1, Square::H8, Square::C8]),
+        ("1r4kr/8/8/8/8/8/8/1R4KR w Bh - 0 1", [Square::H1, Square::B1, Square::H8, Square::A8]),
+    ];
+    for (fen, rooks) in fens {
+        let board = Board::try_from_fen(fen).unwrap_or_else(|e| panic!("{}: {}", fen, e));
+        assert_eq!(board.castling_rooks, rooks, "{}", fen);
+    }
+    assert_eq!(Board::from_fen(fens[0].0).to_fen(), fens[0].0);
+    assert_eq!(Board::from_fen(fens[2].0).to_fen(), "1r4kr/8/8/8/8/8/8/1R4KR w Qk - 0 1");
+}
+
+#[test]
+fn test_from_fen_syntax_only() {
+    // Positions for internal use need not be legal, but must be well formed
+    let empty = Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1");
+    assert_eq!(empty.all_occupied, 0);
+    assert!(std::panic::catch_unwind(|| Board::from_fen("8/8/8/8/8/8/8/8 w - e9 0 1")).is_err());
+}
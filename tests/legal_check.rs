@@ -0,0 +1,20 @@
+use chess_engine::board::Board;
+use chess_engine::movegen::legal_check::{is_current_king_attacked, is_other_king_attacked};
+
+#[test]
+fn test_king_attacked() {
+    // (fen, side to move in check, other king attacked)
+    let positions = [
+        ("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1", true, false),
+        ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", false, true),
+        ("8/8/8/3k4/4P3/8/8/K7 b - - 0 1", true, false),
+        ("8/8/8/3k4/4P3/8/8/K7 w - - 0 1", false, true),
+        ("rnbqkbnr/pppp1ppp/8/4p2Q/4P3/8/PPPP1PPP/RNB1KBNR b KQkq - 1 2", false, false),
+        ("rnb1kbnr/pppp1ppp/8/4p3/4P2q/5P2/PPPP2PP/RNBQKBNR w KQkq - 1 3", true, false),
+    ];
+    for (fen, in_check, other_attacked) in positions {
+        let board = Board::from_fen(fen);
+        assert_eq!(is_current_king_attacked(&board), in_check, "In check: {}", fen);
+        assert_eq!(is_other_king_attacked(&board), other_attacked, "Other king attacked: {}", fen);
+    }
+}
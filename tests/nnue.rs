@@ -0,0 +1,129 @@
+use chess_engine::board::{Board, Color};
+use chess_engine::eval::nnue::{Accumulator, AccumulatorStack, Network, NnueError, HIDDEN, INPUTS, QA, QB, SCALE};
+use chess_engine::eval::params::EvalTables;
+use chess_engine::eval::{evaluate, evaluate_board, evaluate_with, trace, trace_with_network};
+use chess_engine::movegen::legal_check::is_other_king_attacked;
+use chess_engine::movegen::picker::MovePicker;
+
+fn random_network() -> Network {
+    let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+    let mut next = |range: i64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % (2 * range as u64 + 1)) as i64 - range
+    };
+    Network {
+        feature_weights: (0..INPUTS * HIDDEN).map(|_| next(40) as i16).collect(),
+        feature_bias: (0..HIDDEN).map(|_| next(80) as i16).collect(),
+        output_weights: (0..2 * HIDDEN).map(|_| next(127) as i8).collect(),
+        output_bias: next(10_000) as i32,
+    }
+}
+
+/// Walks all legal moves to `depth`, checking the accumulators pushed with the piece changes of
+/// each move against fresh ones.
+fn check_accumulators(board: &mut Board, accumulators: &mut AccumulatorStack, network: &Network, depth: u8) {
+    assert_eq!(accumulators.current(), Some(&Accumulator::new(board, network)), "Incremental accumulator: {}", board.to_fen());
+    if depth == 0 {
+        return;
+    }
+
+    let before = *accumulators.current().unwrap();
+    let mut picker = MovePicker::new();
+    while let Some(mv) = picker.next(board) {
+        let undo = board.make_move(mv);
+        if !is_other_king_attacked(board) {
+            accumulators.push(board, &undo.changes);
+            check_accumulators(board, accumulators, network, depth - 1);
+            accumulators.pop();
+        }
+        board.undo_move(undo);
+        assert_eq!(accumulators.current(), Some(&before), "Pop restores the accumulator");
+    }
+}
+
+#[test]
+fn test_nnue() {
+    let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let tables = EvalTables::default();
+
+    // Without a network the classical eval is used
+    let board = Board::from_fen(kiwipete);
+    assert_eq!(evaluate(&board, &tables, None), evaluate_board(&board, &tables), "Fallback to evaluate_board");
+    assert_eq!(AccumulatorStack::new(&board, None).current(), None);
+
+    let network = random_network();
+    let bytes = network.to_bytes();
+    assert_eq!(Network::from_bytes(&bytes).as_ref(), Ok(&network), "File round trip");
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    assert_eq!(Network::from_bytes(&bad_magic), Err(NnueError::BadMagic));
+    let mut bad_version = bytes.clone();
+    bad_version[4] = 99;
+    assert_eq!(Network::from_bytes(&bad_version), Err(NnueError::UnsupportedVersion(99)));
+    let mut bad_hidden = bytes.clone();
+    bad_hidden[12] = 0;
+    bad_hidden[13] = 2;
+    assert!(matches!(Network::from_bytes(&bad_hidden), Err(NnueError::ArchitectureMismatch { hidden: 512, .. })));
+    assert!(matches!(Network::from_bytes(&bytes[..bytes.len() - 1]), Err(NnueError::WrongSize { .. })));
+
+    let fens = [
+        kiwipete,
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        // Chess960 castling with the king staying put and the rook landing on the king's square
+        "1r4kr/pppppppp/8/8/8/8/PPPPPPPP/1R4KR w KQkq - 0 1",
+    ];
+    for fen in fens {
+        let mut board = Board::from_fen(fen);
+        let mut accumulators = AccumulatorStack::new(&board, Some(&network));
+        check_accumulators(&mut board, &mut accumulators, &network, 2);
+        assert_eq!(evaluate_with(&board, &tables, &accumulators), evaluate(&board, &tables, Some(&network)), "Stack and fresh eval: {}", fen);
+
+        // Plain reference of the output layer; `evaluate` is white relative
+        let us = board.side_to_move as usize;
+        let accumulator = Accumulator::new(&board, &network);
+        let values = accumulator.values[us].iter().chain(&accumulator.values[1 - us]);
+        let output: i32 = values
+            .zip(&network.output_weights)
+            .map(|(&v, &w)| (v as i32).clamp(0, QA) * w as i32)
+            .sum::<i32>() + network.output_bias;
+        let score = (output as i64 * SCALE as i64 / (QA * QB) as i64) as i32;
+        let expected = if board.side_to_move == Color::White { score } else { -score };
+        assert_eq!(evaluate(&board, &tables, Some(&network)), expected, "Network eval: {}", fen);
+    }
+
+    // The trace of the `eval` command reports the network's score next to the classical terms
+    let board = Board::from_fen(kiwipete);
+    assert_eq!(trace_with_network(&board, &tables, None), trace(&board, &tables));
+    let eval_trace = trace_with_network(&board, &tables, Some(&network));
+    assert_eq!(eval_trace.nnue, Some(evaluate(&board, &tables, Some(&network))));
+    assert!(eval_trace.to_string().contains("(white side, NNUE)"));
+
+    // Known endings are left to the endgame module
+    let board = Board::from_fen("8/8/8/4k3/8/8/8/4K3 w - - 0 1");
+    assert_eq!(evaluate(&board, &tables, Some(&network)), 0, "Bare kings");
+    assert_eq!(trace_with_network(&board, &tables, Some(&network)).nnue, None);
+}
+
+#[test]
+fn test_saturated_output() {
+    // Every activation clipped to QA against the largest output weights
+    let network = Network {
+        feature_weights: vec![0; INPUTS * HIDDEN],
+        feature_bias: vec![QA as i16; HIDDEN],
+        output_weights: vec![127; 2 * HIDDEN],
+        output_bias: 0,
+    };
+    let accumulator = Accumulator { values: [[i16::MAX; HIDDEN]; 2] };
+    let output = 2 * HIDDEN as i64 * QA as i64 * 127;
+    assert!(output * SCALE as i64 > i32::MAX as i64);
+    let expected = (output * SCALE as i64 / (QA * QB) as i64) as i32;
+    assert_eq!(network.evaluate(&accumulator, Color::White), expected);
+
+    let negative = Network { output_weights: vec![-128; 2 * HIDDEN], output_bias: -i32::MAX / 2, ..network };
+    let output = -2 * HIDDEN as i64 * QA as i64 * 128 - (i32::MAX / 2) as i64;
+    assert_eq!(negative.evaluate(&accumulator, Color::Black), (output * SCALE as i64 / (QA * QB) as i64) as i32);
+}
@@ -0,0 +1,160 @@
+use chess_engine::board::Board;
+use chess_engine::datagen::GameResult;
+use chess_engine::parsing::SanError;
+use chess_engine::pgn::{PgnError, PgnGame, PgnReader, LINE_WIDTH};
+use chess_engine::r#move::Move;
+
+const GAME: &str = r#"[Event "Casual \"blitz\""]
+[Site "?"]
+[White "Engine A"]
+[Black "Engine B"]
+[Result "1-0"]
+[ECO "C50"]
+
+{Opening comment} 1. e4 e5 2. Nf3 Nc6 3. Bc4!? {Italian} (3. Bb5 a6 (3... Nf6 4. O-O) 4. Ba4 $1)
+3... Bc5 4. c3 Nf6 5. d4 exd4 6. cxd4 Bb4+ 7. Nc3 Nxe4 8. O-O Nxc3 9. bxc3 Bxc3?
+; Too greedy
+10. Qb3 Bxa1 11. Bxf7+ Kf8 12. Bg5 Ne7 13. Ne5 d5 14. Bh5 1-0
+"#;
+
+#[test]
+fn test_parse_game() {
+    let game = PgnGame::parse(GAME).unwrap();
+    assert_eq!(game.tag("Event"), Some("Casual \"blitz\""));
+    assert_eq!(game.tag("ECO"), Some("C50"));
+    assert_eq!(game.result, "1-0");
+    assert_eq!(game.game_result(), Some(GameResult::WhiteWin));
+    assert_eq!(game.comment.as_deref(), Some("Opening comment"));
+    assert_eq!(game.moves.len(), 27);
+
+    let bc4 = &game.moves[4];
+    assert_eq!((bc4.nags.as_slice(), bc4.comment.as_deref()), (&[5u8][..], Some("Italian")));
+    assert_eq!(bc4.variations.len(), 1);
+    let ruy = &bc4.variations[0];
+    assert_eq!(ruy.iter().map(|m| m.mv.to_algebraic()).collect::<Vec<_>>(), ["f1b5", "a7a6", "b5a4"]);
+    assert_eq!(ruy[2].nags, [1]);
+    assert_eq!(ruy[1].variations[0].len(), 2, "Nested variation replaces 3... a6");
+    assert_eq!(game.moves[17].nags, [2]);
+    assert_eq!(game.moves[17].comment.as_deref(), Some("Too greedy"));
+
+    let mut board = game.start_board().unwrap();
+    for &mv in &game.mainline()[..26] {
+        board.make_move(mv);
+    }
+    assert_eq!(game.moves[26].mv.to_san(&board), "Bh5");
+    board.make_move(game.moves[26].mv);
+    assert_eq!(board.fullmove_number, 14);
+    assert_eq!(board.hash, game.end_board().unwrap().hash);
+}
+
+#[test]
+fn test_write_game() {
+    let game = PgnGame::parse(GAME).unwrap();
+    let text = game.to_pgn();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines[..8],
+        [
+            "[Event \"Casual \\\"blitz\\\"\"]",
+            "[Site \"?\"]",
+            "[Date \"????.??.??\"]",
+            "[Round \"?\"]",
+            "[White \"Engine A\"]",
+            "[Black \"Engine B\"]",
+            "[Result \"1-0\"]",
+            "[ECO \"C50\"]",
+        ]
+    );
+    assert_eq!(lines[8], "");
+    assert!(lines[9].starts_with("{Opening comment} 1. e4 e5 2. Nf3 Nc6 3. Bc4 $5 {Italian} (3. Bb5 a6 (3... Nf6"));
+    assert!(lines.iter().all(|line| line.len() <= LINE_WIDTH));
+    assert!(text.contains("$2 {Too greedy} 10. Qb3"));
+    assert!(text.ends_with("14. Bh5 1-0\n"));
+
+    // Writing is stable and loses nothing
+    assert_eq!(PgnGame::parse(&text).unwrap(), {
+        let mut expected = game.clone();
+        expected.set_tag("Date", "????.??.??");
+        expected.set_tag("Round", "?");
+        expected.tags.sort_by_key(|(name, _)| ["Event", "Site", "Date", "Round", "White", "Black", "Result"].iter().position(|n| n == name).unwrap_or(7));
+        expected
+    });
+}
+
+#[test]
+fn test_record_game() {
+    let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 40";
+    let mut game = PgnGame::new(Some(fen));
+    let mut board = Board::from_fen(fen);
+    for san in ["Kd7", "e4", "Kc6"] {
+        let mv = Move::from_san(san, &board).unwrap();
+        board.make_move(mv);
+        game.push(mv);
+    }
+    game.set_result(Some(GameResult::Draw));
+    let text = game.to_pgn();
+    assert!(text.contains("[Result \"1/2-1/2\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 40\"]\n"));
+    assert!(text.ends_with("\n40... Kd7 41. e4 Kc6 1/2-1/2\n"));
+    assert_eq!(PgnGame::parse(&text).unwrap().mainline(), game.mainline());
+
+    // Long games wrap without splitting tokens
+    let mut long = PgnGame::new(None);
+    let mut board = long.start_board().unwrap();
+    for _ in 0..20 {
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+            let mv = Move::from_san(san, &board).unwrap();
+            board.make_move(mv);
+            long.push(mv);
+        }
+    }
+    long.comment = Some("word ".repeat(40));
+    let text = long.to_pgn();
+    assert!(text.lines().all(|line| line.len() <= LINE_WIDTH));
+    assert_eq!(PgnGame::parse(&text).unwrap().moves, long.moves);
+}
+
+#[test]
+fn test_reader() {
+    let text = format!("{}\n[Event \"Second\"]\n\n1.d4 d5 {{a [bracket] comment\n[Not a tag]}} 2.c4 *\n\n1. e4 e5 0-1\n", GAME);
+    let games: Vec<PgnGame> = PgnReader::new(text.as_bytes()).map(Result::unwrap).collect();
+    assert_eq!(games.len(), 3, "A game without tags starts after the previous one's result");
+    assert_eq!(games[1].tag("Event"), Some("Second"));
+    assert_eq!(games[1].moves.len(), 3);
+    assert_eq!(games[1].result, "*");
+    assert_eq!(games[1].moves[1].comment.as_deref(), Some("a [bracket] comment [Not a tag]"));
+    assert_eq!((games[2].tags.len(), games[2].moves.len(), games[2].result.as_str()), (0, 2, "0-1"));
+
+    // Results inside comments do not end the game, one after the moves does
+    let text = "1. e4 {not over 1-0} e5 (1... c5 {0-1}) 2. Nf3 ; 0-1\n2... Nc6 1/2-1/2 1. d4 d5 1-0\n";
+    let games: Vec<PgnGame> = PgnReader::new(text.as_bytes()).map(Result::unwrap).collect();
+    assert_eq!(games.iter().map(|g| (g.moves.len(), g.result.as_str())).collect::<Vec<_>>(), [(4, "1/2-1/2"), (2, "1-0")]);
+
+    let games: Vec<_> = PgnReader::new("[Event \"A\"]\n1. e4 *\n[Event \"B\"]\n1. d4 1-0\n".as_bytes()).collect();
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[1].as_ref().unwrap().game_result(), Some(GameResult::WhiteWin));
+    assert_eq!(PgnReader::new("".as_bytes()).count(), 0);
+}
+
+#[test]
+fn test_pgn_errors() {
+    assert_eq!(
+        PgnGame::parse("1. e4 e5 2. Ke3 *"),
+        Err(PgnError::Move { ply: 2, san: "Ke3".to_string(), error: SanError::Illegal("Ke3".to_string()) })
+    );
+    assert_eq!(PgnGame::parse("1. e4 e5 2. Ke3 *").unwrap_err().to_string(), "move 2: illegal move 'Ke3'");
+    assert!(matches!(PgnGame::parse("1. e4 (1. d4 *"), Err(PgnError::Syntax(_))));
+    assert!(matches!(PgnGame::parse("1. e4 ) e5 *"), Err(PgnError::Syntax(_))));
+    assert!(matches!(PgnGame::parse("(1. d4) 1. e4 *"), Err(PgnError::Syntax(_))));
+    assert!(matches!(PgnGame::parse("[Event Unquoted]\n1. e4 *"), Err(PgnError::Syntax(_))));
+    assert!(matches!(PgnGame::parse("1. e4 $300 *"), Err(PgnError::Syntax(_))));
+    assert_eq!(PgnGame::parse("1. e4 1-0 1. d4 *"), Err(PgnError::Syntax("move text after the result".to_string())));
+
+    // Moves after the FEN's move number are counted from it
+    let error = PgnGame::parse("[FEN \"4k3/8/8/8/8/8/8/4K3 w - - 0 30\"]\n30. Kd1 Ke9 *").unwrap_err();
+    assert!(matches!(error, PgnError::Move { ply: 59, .. }), "{:?}", error);
+
+    let game = PgnGame::new(Some("4k3/8/8/8/8/8/8/4K3 w - e3 0 1"));
+    assert!(matches!(game.start_board(), Err(PgnError::Fen(_))));
+    assert!(matches!(game.end_board(), Err(PgnError::Fen(_))));
+    assert!(matches!(PgnGame::parse(&game.to_pgn()), Err(PgnError::Fen(_))));
+}
@@ -0,0 +1,77 @@
+use chess_engine::board::Board;
+use chess_engine::parsing::SanError;
+use chess_engine::r#move::Move;
+
+
+#[test]
+fn test_to_san() {
+    let cases = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2e4", "e4"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3", "Nf3"),
+        ("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "a1d1", "Rad1"),
+        ("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "h1d1", "Rhd1"),
+        ("4k3/8/8/R7/8/8/4K3/R7 w - - 0 1", "a1a3", "R1a3"),
+        ("4k3/8/8/8/8/Q7/4K3/Q1Q5 w - - 0 1", "a1b2", "Qa1b2"),
+        ("4k3/8/8/b7/8/8/3N4/4K1N1 w - - 0 1", "g1f3", "Nf3"), // The other knight is pinned
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", "exd6"),
+        ("8/4P3/8/8/8/8/k7/4K3 w - - 0 1", "e7e8q", "e8=Q"),
+        ("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1", "e7d8n", "exd8=N"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1", "O-O"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8", "O-O-O"),
+        ("5k2/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1", "O-O+"),
+        ("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1", "g1g8", "Qg8#"),
+        ("4k3/8/8/8/8/8/3q4/4K3 w - - 0 1", "e1d2", "Kxd2"),
+    ];
+    for (fen, uci, expected) in cases {
+        let board = Board::from_fen(fen);
+        let mv = Move::from_algebraic(uci, &board);
+        assert_eq!(mv.to_san(&board), expected, "{} in {}", uci, fen);
+        assert_eq!(Move::from_san(expected, &board), Ok(mv), "{} in {}", expected, fen);
+    }
+}
+
+#[test]
+fn test_san_round_trip() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ];
+    for fen in fens {
+        let board = Board::from_fen(fen);
+        let moves = board.legal_moves();
+        let mut sans: Vec<String> = moves.iter().map(|mv| mv.to_san(&board)).collect();
+        for (mv, san) in moves.iter().zip(&sans) {
+            assert_eq!(Move::from_san(san, &board), Ok(*mv), "{} in {}", san, fen);
+        }
+        sans.sort();
+        sans.dedup();
+        assert_eq!(sans.len(), moves.len(), "SAN is unique in {}", fen);
+    }
+}
+
+#[test]
+fn test_from_san_errors() {
+    let start = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let rooks = Board::from_fen("4k3/8/8/8/8/8/4K3/R6R w - - 0 1");
+    let promotion = Board::from_fen("8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+
+    for san in ["e5", "Nd2", "O-O", "exd3", "Ke2"] {
+        assert_eq!(Move::from_san(san, &start), Err(SanError::Illegal(san.to_string())), "{}", san);
+    }
+    assert_eq!(Move::from_san("Rd1", &rooks), Err(SanError::Ambiguous("Rd1".to_string())));
+    for san in ["", "Zz9", "e9", "Nf", "e8=K", "Ne8=Q", "e8=QQ", "Nb1c3d4"] {
+        assert_eq!(Move::from_san(san, &promotion), Err(SanError::Invalid(san.to_string())), "{:?}", san);
+    }
+    assert_eq!(Move::from_san("e7", &promotion), Err(SanError::Illegal("e7".to_string())));
+    assert!(Move::from_san("e8", &promotion).is_err(), "Promotion piece required");
+
+    // Common variations of the notation are accepted
+    assert_eq!(Move::from_san("Nf3!?", &start), Ok(Move::from_algebraic("g1f3", &start)));
+    assert_eq!(Move::from_san("e8Q+", &promotion), Ok(Move::from_algebraic("e7e8q", &promotion)));
+    assert_eq!(Move::from_san("e8=n", &promotion), Ok(Move::from_algebraic("e7e8n", &promotion)));
+    let castling = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    assert_eq!(Move::from_san("0-0-0", &castling), Ok(Move::from_algebraic("e1c1", &castling)));
+}
@@ -0,0 +1,238 @@
+use chess_engine::board::Board;
+use chess_engine::engine::Engine;
+use chess_engine::eval::endgame::material_key;
+use chess_engine::movegen::legal_check::is_other_king_attacked;
+use chess_engine::syzygy::table::TableInfo;
+use chess_engine::syzygy::{SyzygyError, Tablebases, Wdl};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+const TEST_TABLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/syzygy");
+// What the probed positions and their captures need, up to five pieces
+const TEST_TABLE_NAMES: [&str; 10] = ["KQvK", "KRvK", "KPvK", "KNvKN", "KRvKR", "KPvKP", "KPvKR", "KQRvK", "KRPvK", "KRPvKR"];
+
+/// The real tables in tests/data/syzygy, `None` until helper_scripts/fetch_syzygy_tables.sh
+/// has downloaded them.
+fn test_tables() -> Option<Tablebases> {
+    let files = TEST_TABLE_NAMES.iter().flat_map(|name| [format!("{}.rtbw", name), format!("{}.rtbz", name)]);
+    for file in files {
+        if !Path::new(TEST_TABLES).join(&file).exists() {
+            eprintln!("Skipped: {} is missing, run helper_scripts/fetch_syzygy_tables.sh", file);
+            return None;
+        }
+    }
+    Some(Tablebases::open(TEST_TABLES).unwrap())
+}
+
+#[test]
+fn test_table_names() {
+    let info = TableInfo::from_name("KRPvKR").unwrap();
+    assert_eq!(info.piece_count, 5);
+    assert!(info.has_pawns);
+    assert_eq!(info.key, material_key(&Board::from_fen("8/8/4k3/2r5/8/3PK3/8/R7 w - - 0 1")));
+    assert_eq!(info.key2, material_key(&Board::from_fen("8/8/4K3/2R5/8/3pk3/8/r7 w - - 0 1")));
+
+    let symmetric = TableInfo::from_name("KRvKR").unwrap();
+    assert_eq!(symmetric.key, symmetric.key2);
+    for name in ["KRKR", "KQvR", "KvKK", "KXvK", "KQQQQvKQQ"] {
+        assert!(TableInfo::from_name(name).is_none(), "Invalid name: {}", name);
+    }
+}
+
+#[test]
+fn test_open_errors() {
+    let dir = env::temp_dir().join(format!("yakari-syzygy-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(matches!(Tablebases::open(dir.to_str().unwrap()), Err(SyzygyError::Io(_))), "Missing directory");
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("README.txt"), "not a table").unwrap();
+    assert!(matches!(Tablebases::open(dir.to_str().unwrap()), Err(SyzygyError::NoTables(_))), "No tables");
+
+    // A broken file is found, but probing it fails instead of panicking
+    fs::write(dir.join("KQvK.rtbw"), [0_u8; 64]).unwrap();
+    let tables = Tablebases::open(dir.to_str().unwrap()).unwrap();
+    assert_eq!((tables.len(), tables.max_pieces()), (1, 3));
+    let mut board = Board::from_fen("8/8/4k3/8/8/4K3/8/Q7 w - - 0 1");
+    assert_eq!(tables.probe_wdl(&mut board), None, "Corrupt table");
+    assert_eq!(tables.probe_wdl(&mut Board::from_fen("8/8/4k3/8/8/4K3/8/R7 w - - 0 1")), None, "Missing table");
+    assert_eq!(tables.hits(), 0);
+
+    // Right magic, but the header stops short
+    fs::write(dir.join("KRvK.rtbw"), [0x71, 0xE8, 0x23, 0x5D, 0x01, 0x00]).unwrap();
+    let tables = Tablebases::open(dir.to_str().unwrap()).unwrap();
+    assert_eq!(tables.probe_wdl(&mut Board::from_fen("8/8/4k3/8/8/4K3/8/R7 w - - 0 1")), None, "Truncated table");
+
+    // Clearing SyzygyPath frees the old set
+    let mut engine = Engine::with_hash("Yakari".to_string(), "EiSiMo".to_string(), 1);
+    assert_eq!(engine.set_syzygy_path(dir.to_str().unwrap()).unwrap(), 2);
+    let old = Arc::downgrade(engine.tablebases.as_ref().unwrap());
+    engine.set_syzygy_path("<empty>").unwrap();
+    assert!(old.upgrade().is_none());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_probe_tables() {
+    let Some(tables) = test_tables() else { return };
+    assert_eq!((tables.len(), tables.max_pieces()), (10, 5));
+
+    let wdl = [
+        ("8/8/4k3/8/8/4K3/8/8 w - - 0 1", Wdl::Draw),
+        ("8/8/4k3/8/8/4K3/8/Q7 w - - 0 1", Wdl::Win),
+        ("8/8/4k3/8/8/4K3/8/Q7 b - - 0 1", Wdl::Loss),
+        ("8/8/8/8/8/1k6/8/KQ6 b - - 0 1", Wdl::Loss),
+        ("7K/8/8/8/8/8/2k5/1Q6 b - - 0 1", Wdl::Draw),
+        ("8/8/4K3/8/8/4k3/8/q7 b - - 0 1", Wdl::Win),
+        ("4k3/8/8/8/8/8/8/4K2R w - - 0 1", Wdl::Win),
+        ("7k/5K2/8/8/8/8/8/6r1 w - - 0 1", Wdl::Loss),
+        // Pawn table, both colors and every file slice
+        ("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", Wdl::Loss),
+        ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", Wdl::Win),
+        ("4k3/8/8/8/8/8/P7/K7 w - - 0 1", Wdl::Draw),
+        ("4k3/8/8/8/8/8/p7/K7 b - - 0 1", Wdl::Draw),
+        ("8/8/8/8/8/3k4/1p6/3K4 b - - 0 1", Wdl::Win),
+        ("8/2k5/8/8/8/8/6P1/6K1 w - - 0 1", Wdl::Win),
+        ("8/8/8/8/8/8/4k3/2K5 w - - 0 1", Wdl::Draw),
+        // Symmetric table, black to move is looked up with the colors swapped
+        ("6nk/8/3N2K1/8/8/8/8/8 w - - 0 1", Wdl::Win),
+        ("8/8/8/8/8/3n2k1/8/6NK b - - 0 1", Wdl::Win),
+        ("8/8/4k3/3n4/8/3NK3/8/8 w - - 0 1", Wdl::Draw),
+        ("8/8/4k3/3n4/8/3NK3/8/8 b - - 0 1", Wdl::Draw),
+        ("8/8/4k3/3r4/8/3RK3/8/8 w - - 0 1", Wdl::Draw),
+        // Pawns on both sides, five pieces
+        ("7k/5K1p/6P1/8/8/8/8/8 w - - 0 1", Wdl::Win),
+        ("k7/8/1K6/8/8/8/8/6QR w - - 0 1", Wdl::Win),
+        ("k7/8/1K6/8/6P1/8/7r/3R4 w - - 0 1", Wdl::Win),
+    ];
+    for (fen, expected) in wdl {
+        let mut board = Board::from_fen(fen);
+        let before = board;
+        assert_eq!(tables.probe_wdl(&mut board), Some(expected), "WDL: {}", fen);
+        assert_eq!(board, before, "Board restored: {}", fen);
+    }
+
+    // Castling rights are not covered, neither is material without a table
+    assert_eq!(tables.probe_wdl(&mut Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1")), None);
+    assert_eq!(tables.probe_wdl(&mut Board::from_fen("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1")), None);
+
+    // Distance to zeroing in plies, the same as the distance to mate without pawns
+    let dtz = [
+        ("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1", 1),
+        ("k7/8/1K6/8/8/8/8/6Q1 b - - 0 1", -2),
+        ("4k3/8/4K3/8/8/8/8/7R w - - 0 1", 1),
+        ("8/8/8/8/8/1k6/7q/K7 w - - 0 1", -2),
+        ("7k/5K1p/6P1/8/8/8/8/8 w - - 0 1", 1),
+        ("k7/8/1K6/8/8/8/8/6QR w - - 0 1", 1),
+        ("k7/8/1K6/8/6P1/8/7r/3R4 w - - 0 1", 1),
+        ("8/8/4k3/3r4/8/3RK3/8/8 w - - 0 1", 0),
+    ];
+    for (fen, expected) in dtz {
+        assert_eq!(tables.probe_dtz(&mut Board::from_fen(fen)), Some(expected), "DTZ: {}", fen);
+    }
+    let dtz = tables.probe_dtz(&mut Board::from_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1")).unwrap();
+    assert!(dtz > 0 && dtz < 100, "KPvK win within the 50 move rule: {}", dtz);
+    let dtz = tables.probe_dtz(&mut Board::from_fen("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1")).unwrap();
+    assert!(dtz < 0 && dtz > -100, "KPvK loss within the 50 move rule: {}", dtz);
+
+    // The longest wins are mates in 10 and 16 moves. Mirror images aside, the white king is
+    // in the a1-d1-d4 triangle.
+    for (piece, longest) in ['Q', 'R'].into_iter().zip([19, 31]) {
+        let mut max_dtz = 0;
+        let triangle = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+        for (white_king, black_king, sq) in triangle.into_iter().flat_map(|a| (0..64).flat_map(move |b| (0..64).map(move |c| (a, b, c)))) {
+            let (wk, bk) = (white_king as i32, black_king as i32);
+            if white_king == black_king || sq == white_king || sq == black_king || ((wk % 8 - bk % 8).abs() <= 1 && (wk / 8 - bk / 8).abs() <= 1) {
+                continue;
+            }
+            let mut board = Board::from_fen(&placement_fen(&[(white_king, 'K'), (black_king, 'k'), (sq, piece)], "w"));
+            if is_other_king_attacked(&board) {
+                continue;
+            }
+            max_dtz = max_dtz.max(tables.probe_dtz(&mut board).unwrap());
+        }
+        assert_eq!(max_dtz, longest, "Longest K{}vK win", piece);
+    }
+
+    // Mate in one is found by DTZ at the root
+    let mut mate = Board::from_fen("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1");
+    let root = tables.probe_root(&mut mate).unwrap();
+    assert_eq!((root.best_move.to_algebraic().as_str(), root.wdl, root.dtz), ("g1g8", Wdl::Win, 1));
+    let mut mate = Board::from_fen("k7/8/1K6/8/6P1/8/7r/3R4 w - - 0 1");
+    let root = tables.probe_root(&mut mate).unwrap();
+    assert_eq!((root.best_move.to_algebraic().as_str(), root.wdl, root.dtz), ("d1d8", Wdl::Win, 1));
+    assert!(tables.hits() > 0);
+}
+
+fn placement_fen(pieces: &[(usize, char)], side_to_move: &str) -> String {
+    let mut fen = String::new();
+    for rank in (0..8).rev() {
+        let mut empty = 0;
+        for file in 0..8 {
+            match pieces.iter().find(|&&(sq, _)| sq == rank * 8 + file) {
+                Some(&(_, c)) => {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen.push(c);
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if rank > 0 {
+            fen.push('/');
+        }
+    }
+    format!("{} {} - - 0 1", fen, side_to_move)
+}
+
+#[test]
+fn test_probe_corrupt_tables() {
+    if test_tables().is_none() {
+        return;
+    }
+    let dir = env::temp_dir().join(format!("yakari-syzygy-corrupt-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let positions = ["8/8/4k3/8/8/4K3/8/Q7 b - - 0 1", "8/8/3k4/8/8/8/8/K6R w - - 0 1", "8/8/4k3/8/8/4K3/8/Q7 w - - 0 1"];
+
+    // Garbled or cut off tables give no result, but never a panic
+    let names = ["KQvK.rtbw", "KQvK.rtbz", "KRvK.rtbw", "KRvK.rtbz"];
+    for name in names {
+        fs::copy(Path::new(TEST_TABLES).join(name), dir.join(name)).unwrap();
+    }
+    let mut state = 0x9E37_79B9_u32;
+    for name in names {
+        let bytes = fs::read(Path::new(TEST_TABLES).join(name)).unwrap();
+        let mut damaged: Vec<Vec<u8>> = [bytes.len() / 2, bytes.len() - 1].iter().map(|&cut| bytes[..cut].to_vec()).collect();
+        for _ in 0..40 {
+            let mut garbled = bytes.clone();
+            for _ in 0..8 {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                garbled[5 + state as usize % (bytes.len() - 5)] ^= (state >> 24) as u8 | 1;
+            }
+            damaged.push(garbled);
+        }
+        for table in damaged {
+            fs::write(dir.join(name), table).unwrap();
+            let tables = Tablebases::open(dir.to_str().unwrap()).unwrap();
+            for fen in positions {
+                let mut board = Board::from_fen(fen);
+                let _ = tables.probe_wdl(&mut board);
+                let _ = tables.probe_dtz(&mut board);
+            }
+        }
+        fs::write(dir.join(name), bytes).unwrap();
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
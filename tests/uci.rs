@@ -0,0 +1,56 @@
+use chess_engine::board::Board;
+use chess_engine::engine::{Engine, PositionError};
+use chess_engine::parsing::{FenError, MoveParseError};
+use chess_engine::r#move::Move;
+
+#[test]
+fn test_parse_uci_move() {
+    let cases = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2e4"),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8c8"),
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"),
+        ("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1", "e7d8n"),
+        ("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1", "e7e8q"),
+    ];
+    for (fen, uci) in cases {
+        let board = Board::from_fen(fen);
+        assert_eq!(board.parse_uci_move(uci), Ok(Move::from_algebraic(uci, &board)), "{} in {}", uci, fen);
+    }
+}
+
+#[test]
+fn test_parse_uci_move_errors() {
+    let board = Board::from_fen("r3k2r/8/8/8/8/8/4P3/R3K2R w Qkq - 0 1");
+    let invalid = ["", "e2", "e2e", "e2e4qq", "i2e4", "e0e4", "e2e9", "e2e4x", "e2e4Q", "0000", "E2E4"];
+    for s in invalid {
+        assert_eq!(board.parse_uci_move(s), Err(MoveParseError::Invalid(s.to_string())), "{:?}", s);
+    }
+    for s in ["e3e4", "a8a7", "h8h7"] {
+        assert_eq!(board.parse_uci_move(s), Err(MoveParseError::NoPiece(s.to_string())), "{}", s);
+    }
+    // Out of reach, castling without the right, and a promotion piece on a move that does not promote
+    for s in ["e2e5", "a1b2", "e1g1", "e2e3q"] {
+        assert_eq!(board.parse_uci_move(s), Err(MoveParseError::Illegal(s.to_string())), "{}", s);
+    }
+    assert!(board.parse_uci_move("e1c1").is_ok());
+    let promotion = Board::from_fen("8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+    assert_eq!(promotion.parse_uci_move("e7e8"), Err(MoveParseError::Illegal("e7e8".to_string())));
+    let pinned = Board::from_fen("4k3/8/8/b7/8/8/3N4/4K3 w - - 0 1");
+    assert_eq!(pinned.parse_uci_move("d2f3"), Err(MoveParseError::Illegal("d2f3".to_string())));
+}
+
+#[test]
+fn test_setpos_keeps_position_on_error() {
+    let mut engine = Engine::with_hash("Yakari".to_string(), "EiSiMo".to_string(), 1);
+    engine.setpos_startpos(&["e2e4", "e7e5"]).unwrap();
+    let after_e5 = engine.board.to_fen();
+    assert_eq!(engine.setpos_startpos(&["e2e4", "e2e4"]), Err(PositionError::Move(MoveParseError::NoPiece("e2e4".to_string()))));
+    assert_eq!(engine.setpos_startpos(&["e2e4", "e7"]), Err(PositionError::Move(MoveParseError::Invalid("e7".to_string()))));
+    assert_eq!(engine.setpos_fen("8/8/8/8/8/8/8/8 w - - 0 1", &[]), Err(PositionError::Fen(FenError::KingCount(chess_engine::board::Color::White, 0))));
+    assert_eq!(engine.board.to_fen(), after_e5);
+
+    engine.setpos_fen("4k3/8/8/8/8/8/8/RK6 w - - 0 1", &["a1a8", "e8e7"]).unwrap();
+    assert_eq!(engine.board.to_fen(), "R7/4k3/8/8/8/8/8/1K6 w - - 2 2");
+}